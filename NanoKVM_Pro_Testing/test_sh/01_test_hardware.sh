@@ -1,5 +1,7 @@
 #!/bin/bash
 
+. "$(dirname "$0")/result.sh"
+
 # 生成文件夹（防止自启）
 mkdir -p /root/factory_test/done
 mkdir -p /root/log
@@ -23,6 +25,11 @@ cp /root/NanoKVM_Pro_Testing/config /root/
 soc_id=$(cat /device_key)
 echo "SOC ID: $soc_id"
 
+# 结构化结果中上报的弹窗内容、串号和wifi模块
+PROMPT=""
+SERIAL=""
+WIFI_EXIST=false
+
 # 判断是否存在旧产测中的串号
 if [ -f /etc/test-kvm/serial ]; then
     # 存储到变量
@@ -47,8 +54,10 @@ if [ "$BOARD_SERIAL" == "Unknown" ]; then
         echo "不弹窗，直接开始"
     else
         # 维修硬件，弹窗是否从零开始
-        echo "弹窗内容：使用上次存储的串号，是否重新开始？"
-        echo "当前板卡的串号为：$TEST_SERIAL"
+        PROMPT="使用上次存储的串号，是否重新开始？"
+        SERIAL=$TEST_SERIAL
+        echo "弹窗内容：$PROMPT"
+        echo "当前板卡的串号为：$SERIAL"
     fi
     # 检测ATX/Desk版本
     echo "正在检测ATX/Desk版本..."
@@ -72,16 +81,22 @@ else
     if [ "$TEST_SERIAL" == "NULL" ]; then
         # 将当前板卡串号写入文件
         echo $BOARD_SERIAL > /etc/test-kvm/serial
-        echo "弹窗内容：疑似更换核心板，使用当前底板串号，是否从零开始？"
-        echo "当前板卡的串号为：$BOARD_SERIAL"
+        PROMPT="疑似更换核心板，使用当前底板串号，是否从零开始？"
+        SERIAL=$BOARD_SERIAL
+        echo "弹窗内容：$PROMPT"
+        echo "当前板卡的串号为：$SERIAL"
     else
         if [ "$BOARD_SERIAL" == "$TEST_SERIAL" ]; then
-            echo "弹窗内容：再次测试，是否从零开始产测？"
-            echo "当前板卡的串号为：$BOARD_SERIAL"
+            PROMPT="再次测试，是否从零开始产测？"
+            SERIAL=$BOARD_SERIAL
+            echo "弹窗内容：$PROMPT"
+            echo "当前板卡的串号为：$SERIAL"
         else
             echo $BOARD_SERIAL > /etc/test-kvm/serial
-            echo "弹窗内容：疑似更换核心板，使用当前底板串号，是否从零开始？"
-            echo "当前板卡的串号为：$BOARD_SERIAL"
+            PROMPT="疑似更换核心板，使用当前底板串号，是否从零开始？"
+            SERIAL=$BOARD_SERIAL
+            echo "弹窗内容：$PROMPT"
+            echo "当前板卡的串号为：$SERIAL"
         fi
     fi
     BOARD_TYPE=$(cat /proc/lt6911_info/version | awk -F'[()]' '{print $2}')
@@ -90,10 +105,12 @@ fi
 
 # 检测是否有wifi模块
 if [ -f /etc/test-kvm/wifi_exist ]; then
+    WIFI_EXIST=true
     echo "当前板卡有wifi模块"
 else
     # 通过ip a | grep wlan0判断是否存在wifi模块
     if ip a | grep -q "wlan0"; then
+        WIFI_EXIST=true
         echo "当前板卡有wifi模块"
         # 创建标记文件，避免重复检测
         touch /etc/test-kvm/wifi_exist
@@ -120,6 +137,8 @@ else
     echo "$output"
 fi
 
+report_result detect_hardware info "soc_id=$soc_id" "board_type=$BOARD_TYPE" "serial=$SERIAL" "prompt=$PROMPT" "wifi=$WIFI_EXIST"
+
 sync
 
 echo "Finish"
@@ -1,5 +1,7 @@
 #!/bin/bash
 
+. "$(dirname "$0")/result.sh"
+
 log_info()  { echo -e "\e[32m[INFO] $*\e[0m"; }
 log_warn()  { echo -e "\e[33m[WARN] $*\e[0m"; }
 log_error() { echo -e "\e[31m[ERROR] $*\e[0m"; }
@@ -93,8 +95,10 @@ emmc_test() {
 EMMC=emmc_test
 if $EMMC; then
     echo "eMMC test passed"
+    report_result emmc_test pass
 else
     echo "eMMC test failed"
+    report_result emmc_test fail defect:EMMC
 fi
 
 sync
@@ -1,5 +1,7 @@
 #!/bin/bash
 
+. "$(dirname "$0")/result.sh"
+
 FIRMWARE_DIR="/root/NanoKVM_Pro_Testing/firmware"
 KO_DIR="/root/NanoKVM_Pro_Testing/ko"
 SAVE_DIR="/root/factory_test/done"
@@ -22,10 +24,12 @@ deinit_vin() {
 test_all_io() {
     echo "=== 开始测试所有IO状态 ==="
     local all_tests_passed=true
+    IO_DEFECTS=()
 
     # 判断/proc/lt6911_info目录是否存在
     if [ ! -d /proc/lt6911_info ]; then
         echo "/proc/lt6911_info目录不存在"
+        IO_DEFECTS+=("defect:LT6911_DRIVER")
         all_tests_passed=false
         return 1 # 直接返回测试失败
     fi
@@ -36,6 +40,7 @@ test_all_io() {
         echo "LT86102 RST 引脚正常"
     else
         echo "LT86102 RST 引脚异常"
+        IO_DEFECTS+=("defect:LT86102_RST")
         all_tests_passed=false
     fi
     
@@ -45,6 +50,7 @@ test_all_io() {
         echo "LT6911 RST 引脚正常"
     else
         echo "LT6911 RST 引脚异常"
+        IO_DEFECTS+=("defect:LT6911_RST")
         all_tests_passed=false
     fi
     
@@ -54,6 +60,7 @@ test_all_io() {
         echo "LT86102 RX 引脚正常"
     else
         echo "LT86102 RX 引脚异常"
+        IO_DEFECTS+=("defect:LT86102_RX")
         all_tests_passed=false
     fi
     
@@ -63,6 +70,7 @@ test_all_io() {
         echo "LT86102 TX 引脚正常"
     else
         echo "LT86102 TX 引脚异常"
+        IO_DEFECTS+=("defect:LT86102_TX")
         all_tests_passed=false
     fi
     
@@ -72,6 +80,7 @@ test_all_io() {
         echo "LT6911 INT 引脚正常"
     else
         echo "LT6911 INT 引脚异常"
+        IO_DEFECTS+=("defect:LT6911_INT")
         all_tests_passed=false
     fi
     
@@ -82,12 +91,14 @@ test_all_io() {
         echo "LT6911 I2C 引脚正常"
     else
         echo "LT6911 I2C 引脚异常"
+        IO_DEFECTS+=("defect:LT6911_I2C")
         all_tests_passed=false
     fi
     if echo "$I2C_RESULT" | grep -q "38"; then
         echo "LT86102 I2C 引脚正常"
     else
         echo "LT86102 I2C 引脚异常"
+        IO_DEFECTS+=("defect:LT86102_I2C")
         all_tests_passed=false
     fi
 
@@ -186,11 +197,14 @@ case "$1" in
             if test_all_io; then
                 touch "$SAVE_DIR/.hdmi_io.done"
                 echo "HDMI IO test passed"
+                report_result hdmi_io_test pass
             else
                 echo "HDMI IO test failed"
+                report_result hdmi_io_test fail "${IO_DEFECTS[@]}"
             fi
         else
             echo "HDMI IO test passed"
+            report_result hdmi_io_test pass
         fi
         ;;
     vin)
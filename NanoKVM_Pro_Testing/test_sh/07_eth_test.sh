@@ -1,5 +1,8 @@
 #!/bin/bash
 
+. "$(dirname "$0")/result.sh"
+. "$(dirname "$0")/net_test.sh"
+
 # ./07_eth_test.sh download 500 "http://192.168.1.7:8080/download"
 # ./07_eth_test.sh upload 350 http://192.168.1.7:8080/upload
 
@@ -79,30 +82,62 @@ eth_upload_test() {
 case "$1" in
     download)
         if [ ! -f "$SAVE_DIR/.eth_download.done" ]; then
-            if eth_download_test $2 $3; then
+            if eth_download_test "$2" "$3"; then
                 touch "$SAVE_DIR/.eth_download.done"
                 echo "ETH download test passed"
+                report_result eth_download_test pass "speed_bps=$speed_result" "threshold_mbps=$2"
             else
                 echo "ETH download test failed"
+                report_result eth_download_test fail "speed_bps=$speed_result" "threshold_mbps=$2" defect:ETH_DOWNLOAD_SPEED
             fi
         else
             echo "ETH download test passed"
+            report_result eth_download_test pass
         fi
         ;;
     upload)
         if [ ! -f "$SAVE_DIR/.eth_upload.done" ]; then
-            if eth_upload_test $2 $3; then
+            if eth_upload_test "$2" "$3"; then
                 touch "$SAVE_DIR/.eth_upload.done"
                 echo "ETH upload test passed"
+                report_result eth_upload_test pass "speed_bps=$speed_result" "threshold_mbps=$2"
             else
                 echo "ETH upload test failed"
+                report_result eth_upload_test fail "speed_bps=$speed_result" "threshold_mbps=$2" defect:ETH_UPLOAD_SPEED
             fi
         else
             echo "ETH upload test passed"
+            report_result eth_upload_test pass
+        fi
+        ;;
+    tcp)
+        # ./07_eth_test.sh tcp upload 172.168.100.1 5201 5
+        if [ -f "$SAVE_DIR/.eth_$2.done" ]; then
+            echo "ETH $2 test passed"
+            report_result "eth_$2_test" pass
+        elif net_tcp_test "$3" "$4" "eth_$2_test" "$2" "$5"; then
+            touch "$SAVE_DIR/.eth_$2.done"
+            echo "ETH $2 test passed"
+        else
+            echo "ETH $2 test failed"
+        fi
+        ;;
+    udp)
+        # ./07_eth_test.sh udp 172.168.100.1 5201 200
+        if [ -f "$SAVE_DIR/.eth_udp.done" ]; then
+            echo "ETH UDP test passed"
+            report_result eth_udp_test pass
+        elif net_udp_test "$2" "$3" eth_udp_test "$4"; then
+            touch "$SAVE_DIR/.eth_udp.done"
+            echo "ETH UDP test passed"
+        else
+            echo "ETH UDP test failed"
         fi
         ;;
     *)
         echo "Usage: $0 <download|upload> spped url"
+        echo "       $0 tcp <upload|download|bidir> host port seconds"
+        echo "       $0 udp host port packets"
         ;;
 esac
 
@@ -1,5 +1,8 @@
 #!/bin/bash
 
+. "$(dirname "$0")/result.sh"
+. "$(dirname "$0")/net_test.sh"
+
 # ./07_eth_test.sh download 500 "http://192.168.1.7:8080/download"
 # ./07_eth_test.sh upload 350 http://192.168.1.7:8080/upload
 
@@ -119,40 +122,75 @@ case "$1" in
         # ./08_wifi_test.sh connect "NanoKVM_WiFi_Test_1" "nanokvmwifi"
         if [ -f "$SAVE_DIR/.wifi_download.done" ] && [ -f "$SAVE_DIR/.wifi_upload.done" ]; then
             echo "WiFi connect passed"
+            report_result wifi_wait_connection pass
         else
             if wifi_connect $2 $3; then
                 echo "WiFi connect passed"
+                report_result wifi_wait_connection pass "local_ip=$local_ip" "server_ip=$server_ip"
             else
                 echo "WiFi connect failed"
+                report_result wifi_wait_connection fail defect:WIFI_DHCP
             fi
         fi
         ;;
     download)
         if [ ! -f "$SAVE_DIR/.wifi_download.done" ]; then
-            if wifi_download_test $2 $3; then
+            if wifi_download_test "$2" "$3"; then
                 touch "$SAVE_DIR/.wifi_download.done"
                 echo "WiFi download test passed"
+                report_result wifi_download_test pass "speed_bps=$speed_result" "threshold_mbps=$2"
             else
                 echo "WiFi download test failed"
+                report_result wifi_download_test fail "speed_bps=$speed_result" "threshold_mbps=$2" defect:WIFI_DOWNLOAD_SPEED
             fi
         else
             echo "WiFi download test passed"
+            report_result wifi_download_test pass
         fi
         ;;
     upload)
         if [ ! -f "$SAVE_DIR/.wifi_upload.done" ]; then
-            if wifi_upload_test $2 $3; then
+            if wifi_upload_test "$2" "$3"; then
                 touch "$SAVE_DIR/.wifi_upload.done"
                 echo "WiFi upload test passed"
+                report_result wifi_upload_test pass "speed_bps=$speed_result" "threshold_mbps=$2"
             else
                 echo "WiFi upload test failed"
+                report_result wifi_upload_test fail "speed_bps=$speed_result" "threshold_mbps=$2" defect:WIFI_UPLOAD_SPEED
             fi
         else
             echo "WiFi upload test passed"
+            report_result wifi_upload_test pass
+        fi
+        ;;
+    tcp)
+        # ./08_wifi_test.sh tcp upload 172.168.100.1 5201 5
+        if [ -f "$SAVE_DIR/.wifi_$2.done" ]; then
+            echo "WiFi $2 test passed"
+            report_result "wifi_$2_test" pass
+        elif net_tcp_test "$3" "$4" "wifi_$2_test" "$2" "$5"; then
+            touch "$SAVE_DIR/.wifi_$2.done"
+            echo "WiFi $2 test passed"
+        else
+            echo "WiFi $2 test failed"
+        fi
+        ;;
+    udp)
+        # ./08_wifi_test.sh udp 172.168.100.1 5201 200
+        if [ -f "$SAVE_DIR/.wifi_udp.done" ]; then
+            echo "WiFi UDP test passed"
+            report_result wifi_udp_test pass
+        elif net_udp_test "$2" "$3" wifi_udp_test "$4"; then
+            touch "$SAVE_DIR/.wifi_udp.done"
+            echo "WiFi UDP test passed"
+        else
+            echo "WiFi UDP test failed"
         fi
         ;;
     *)
         echo "Usage: $0 <download|upload> spped url"
+        echo "       $0 tcp <upload|download|bidir> host port seconds"
+        echo "       $0 udp host port packets"
         ;;
 esac
 
@@ -0,0 +1,86 @@
+#!/bin/bash
+
+# 网络测试客户端，配合产测主机的网络测试服务（类似iperf），由07/08脚本source使用
+# 只使用bash的/dev/tcp和/dev/udp，不依赖iperf；结果由产测主机按配置阈值判断后以结构化结果行返回
+# 产测主机默认端口为5201，TCP和UDP共用
+
+# 不良代码前缀取自项目名，如 eth_upload_test 为 ETH_NET_TEST，wifi_udp_test 为 WIFI_NET_TEST
+net_defect() {
+    local prefix=${1%%_*}
+    echo "${prefix^^}_NET_TEST"
+}
+
+# 查询结果并输出结构化结果行，通过时返回0
+# 用法：net_result <主机> <端口> <项目> [收到的字节数 微秒数]
+net_result() {
+    local host=$1 port=$2 item=$3 line
+    shift 3
+    if ! exec 3<>"/dev/tcp/$host/$port"; then
+        report_result "$item" fail "defect:$(net_defect "$item")" "message:无法连接网络测试服务"
+        return 1
+    fi
+    echo "RESULT $item $*" >&3
+    read -r -t 10 line <&3
+    exec 3>&-
+    echo "$line"
+    [[ "$line" == *'"status":"pass"'* ]]
+}
+
+# TCP吞吐测试，upload为板卡发送，download为板卡接收，bidir为同时收发
+# 用法：net_tcp_test <主机> <端口> <项目> <upload|download|bidir> <秒数>
+net_tcp_test() {
+    local host=$1 port=$2 item=$3 mode=$4 seconds=$5 started bytes=""
+    if ! exec 3<>"/dev/tcp/$host/$port"; then
+        report_result "$item" fail "defect:$(net_defect "$item")" "message:无法连接网络测试服务"
+        return 1
+    fi
+    echo "TCP $item $mode $seconds" >&3
+    # 服务端到时关闭连接，timeout只是防止服务端异常时卡住
+    started=${EPOCHREALTIME/./}
+    case "$mode" in
+        upload)
+            timeout $((seconds + 10)) cat /dev/zero >&3 2>/dev/null
+            ;;
+        download)
+            bytes=$(timeout $((seconds + 10)) cat <&3 | wc -c)
+            ;;
+        bidir)
+            timeout $((seconds + 10)) cat /dev/zero >&3 2>/dev/null &
+            bytes=$(timeout $((seconds + 10)) cat <&3 | wc -c)
+            wait
+            ;;
+    esac
+    exec 3>&-
+    if [ -n "$bytes" ]; then
+        net_result "$host" "$port" "$item" "$bytes" $((${EPOCHREALTIME/./} - started))
+    else
+        net_result "$host" "$port" "$item"
+    fi
+}
+
+# UDP丢包和抖动测试，每个包内容为 "<项目> <序号> <发送时间微秒>"
+# 用法：net_udp_test <主机> <端口> <项目> <包数> [发包间隔秒]
+net_udp_test() {
+    local host=$1 port=$2 item=$3 count=$4 interval=${5:-0.01} reply i
+    if ! exec 3<>"/dev/tcp/$host/$port"; then
+        report_result "$item" fail "defect:$(net_defect "$item")" "message:无法连接网络测试服务"
+        return 1
+    fi
+    echo "UDP $item $count" >&3
+    read -r -t 5 reply <&3
+    exec 3>&-
+    if [ "$reply" != "OK" ]; then
+        report_result "$item" fail "defect:$(net_defect "$item")" "message:网络测试服务未就绪"
+        return 1
+    fi
+
+    exec 4>"/dev/udp/$host/$port"
+    for ((i = 0; i < count; i++)); do
+        printf '%s %d %s\n' "$item" "$i" "${EPOCHREALTIME/./}" >&4
+        sleep "$interval"
+    done
+    exec 4>&-
+    # 等待最后的包到达
+    sleep 1
+    net_result "$host" "$port" "$item"
+}
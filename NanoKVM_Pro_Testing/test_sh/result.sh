@@ -0,0 +1,38 @@
+#!/bin/bash
+
+# 结构化结果输出，由各测试脚本source使用，格式见 doc/ResultProtocol.md
+# 用法：report_result <项目> <pass|fail|info> [键=值 ...] [defect:不良代码 ...] [message:说明]
+# 例如：report_result hdmi_io_test fail defect:LT6911_I2C "message:I2C地址未响应"
+
+# JSON字符串转义
+json_escape() {
+    local s=$1
+    s=${s//\\/\\\\}
+    s=${s//\"/\\\"}
+    s=${s//$'\t'/\\t}
+    s=${s//$'\r'/}
+    s=${s//$'\n'/\\n}
+    printf '%s' "$s"
+}
+
+report_result() {
+    local item=$1
+    local status=$2
+    shift 2
+    local values="" defects="" message="" arg
+    for arg in "$@"; do
+        case "$arg" in
+            defect:*)
+                defects+="${defects:+,}\"$(json_escape "${arg#defect:}")\""
+                ;;
+            message:*)
+                message=$(json_escape "${arg#message:}")
+                ;;
+            *=*)
+                values+="${values:+,}\"$(json_escape "${arg%%=*}")\":\"$(json_escape "${arg#*=}")\""
+                ;;
+        esac
+    done
+    printf '@@RESULT {"item":"%s","status":"%s","values":{%s},"defects":[%s],"message":"%s"}\n' \
+        "$(json_escape "$item")" "$status" "$values" "$defects" "$message"
+}
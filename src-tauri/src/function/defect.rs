@@ -0,0 +1,84 @@
+// 不良处理：还没有分配串号的板卡打印不良时，以时间命名保存一份记录，关联串口日志和开机分析结果，维修时按时间查找
+// 以及两个测试程序共用的串口登录失败处理
+use std::time::Duration;
+use chrono::Local;
+use tauri::AppHandle;
+
+use crate::function::save::{set_test_status, set_test_log};
+use crate::function::serial::capture::link_console_capture;
+use crate::function::serial::boot::link_boot_reports;
+use crate::function::serial::power::power_cycle;
+use crate::function::serial::script::LoginFailure;
+use crate::function::dialog_test::show_dialog_and_wait;
+use crate::function::printer::{generate_defects_image_with_params, print_image, PRINTER_ENABLE, TARGET_PRINTER};
+
+// 不良记录文件名前缀，不会与串号冲突，也不会进入上传队列
+const DEFECT_RECORD_PREFIX: &str = "defect_";
+// 账户锁定后断电重启的断电时间
+const POWER_CYCLE_OFF_MS: u64 = 1000;
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = true;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[defect]{}", msg);
+    }
+}
+
+// 保存不良记录并关联当前的串口日志和开机记录，开机故障在打印不良前写入
+pub fn save_defect_record(defect: &str) {
+    let now = Local::now();
+    let record = format!("{}{}", DEFECT_RECORD_PREFIX, now.format("%Y%m%d_%H%M%S"));
+    let saved = set_test_status(&record, "test_pass", "false")
+        .and_then(|_| set_test_log(&record, &now.format("%Y-%m-%d").to_string(), "defect", defect));
+    if let Err(e) = saved {
+        log(&format!("保存不良记录 {} 失败: {}", record, e));
+        return;
+    }
+    link_console_capture(&record);
+    link_boot_reports(&record);
+    log(&format!("不良记录: {}，{}", record, defect));
+}
+
+// 串口登录被拒绝（密码错误或账户锁定）：弹窗让操作员选择再次检测或直接打印不良
+// add_error_msg 追加错误信息并返回需要打印的完整内容，两个测试程序各自记录错误信息
+// 返回true表示已打印不良，false表示需要重新检测；账户锁定时先断电重启解除锁定
+pub async fn handle_login_failure(app_handle: AppHandle, failure: LoginFailure, add_error_msg: impl FnOnce(&str) -> String) -> bool {
+    let message = match failure {
+        LoginFailure::Locked => "⚠️ 串口登录失败：账户已被锁定，请断电重启KVM后再次检测",
+        _ => "⚠️ 串口登录失败：用户名或密码错误，请检查config.toml中[credentials]的配置是否与固件一致",
+    };
+    log(message);
+    let response = show_dialog_and_wait(app_handle, message.to_string(), vec![
+        serde_json::json!({ "text": "再次检测" }),
+        serde_json::json!({ "text": "直接打印不良" })
+    ]);
+    if response == "直接打印不良" {
+        log("用户选择了直接打印不良");
+        save_defect_record(message);
+        let error_msg = add_error_msg("串口登录失败，请检查固件账户密码 | ");
+        if !error_msg.is_empty() {
+            log(&format!("测试过程中出现错误: {}", error_msg));
+            // 生成错误图片
+            let img = generate_defects_image_with_params(&error_msg);
+            if PRINTER_ENABLE {
+                if let Err(e) = print_image(&img, Some(TARGET_PRINTER)) {
+                    log(&format!("打印图像失败: {}", e));
+                }
+            }
+        }
+        // 等待弹窗消失500ms
+        std::thread::sleep(Duration::from_millis(500));
+        return true;
+    }
+    log("用户选择了再次检测");
+    // 等待弹窗消失500ms
+    std::thread::sleep(Duration::from_millis(500));
+    // 账户锁定需要断电重启才能解除
+    if failure == LoginFailure::Locked {
+        let _ = power_cycle(POWER_CYCLE_OFF_MS).await;
+    }
+    false
+}
@@ -10,3 +10,6 @@ pub mod hdmi;
 pub mod wifi_ap;
 pub mod static_eth;
 pub mod upload;
+pub mod transcript;
+pub mod script_result;
+pub mod defect;
@@ -1,14 +1,23 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
 use toml;
-use std::sync::OnceLock;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::SystemTime;
 use chrono::Local;
 use chrono::Datelike;
 
+/// 凭据文件名，位于config目录
+const SECRETS_FILE_NAME: &str = "secrets.toml";
+
 /// 全局存储应用程序根路径
 static APP_ROOT: OnceLock<PathBuf> = OnceLock::new();
 
+/// JSON记录写锁，读-改-写记录的函数在持锁期间完成，避免并发的测试任务互相覆盖修改
+static RECORD_LOCK: Mutex<()> = Mutex::new(());
+
 /// 应用程序配置（[application] 部分）
 #[derive(Deserialize, Debug)]
 pub struct ApplicationConfig {
@@ -25,6 +34,322 @@ pub struct TestingConfig {
     pub eth_down_speed: u32,
     pub wifi_up_speed: u32,
     pub wifi_down_speed: u32,
+    #[serde(default = "default_eth_test_mb")]
+    pub eth_test_mb: u32,
+    #[serde(default = "default_wifi_test_mb")]
+    pub wifi_test_mb: u32,
+    #[serde(default = "default_net_test")]
+    pub net_test: String,
+    #[serde(default = "default_net_test_seconds")]
+    pub net_test_seconds: u32,
+    #[serde(default = "default_udp_packets")]
+    pub udp_packets: u32,
+    #[serde(default = "default_udp_max_loss")]
+    pub udp_max_loss: f64,
+    #[serde(default = "default_udp_max_jitter_ms")]
+    pub udp_max_jitter_ms: f64,
+}
+
+fn default_eth_test_mb() -> u32 {
+    50
+}
+
+fn default_wifi_test_mb() -> u32 {
+    10
+}
+
+fn default_net_test() -> String {
+    "tcp".to_string()
+}
+
+fn default_net_test_seconds() -> u32 {
+    5
+}
+
+fn default_udp_packets() -> u32 {
+    200
+}
+
+fn default_udp_max_loss() -> f64 {
+    1.0
+}
+
+fn default_udp_max_jitter_ms() -> f64 {
+    5.0
+}
+
+/// 串口工具配置（[[serial.tool]] 部分），按顺序匹配，排在前面的优先
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SerialToolConfig {
+    #[serde(default)]
+    pub name: String,
+    pub vid: u16,
+    pub pid: u16,
+    #[serde(default)]
+    pub serial_number: Option<String>,  // USB序列号，为空时不检查
+    #[serde(default = "default_baud_rate")]
+    pub baud_rate: u32,
+    #[serde(default = "default_flow_control")]
+    pub flow_control: String,           // "none" / "software" / "hardware"
+}
+
+fn default_baud_rate() -> u32 {
+    115200
+}
+
+fn default_flow_control() -> String {
+    "none".to_string()
+}
+
+/// 串口配置（[serial] 部分）
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SerialConfig {
+    #[serde(default)]
+    pub port: String,                   // 指定串口名（如"COM5"），为空时按工具列表自动扫描
+    #[serde(default = "default_serial_tools", rename = "tool")]
+    pub tools: Vec<SerialToolConfig>,
+}
+
+fn default_serial_tools() -> Vec<SerialToolConfig> {
+    vec![SerialToolConfig {
+        name: "CH343".to_string(),
+        vid: 0x1a86,
+        pid: 0x55d3,
+        serial_number: None,
+        baud_rate: default_baud_rate(),
+        flow_control: default_flow_control(),
+    }]
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        SerialConfig { port: String::new(), tools: default_serial_tools() }
+    }
+}
+
+/// 待测板卡登录凭据（[credentials] 部分），串口登录和SSH共用
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CredentialsConfig {
+    #[serde(default = "default_username")]
+    pub username: String,
+    #[serde(default = "default_password")]
+    pub password: String,
+}
+
+fn default_username() -> String {
+    "root".to_string()
+}
+
+fn default_password() -> String {
+    "sipeed".to_string()
+}
+
+impl Default for CredentialsConfig {
+    fn default() -> Self {
+        CredentialsConfig { username: default_username(), password: default_password() }
+    }
+}
+
+/// 文件服务器配置（[server] 部分），板卡访问本机的URL都由这里生成
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ServerConfig {
+    #[serde(default = "default_server_host")]
+    pub host: String,                   // 本机在测试网络中的地址，同时用作以太网静态IP
+    #[serde(default = "default_server_bind")]
+    pub bind: String,                   // 监听地址
+    #[serde(default = "default_server_port")]
+    pub port: u16,                      // 文件服务器端口
+    #[serde(default = "default_net_test_port")]
+    pub net_test_port: u16,             // 网络测试服务端口，TCP和UDP共用
+    #[serde(default)]
+    pub app_dir: String,                // 产测包目录，为空时使用应用程序根目录下的app，相对路径相对于根目录
+    #[serde(default)]
+    pub package: String,                // 默认产测包文件名，为空时使用目录中按文件名排序的第一个包
+}
+
+fn default_server_host() -> String {
+    "172.168.100.1".to_string()
+}
+
+fn default_server_bind() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_server_port() -> u16 {
+    8080
+}
+
+fn default_net_test_port() -> u16 {
+    5201
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: default_server_host(),
+            bind: default_server_bind(),
+            port: default_server_port(),
+            net_test_port: default_net_test_port(),
+            app_dir: String::new(),
+            package: String::new(),
+        }
+    }
+}
+
+/// MES服务器配置（[mes] 部分），令牌和密码保存在单独的 secrets.toml 中
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MesConfig {
+    #[serde(default = "default_mes_base_url")]
+    pub base_url: String,               // 接口地址前缀，如 "https://maixvision.sipeed.com/api/v1/nanokvm"
+    #[serde(default)]
+    pub ca_cert: String,                // 自定义CA证书（PEM），相对路径相对于config目录，为空时只使用系统证书
+    #[serde(default)]
+    pub proxy: String,                  // 代理地址，如 "http://10.0.0.1:7890"，为空时不使用代理
+    #[serde(default = "default_mes_timeout_secs")]
+    pub timeout_secs: u64,              // 单次请求超时时间，单位秒
+}
+
+fn default_mes_base_url() -> String {
+    "https://maixvision.sipeed.com/api/v1/nanokvm".to_string()
+}
+
+fn default_mes_timeout_secs() -> u64 {
+    30
+}
+
+impl Default for MesConfig {
+    fn default() -> Self {
+        MesConfig {
+            base_url: default_mes_base_url(),
+            ca_cert: String::new(),
+            proxy: String::new(),
+            timeout_secs: default_mes_timeout_secs(),
+        }
+    }
+}
+
+/// MES凭据（secrets.toml 的 [mes] 部分）
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct MesSecrets {
+    #[serde(default)]
+    pub token: String,                  // 请求头 token
+    #[serde(default)]
+    pub passwd: String,                 // 上传测试结果时的请求头 passwd
+}
+
+/// Webhook凭据（secrets.toml 的 [webhook] 部分）
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct WebhookSecrets {
+    #[serde(default)]
+    pub token: String,                  // 不为空时作为 Authorization: Bearer 发送
+}
+
+/// secrets.toml 文件结构
+#[derive(Deserialize, Debug, Default)]
+struct SecretsFile {
+    #[serde(default)]
+    mes: MesSecrets,
+    #[serde(default)]
+    webhook: WebhookSecrets,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// MES输出配置（[sinks.mes] 部分），接口地址等在 [mes] 中
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MesSinkConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_true")]
+    pub required: bool,
+}
+
+impl Default for MesSinkConfig {
+    fn default() -> Self {
+        MesSinkConfig { enabled: true, required: true }
+    }
+}
+
+/// 本地SQLite存档配置（[sinks.sqlite] 部分）
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SqliteSinkConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_true")]
+    pub required: bool,
+    #[serde(default = "default_sqlite_path")]
+    pub path: String,                   // 数据库文件，相对路径相对于data目录
+}
+
+fn default_sqlite_path() -> String {
+    "results.db".to_string()
+}
+
+impl Default for SqliteSinkConfig {
+    fn default() -> Self {
+        SqliteSinkConfig { enabled: false, required: true, path: default_sqlite_path() }
+    }
+}
+
+/// 共享文件夹输出配置（[sinks.folder] 部分）
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FolderSinkConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_true")]
+    pub required: bool,
+    #[serde(default)]
+    pub path: String,                   // 目录，如 "\\\\nas\\nanokvm"
+    #[serde(default = "default_folder_format")]
+    pub format: String,                 // "json" / "csv" / "both"
+}
+
+fn default_folder_format() -> String {
+    "json".to_string()
+}
+
+impl Default for FolderSinkConfig {
+    fn default() -> Self {
+        FolderSinkConfig { enabled: false, required: true, path: String::new(), format: default_folder_format() }
+    }
+}
+
+/// Webhook输出配置（[sinks.webhook] 部分），令牌在 secrets.toml 中
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct WebhookSinkConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_true")]
+    pub required: bool,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default = "default_webhook_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_webhook_timeout_secs() -> u64 {
+    10
+}
+
+impl Default for WebhookSinkConfig {
+    fn default() -> Self {
+        WebhookSinkConfig { enabled: false, required: true, url: String::new(), timeout_secs: default_webhook_timeout_secs() }
+    }
+}
+
+/// 结果输出配置（[sinks] 部分），每个输出单独启用；required的输出全部接收后记录才算上传完成
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct SinksConfig {
+    #[serde(default)]
+    pub mes: MesSinkConfig,
+    #[serde(default)]
+    pub sqlite: SqliteSinkConfig,
+    #[serde(default)]
+    pub folder: FolderSinkConfig,
+    #[serde(default)]
+    pub webhook: WebhookSinkConfig,
 }
 
 /// 完整配置结构
@@ -79,12 +404,99 @@ pub struct TestLog {
     pub entries: std::collections::HashMap<String, TestLogEntry>,
 }
 
+/// 串口控制台日志记录，文件位于save目录，与JSON同级
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ConsoleLog {
+    pub started: String,
+    pub raw: String,
+    pub clean: String,
+}
+
+/// 开机过程中检测到的故障
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct BootFault {
+    pub kind: String,       // "panic" / "oops" / "mmc" / "watchdog" / "unexpected_reset"
+    pub at_ms: u64,         // 距开机起点的时间
+    pub line: String,       // 触发检测的控制台行
+}
+
+/// 一次开机的分析结果，时间均为距开机起点（上电或复位）的毫秒数
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct BootReport {
+    pub started: String,            // 开机起点的本地时间
+    pub trigger: String,            // 开机起点："power_cycle" / "reset" / "console"（未经本程序上电，从第一行日志开始计时）
+    pub uboot_ms: Option<u64>,      // 出现U-Boot标识
+    pub kernel_ms: Option<u64>,     // 内核开始启动
+    pub login_ms: Option<u64>,      // 出现登录提示
+    pub faults: Vec<BootFault>,
+}
+
+/// 一次SSH命令的输出记录，完整输出保存在save目录的文本文件中
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct CommandLog {
+    pub item: String,               // 测试项目，与前端按钮id一致
+    pub command: String,
+    pub started: String,            // 开始执行的本地时间
+    pub duration_ms: u64,
+    pub exit_status: Option<i32>,   // 连接失败或超时时为空
+    pub transcript: String,         // 输出文件名，文件位于save目录
+}
+
+/// 一次网速测试的结果，客户端为板卡上curl的测量值，服务端为本机文件服务器的测量值，单位Mbps
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SpeedMeasurement {
+    pub item: String,               // 测试项目，一般与前端按钮id一致，如 "eth_upload_test"；SFTP推送产测包为 "package_push"
+    pub threshold_mbps: f64,
+    pub client_mbps: Option<f64>,   // 板卡已通过测试时不再测速，为空
+    pub server_mbps: Option<f64>,
+    pub server_bytes: u64,
+    pub server_ms: u64,
+    #[serde(default)]
+    pub method: String,             // "http" / "sftp" / "tcp" / "udp"
+    #[serde(default)]
+    pub loss_percent: Option<f64>,  // 仅UDP
+    #[serde(default)]
+    pub jitter_ms: Option<f64>,     // 仅UDP
+}
+
+/// 待上传文件
+#[derive(Debug, Clone)]
+pub struct UnuploadedFile {
+    pub name: String,
+    pub path: PathBuf,
+    pub modified: SystemTime,       // 进入待上传队列的时间
+}
+
+/// 一个结果输出的投递情况
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SinkDelivery {
+    pub accepted: bool,
+    pub attempts: u32,
+    pub last_attempt: String,
+    #[serde(default)]
+    pub accepted_at: String,
+    #[serde(default)]
+    pub last_error: String,
+}
+
 /// 完整的JSON数据结构体
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct TestData {
     pub device_info: DeviceInfo,
     pub test_content: TestContent,
     pub test_log: TestLog,
+    #[serde(default)]
+    pub console_log: Vec<ConsoleLog>,
+    #[serde(default)]
+    pub boot_log: Vec<BootReport>,
+    #[serde(default)]
+    pub command_log: Vec<CommandLog>,
+    #[serde(default)]
+    pub artifacts: Vec<String>,     // 从板卡取回的文件，路径相对save目录
+    #[serde(default)]
+    pub speed_test: Vec<SpeedMeasurement>,
+    #[serde(default)]
+    pub deliveries: BTreeMap<String, SinkDelivery>,    // 按结果输出名称记录投递情况
 }
 
 /// 在 AppData\Local 下初始化应用程序数据目录结构
@@ -117,6 +529,7 @@ pub fn init_appdata(app_name: &str) -> Result<PathBuf, Box<dyn std::error::Error
     
     // 4. 创建默认配置文件
     create_default_config()?;
+    create_default_secrets()?;
     
     println!("\n✅ 目录结构初始化完成！");
     println!("📁 根目录: {}", app_root.display());
@@ -139,6 +552,7 @@ fn create_directory_structure(app_root: &Path, app_name: &str) -> Result<(), Box
         app_root.join("config"),
         app_root.join("data").join("unuploaded"),
         app_root.join("data").join("save"),
+        app_root.join("data").join("quarantine"),
         app_root.join("app"),
     ];
     
@@ -175,6 +589,7 @@ pub fn cp_to_unuploaded(serial: &str) -> Result<(), Box<dyn std::error::Error>>
     // 构建源文件和目标文件路径
     let save_path = root_path.join("data").join("save").join(format!("{}.json", serial));
     let unuploaded_path = root_path.join("data").join("unuploaded").join(format!("{}.json", serial));
+    let _guard = lock_records();
     
     // 检查源文件是否存在
     if !save_path.exists() {
@@ -187,13 +602,15 @@ pub fn cp_to_unuploaded(serial: &str) -> Result<(), Box<dyn std::error::Error>>
     
     // 设置unuploaded为true（复制到unuploaded文件夹意味着没有上传）
     test_data.device_info.unuploaded = true;
+    // 重新测试后记录内容已变化，清除上一次的投递情况，各输出重新接收
+    test_data.deliveries.clear();
     
     // 保存更新后的数据到源文件
     let updated_content = serde_json::to_string_pretty(&test_data)?;
-    fs::write(&save_path, &updated_content)?;
+    write_atomic(&save_path, &updated_content)?;
     
-    // 复制到unuploaded目录
-    fs::write(&unuploaded_path, &updated_content)?;
+    // 复制到unuploaded目录，上传线程无法解析的文件会被隔离，不能让它读到写了一半的文件
+    write_atomic(&unuploaded_path, &updated_content)?;
     
     Ok(())
 }
@@ -212,23 +629,24 @@ pub fn rm_from_unuploaded(file_name: &str) -> Result<(), Box<dyn std::error::Err
     // 构建源文件和目标文件路径
     let save_path = root_path.join("data").join("save").join(format!("{}", file_name));
     let unuploaded_path = root_path.join("data").join("unuploaded").join(format!("{}", file_name));
+    let _guard = lock_records();
     
-    // 检查save目录中的文件是否存在
-    if !save_path.exists() {
-        return Err(format!("save目录中的文件不存在: {}", save_path.display()).into());
+    // save目录中的文件不存在时只删除unuploaded中的文件，避免已上传的记录反复上传
+    if save_path.exists() {
+        // 读取源文件内容
+        let content = fs::read_to_string(&save_path)?;
+        let mut test_data: TestData = serde_json::from_str(&content)?;
+        
+        // 设置unuploaded为false（从unuploaded文件夹删除意味着已上传）
+        test_data.device_info.unuploaded = false;
+        
+        // 保存更新后的数据到源文件
+        let updated_content = serde_json::to_string_pretty(&test_data)?;
+        write_atomic(&save_path, &updated_content)?;
+    } else {
+        eprintln!("✗ save目录中的文件不存在: {}", save_path.display());
     }
     
-    // 读取源文件内容
-    let content = fs::read_to_string(&save_path)?;
-    let mut test_data: TestData = serde_json::from_str(&content)?;
-    
-    // 设置unuploaded为false（从unuploaded文件夹删除意味着已上传）
-    test_data.device_info.unuploaded = false;
-    
-    // 保存更新后的数据到源文件
-    let updated_content = serde_json::to_string_pretty(&test_data)?;
-    fs::write(&save_path, &updated_content)?;
-    
     // 删除unuploaded目录中的文件（如果存在）
     if unuploaded_path.exists() {
         fs::remove_file(&unuploaded_path)?;
@@ -237,52 +655,76 @@ pub fn rm_from_unuploaded(file_name: &str) -> Result<(), Box<dyn std::error::Err
     Ok(())
 }
 
-pub fn get_unuploaded_num() -> u32 {
-    match get_app_root() {
-        Ok(root_path) => {
-            let unuploaded_path = root_path.join("data").join("unuploaded");
-            match fs::read_dir(unuploaded_path) {
-                Ok(entries) => {
-                    entries.filter(|e| {
-                        if let Ok(entry) = e.as_ref() {
-                            if let Ok(file_type) = entry.file_type() {
-                                return file_type.is_file();
-                            }
-                        }
-                        false
-                    }).count() as u32
-                },
-                Err(_) => 0
-            }
-        },
-        Err(_) => 0
-    }
-}
-
-pub fn get_one_unuploaded_file_path() -> Option<String> {
-    match get_app_root() {
-        Ok(root_path) => {
-            let unuploaded_path = root_path.join("data").join("unuploaded");
-            match fs::read_dir(&unuploaded_path) {
-                Ok(entries) => {
-                    for entry in entries {
-                        if let Ok(entry) = entry {
-                            if let Ok(file_type) = entry.file_type() {
-                                if file_type.is_file() {
-                                    if let Ok(file_name) = entry.file_name().into_string() {
-                                        let file_path = unuploaded_path.join(&file_name);
-                                        return Some(file_path.to_string_lossy().to_string());
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    None
-                },
-                Err(_) => None
-            }
-        },
-        Err(_) => None
+/// 获取待上传文件，按修改时间从旧到新排序，时间相同时按文件名排序
+/// 
+/// # 返回
+/// - unuploaded目录中的所有文件，应用程序未初始化或目录不存在时为空
+pub fn get_unuploaded_files() -> Vec<UnuploadedFile> {
+    let unuploaded_path = match get_app_root() {
+        Ok(root_path) => root_path.join("data").join("unuploaded"),
+        Err(_) => return Vec::new(),
+    };
+    let mut files: Vec<UnuploadedFile> = match fs::read_dir(&unuploaded_path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                // 跳过正在写入的临时文件
+                if name.ends_with(".tmp") {
+                    return None;
+                }
+                let modified = entry.metadata().and_then(|metadata| metadata.modified()).unwrap_or(SystemTime::now());
+                Some(UnuploadedFile { path: entry.path(), name, modified })
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.name.cmp(&b.name)));
+    files
+}
+
+/// 将无法上传的文件从unuploaded目录移入quarantine目录，并在旁边写入原因
+/// 
+/// # 参数
+/// - `file_name`: unuploaded目录中的文件名
+/// - `reason`: 移入的原因，写入同名的 .reason.txt 文件
+/// 
+/// # 返回
+/// - `Ok(PathBuf)` 移入后的文件路径，重名时文件名后加时间
+/// - `Err(错误信息)` 如果移动失败
+pub fn quarantine_unuploaded(file_name: &str, reason: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let root_path = get_app_root()?;
+    let unuploaded_path = root_path.join("data").join("unuploaded").join(file_name);
+    let quarantine_dir = root_path.join("data").join("quarantine");
+    fs::create_dir_all(&quarantine_dir)?;
+    
+    let mut target_path = quarantine_dir.join(file_name);
+    if target_path.exists() {
+        target_path = quarantine_dir.join(format!("{}.{}", file_name, Local::now().format("%Y%m%d%H%M%S")));
+    }
+    fs::rename(&unuploaded_path, &target_path)?;
+    
+    let mut reason_path = target_path.clone().into_os_string();
+    reason_path.push(".reason.txt");
+    fs::write(&reason_path, format!("{}\n{}\n", Local::now().format("%Y-%m-%d %H:%M:%S"), reason))?;
+    
+    Ok(target_path)
+}
+
+/// 获取quarantine目录中的记录数量，不计原因文件
+pub fn get_quarantine_num() -> u32 {
+    let quarantine_path = match get_app_root() {
+        Ok(root_path) => root_path.join("data").join("quarantine"),
+        Err(_) => return 0,
+    };
+    match fs::read_dir(quarantine_path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+            .filter(|entry| !entry.file_name().to_string_lossy().ends_with(".reason.txt"))
+            .count() as u32,
+        Err(_) => 0,
     }
 }
 
@@ -310,6 +752,205 @@ fn create_default_config() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
+/// 创建默认凭据文件，只有当前用户可以读写
+fn create_default_secrets() -> Result<(), Box<dyn std::error::Error>> {
+    let secrets_file = get_config_file(SECRETS_FILE_NAME)?;
+    
+    if secrets_file.exists() {
+        println!("📄 凭据文件已存在: {}", get_relative_path(&secrets_file, ""));
+        return Ok(());
+    }
+    
+    let secrets_content = r#"# MES凭据，不要提交到代码仓库或复制到其他位置
+# 修改后下一次上传时生效，不需要重启
+
+[mes]
+token = ""              # 请求头 token
+passwd = ""             # 上传测试结果时的请求头 passwd
+
+[webhook]
+token = ""              # 不为空时作为 Authorization: Bearer 发送
+"#;
+    
+    match fs::write(&secrets_file, secrets_content) {
+        Ok(_) => {
+            restrict_permissions(&secrets_file)?;
+            println!("📄 创建凭据文件: {}", get_relative_path(&secrets_file, ""));
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("✗ 创建凭据文件失败: {}", e);
+            Err(format!("创建凭据文件失败: {}", e).into())
+        }
+    }
+}
+
+/// 设置文件只有当前用户可以读写
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(())
+}
+
+/// 设置文件只有当前用户可以读写：替换为只包含当前用户的DACL，并且不继承上级目录的权限
+#[cfg(windows)]
+fn restrict_permissions(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use std::ptr::null_mut;
+    use winapi::shared::winerror::ERROR_SUCCESS;
+    use winapi::um::accctrl::{EXPLICIT_ACCESS_W, NO_INHERITANCE, NO_MULTIPLE_TRUSTEE, SET_ACCESS, SE_FILE_OBJECT, TRUSTEE_IS_SID, TRUSTEE_IS_USER, TRUSTEE_W};
+    use winapi::um::aclapi::{SetEntriesInAclW, SetNamedSecurityInfoW};
+    use winapi::um::winbase::LocalFree;
+    use winapi::um::winnt::{DACL_SECURITY_INFORMATION, FILE_ALL_ACCESS, PACL, PROTECTED_DACL_SECURITY_INFORMATION};
+
+    let user = windows_acl::current_user()?;
+    let mut access = EXPLICIT_ACCESS_W {
+        grfAccessPermissions: FILE_ALL_ACCESS,
+        grfAccessMode: SET_ACCESS,
+        grfInheritance: NO_INHERITANCE,
+        Trustee: TRUSTEE_W {
+            pMultipleTrustee: null_mut(),
+            MultipleTrusteeOperation: NO_MULTIPLE_TRUSTEE,
+            TrusteeForm: TRUSTEE_IS_SID,
+            TrusteeType: TRUSTEE_IS_USER,
+            ptstrName: user.sid() as *mut u16,
+        },
+    };
+    let mut wide = windows_acl::wide_path(path);
+    unsafe {
+        let mut acl: PACL = null_mut();
+        let result = SetEntriesInAclW(1, &mut access, null_mut(), &mut acl);
+        if result != ERROR_SUCCESS {
+            return Err(format!("创建访问控制列表失败: {}", result).into());
+        }
+        let result = SetNamedSecurityInfoW(wide.as_mut_ptr(), SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+            null_mut(), null_mut(), acl, null_mut());
+        LocalFree(acl as *mut _);
+        if result != ERROR_SUCCESS {
+            return Err(format!("设置 {} 的访问权限失败: {}", path.display(), result).into());
+        }
+    }
+    Ok(())
+}
+
+/// 检查文件是否可以被其他用户读取
+#[cfg(unix)]
+fn is_readable_by_others(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).map(|metadata| metadata.permissions().mode() & 0o077 != 0).unwrap_or(false)
+}
+
+/// 检查文件是否可以被其他用户读取：DACL中有允许读取的项不属于当前用户、SYSTEM或管理员组时视为可以读取
+/// 没有DACL表示所有人都可以访问；文件不存在时交给读取时报错
+#[cfg(windows)]
+fn is_readable_by_others(path: &Path) -> bool {
+    use std::ptr::null_mut;
+    use winapi::shared::winerror::ERROR_SUCCESS;
+    use winapi::um::accctrl::SE_FILE_OBJECT;
+    use winapi::um::aclapi::GetNamedSecurityInfoW;
+    use winapi::um::securitybaseapi::{EqualSid, GetAce, GetAclInformation, IsWellKnownSid};
+    use winapi::um::winbase::LocalFree;
+    use winapi::um::winnt::{AclSizeInformation, WinBuiltinAdministratorsSid, WinLocalSystemSid,
+        ACCESS_ALLOWED_ACE, ACCESS_ALLOWED_ACE_TYPE, ACL_SIZE_INFORMATION, DACL_SECURITY_INFORMATION,
+        FILE_READ_DATA, GENERIC_ALL, GENERIC_READ, PACL, PSECURITY_DESCRIPTOR, PSID};
+
+    if !path.exists() {
+        return false;
+    }
+    let user = match windows_acl::current_user() {
+        Ok(user) => user,
+        Err(e) => {
+            eprintln!("✗ 无法获取当前用户: {}", e);
+            return true;
+        }
+    };
+    let wide = windows_acl::wide_path(path);
+    unsafe {
+        let mut dacl: PACL = null_mut();
+        let mut descriptor: PSECURITY_DESCRIPTOR = null_mut();
+        let result = GetNamedSecurityInfoW(wide.as_ptr(), SE_FILE_OBJECT, DACL_SECURITY_INFORMATION,
+            null_mut(), null_mut(), &mut dacl, null_mut(), &mut descriptor);
+        if result != ERROR_SUCCESS {
+            eprintln!("✗ 读取 {} 的访问权限失败: {}", path.display(), result);
+            return true;
+        }
+        let mut readable = dacl.is_null();
+        let mut size: ACL_SIZE_INFORMATION = std::mem::zeroed();
+        if !readable && GetAclInformation(dacl, &mut size as *mut ACL_SIZE_INFORMATION as *mut _,
+            std::mem::size_of::<ACL_SIZE_INFORMATION>() as u32, AclSizeInformation) != 0 {
+            for index in 0..size.AceCount {
+                let mut ace = null_mut();
+                if GetAce(dacl, index, &mut ace) == 0 {
+                    continue;
+                }
+                let ace = &*(ace as *const ACCESS_ALLOWED_ACE);
+                if ace.Header.AceType != ACCESS_ALLOWED_ACE_TYPE || ace.Mask & (FILE_READ_DATA | GENERIC_READ | GENERIC_ALL) == 0 {
+                    continue;
+                }
+                let sid = &ace.SidStart as *const u32 as PSID;
+                if EqualSid(sid, user.sid()) == 0
+                    && IsWellKnownSid(sid, WinLocalSystemSid) == 0
+                    && IsWellKnownSid(sid, WinBuiltinAdministratorsSid) == 0 {
+                    readable = true;
+                    break;
+                }
+            }
+        }
+        LocalFree(descriptor);
+        readable
+    }
+}
+
+/// Windows访问控制的辅助函数
+#[cfg(windows)]
+mod windows_acl {
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr::null_mut;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcessToken};
+    use winapi::um::securitybaseapi::GetTokenInformation;
+    use winapi::um::winnt::{TokenUser, PSID, TOKEN_QUERY, TOKEN_USER};
+
+    /// 当前进程的用户，SID指向buffer内部
+    pub struct CurrentUser {
+        buffer: Vec<u64>,
+    }
+
+    impl CurrentUser {
+        pub fn sid(&self) -> PSID {
+            unsafe { (*(self.buffer.as_ptr() as *const TOKEN_USER)).User.Sid }
+        }
+    }
+
+    /// 读取当前进程令牌中的用户
+    pub fn current_user() -> Result<CurrentUser, String> {
+        unsafe {
+            let mut token = null_mut();
+            if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+                return Err(std::io::Error::last_os_error().to_string());
+            }
+            let mut length = 0u32;
+            GetTokenInformation(token, TokenUser, null_mut(), 0, &mut length);
+            // 按8字节对齐分配，TOKEN_USER中包含指针
+            let mut buffer = vec![0u64; (length as usize).div_ceil(8)];
+            let ok = GetTokenInformation(token, TokenUser, buffer.as_mut_ptr() as *mut _, length, &mut length);
+            let error = std::io::Error::last_os_error();
+            CloseHandle(token);
+            if ok == 0 {
+                return Err(error.to_string());
+            }
+            Ok(CurrentUser { buffer })
+        }
+    }
+
+    /// 以0结尾的UTF-16路径
+    pub fn wide_path(path: &Path) -> Vec<u16> {
+        path.as_os_str().encode_wide().chain(std::iter::once(0)).collect()
+    }
+}
+
 /// 生成默认配置文件内容
 fn generate_default_config() -> String {
     let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
@@ -329,6 +970,66 @@ eth_up_speed = 300      # 测试以太网上传速度，单位Mbps
 eth_down_speed = 500    # 测试以太网下载速度，单位Mbps
 wifi_up_speed = 10      # 测试WiFi上传速度，单位Mbps
 wifi_down_speed = 10    # 测试WiFi下载速度，单位Mbps
+eth_test_mb = 50        # 以太网下载测速的数据量，单位MB
+wifi_test_mb = 10       # WiFi下载测速的数据量，单位MB
+net_test = "tcp"        # 网速测试方式，可选 "tcp"（内置网络测试服务）或 "http"（curl下载上传）
+net_test_seconds = 5    # TCP测速时长，单位秒
+udp_packets = 200       # UDP丢包和抖动测试的包数
+udp_max_loss = 1.0      # UDP允许的最大丢包率，单位%
+udp_max_jitter_ms = 5.0 # UDP允许的最大抖动，单位ms
+
+[serial]
+port = ""               # 指定串口名，如"COM5"；留空时按下方工具列表自动扫描
+
+# USB串口工具列表，按顺序匹配，可添加多个
+[[serial.tool]]
+name = "CH343"
+vid = 0x1a86
+pid = 0x55d3
+# serial_number = ""    # 可选，指定USB序列号，同时插入多个相同工具时使用
+baud_rate = 115200
+flow_control = "none"   # 流控，可选 "none"、"software" 或 "hardware"
+
+[credentials]
+username = "root"       # 待测KVM登录用户名，串口登录和SSH共用
+password = "sipeed"     # 待测KVM登录密码
+
+[mes]
+base_url = "https://maixvision.sipeed.com/api/v1/nanokvm"   # MES接口地址，测试环境时修改为测试服务器
+ca_cert = ""            # 自定义CA证书（PEM），相对于config目录，留空时只使用系统证书
+proxy = ""              # 代理地址，如 "http://10.0.0.1:7890"，留空时不使用代理
+timeout_secs = 30       # 请求超时时间，单位秒
+# token和passwd保存在同目录的secrets.toml中，只有当前用户可以读取
+
+# 测试记录的输出，可同时启用多个；required = true 的输出全部接收后记录才从待上传中移除
+[sinks.mes]
+enabled = true          # 上传到上面[mes]配置的MES
+required = true
+
+[sinks.sqlite]
+enabled = false         # 本地SQLite存档
+required = true
+path = "results.db"     # 相对于data目录
+
+[sinks.folder]
+enabled = false         # 写入共享文件夹
+required = true
+path = ""               # 如 '\\nas\nanokvm'
+format = "json"         # 可选 "json"、"csv" 或 "both"
+
+[sinks.webhook]
+enabled = false         # 以JSON POST到指定地址，令牌在secrets.toml的[webhook]中
+required = true
+url = ""
+timeout_secs = 10
+
+[server]
+host = "172.168.100.1"  # 本机在测试网络中的地址，板卡通过该地址下载产测包，同时用作以太网静态IP
+bind = "0.0.0.0"        # 文件服务器监听地址
+port = 8080             # 文件服务器端口
+net_test_port = 5201    # 网络测试服务端口，TCP和UDP共用
+app_dir = ""            # 产测包目录，留空时使用程序目录下的app
+package = ""            # 默认产测包文件名，留空时使用目录中按文件名排序的第一个.tar
 
 # 注意：修改配置后需要重启应用程序生效
 "#,
@@ -337,6 +1038,18 @@ wifi_down_speed = 10    # 测试WiFi下载速度，单位Mbps
     )
 }
 
+/// 获取config目录中的文件路径
+/// 
+/// # 参数
+/// - `name`: 文件名或相对于config目录的路径，绝对路径原样返回
+/// 
+/// # 返回
+/// - `Ok(PathBuf)` 文件路径
+/// - `Err(错误信息)` 如果应用程序未初始化
+pub fn get_config_file(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_app_root()?.join("config").join(name))
+}
+
 /// 获取全局应用程序根路径
 /// 
 /// # 返回
@@ -419,12 +1132,130 @@ pub fn get_config_str(section: &str, key: &str) -> Option<String> {
             "eth_down_speed" => Some(config.testing.eth_down_speed.to_string()),
             "wifi_up_speed" => Some(config.testing.wifi_up_speed.to_string()),
             "wifi_down_speed" => Some(config.testing.wifi_down_speed.to_string()),
+            "eth_test_mb" => Some(config.testing.eth_test_mb.to_string()),
+            "wifi_test_mb" => Some(config.testing.wifi_test_mb.to_string()),
+            "net_test" => Some(config.testing.net_test),
+            "net_test_seconds" => Some(config.testing.net_test_seconds.to_string()),
+            "udp_packets" => Some(config.testing.udp_packets.to_string()),
+            "udp_max_loss" => Some(config.testing.udp_max_loss.to_string()),
+            "udp_max_jitter_ms" => Some(config.testing.udp_max_jitter_ms.to_string()),
             _ => None,
         },
         _ => None,
     }
 }
 
+/// 读取config.toml中的一个部分，其他部分缺失或有误时不影响该部分
+/// 
+/// # 参数
+/// - `name`: 部分名称，如 "serial"
+/// 
+/// # 返回
+/// - 解析后的配置；应用程序未初始化、配置文件不存在、没有该部分或解析失败时返回默认配置
+fn load_section<T: DeserializeOwned + Default>(name: &str) -> T {
+    let config_content = match get_app_root() {
+        Ok(app_root) => fs::read_to_string(app_root.join("config").join("config.toml")).unwrap_or_default(),
+        Err(_) => return T::default(),
+    };
+    
+    let section = match toml::from_str::<toml::Table>(&config_content) {
+        Ok(mut table) => table.remove(name),
+        Err(e) => {
+            eprintln!("✗ 解析配置文件失败，[{}] 使用默认配置: {}", name, e);
+            None
+        }
+    };
+    match section.map(|value| value.try_into::<T>()) {
+        Some(Ok(config)) => config,
+        Some(Err(e)) => {
+            eprintln!("✗ 解析 [{}] 配置失败，使用默认配置: {}", name, e);
+            T::default()
+        }
+        None => T::default(),
+    }
+}
+
+/// 获取串口工具配置
+/// 
+/// # 返回
+/// - 配置文件中的 [serial] 部分；应用程序未初始化、配置文件不存在或没有该部分时返回默认的CH343配置
+pub fn get_serial_config() -> SerialConfig {
+    load_section("serial")
+}
+
+/// 获取待测板卡登录凭据
+/// 
+/// # 返回
+/// - 配置文件中的 [credentials] 部分；应用程序未初始化、配置文件不存在或没有该部分时返回默认的root/sipeed
+pub fn get_credentials() -> CredentialsConfig {
+    load_section("credentials")
+}
+
+/// 获取文件服务器配置
+/// 
+/// # 返回
+/// - 配置文件中的 [server] 部分；应用程序未初始化、配置文件不存在或没有该部分时返回默认配置
+pub fn get_server_config() -> ServerConfig {
+    load_section("server")
+}
+
+/// 获取MES服务器配置
+/// 
+/// # 返回
+/// - 配置文件中的 [mes] 部分；应用程序未初始化、配置文件不存在或没有该部分时返回默认配置
+pub fn get_mes_config() -> MesConfig {
+    load_section("mes")
+}
+
+/// 读取MES凭据，每次调用都重新读取文件，更换凭据后不需要重启
+/// 
+/// # 返回
+/// - `Ok(MesSecrets)` 如果读取成功
+/// - `Err(错误信息)` 如果文件不存在、格式错误或可以被其他用户读取
+pub fn get_mes_secrets() -> Result<MesSecrets, Box<dyn std::error::Error>> {
+    Ok(read_secrets()?.mes)
+}
+
+/// 读取Webhook凭据
+/// 
+/// # 返回
+/// - `Ok(WebhookSecrets)` 如果读取成功，没有 [webhook] 部分时令牌为空
+/// - `Err(错误信息)` 如果文件不存在、格式错误或可以被其他用户读取
+pub fn get_webhook_secrets() -> Result<WebhookSecrets, Box<dyn std::error::Error>> {
+    Ok(read_secrets()?.webhook)
+}
+
+/// 读取并检查凭据文件
+fn read_secrets() -> Result<SecretsFile, Box<dyn std::error::Error>> {
+    let secrets_file = get_config_file(SECRETS_FILE_NAME)?;
+    if is_readable_by_others(&secrets_file) {
+        return Err(format!("凭据文件 {} 可以被其他用户读取，请改为只允许当前用户读写（Linux下为600）", secrets_file.display()).into());
+    }
+    let content = fs::read_to_string(&secrets_file)
+        .map_err(|e| format!("读取凭据文件 {} 失败: {}", secrets_file.display(), e))?;
+    Ok(toml::from_str(&content)?)
+}
+
+/// 获取结果输出配置
+/// 
+/// # 返回
+/// - 配置文件中的 [sinks] 部分；应用程序未初始化、配置文件不存在或没有该部分时只启用MES
+pub fn get_sinks_config() -> SinksConfig {
+    load_section("sinks")
+}
+
+/// 获取data目录中的文件路径
+/// 
+/// # 参数
+/// - `name`: 文件名或相对于data目录的路径，绝对路径原样返回
+/// 
+/// # 返回
+/// - `Ok(PathBuf)` 文件路径
+/// - `Err(错误信息)` 如果应用程序未初始化
+pub fn get_data_file(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_app_root()?.join("data").join(name))
+}
+
 /// 设置测试状态
 /// 
 /// # 参数
@@ -440,6 +1271,7 @@ pub fn set_test_status(serial: &str, item: &str, status: &str) -> Result<(), Box
     
     // 构建JSON文件路径
     let json_path = root_path.join("data").join("save").join(format!("{}.json", serial));
+    let _guard = lock_records();
     
     // 读取现有数据或创建新数据
     let mut test_data = if json_path.exists() {
@@ -511,25 +1343,37 @@ pub fn set_test_status(serial: &str, item: &str, status: &str) -> Result<(), Box
     
     // 保存数据到JSON文件
     let json_content = serde_json::to_string_pretty(&test_data)?;
-    fs::write(&json_path, json_content)?;
+    write_atomic(&json_path, &json_content)?;
     
     Ok(())
 }
 
-/// 检测应用程序根目录下的app文件夹是否为空或不存在
+/// 获取产测包目录
+/// 
+/// # 返回
+/// - `Some(PathBuf)` 配置 [server] 中的app_dir，为空时为应用程序根目录下的app
+/// - `None` 如果应用程序未初始化
+pub fn get_app_dir() -> Option<PathBuf> {
+    let root_path = get_app_root().ok()?;
+    let app_dir = get_server_config().app_dir;
+    if app_dir.is_empty() {
+        Some(root_path.join("app"))
+    } else {
+        Some(root_path.join(app_dir))
+    }
+}
+
+/// 检测产测包目录是否为空或不存在
 /// 
 /// # 返回
 /// - `false` 如果app文件夹存在且不为空
 /// - `true` 如果app文件夹不存在或为空
 pub fn is_app_folder_empty() -> bool {
-    // 获取应用程序根路径
-    let root_path = match get_app_root() {
-        Ok(path) => path,
-        Err(_) => return true,
-    };
-    
     // 构建app文件夹路径
-    let app_folder_path = root_path.join("app");
+    let app_folder_path = match get_app_dir() {
+        Some(path) => path,
+        None => return true,
+    };
     
     // 检查文件夹是否存在
     if !app_folder_path.exists() {
@@ -554,47 +1398,41 @@ pub fn is_app_folder_empty() -> bool {
     }
 }
 
-// 获取app文件夹内tar结尾的文件路径，比如获取出来的内容如下：
+// 获取默认产测包的路径：配置了package时使用该文件，否则使用按文件名排序的第一个包，比如获取出来的内容如下：
 // file_path = "C:\\Users\\BuGu\\AppData\\Local\\NanoKVM-Testing\\app\\NanoKVM_Pro_Testing_V2_0.tar";
+// 没有找到时返回空路径
 pub fn get_app_file_path() -> PathBuf {
-    // 获取应用程序根路径
-    let root_path = match get_app_root() {
-        Ok(path) => path,
-        Err(_) => return PathBuf::new(),
-    };
-    
-    // 构建app文件夹路径
-    let app_dir = root_path.join("app");
-    
-    // 检查app文件夹是否存在
-    if !app_dir.exists() {
-        return PathBuf::new();
+    let packages = get_app_packages();
+    let package = get_server_config().package;
+    if package.is_empty() {
+        return packages.into_iter().next().unwrap_or_default();
     }
-    
-    // 读取app文件夹内的文件
-    match fs::read_dir(&app_dir) {
-        Ok(entries) => {
-            for entry in entries {
-                if let Ok(entry) = entry {
-                    let path = entry.path();
-                    
-                    // 检查是否为文件且以.tar结尾
-                    if path.is_file() && path.extension().map_or(false, |ext| ext == "tar") {
-                        return path;
-                    }
-                }
-            }
-            
-            // 如果没有找到.tar文件，返回空路径
-            PathBuf::new()
-        }
-        Err(_) => {
-            // 读取文件夹失败，返回空路径
+    match packages.into_iter().find(|path| path.file_name().is_some_and(|file_name| file_name == package.as_str())) {
+        Some(path) => path,
+        None => {
+            eprintln!("✗ 产测包目录中没有配置的产测包: {}", package);
             PathBuf::new()
         }
     }
 }
 
+// 获取产测包目录内所有tar结尾的文件路径，按文件名排序，用于文件服务器的包列表
+pub fn get_app_packages() -> Vec<PathBuf> {
+    let app_dir = match get_app_dir() {
+        Some(path) => path,
+        None => return Vec::new(),
+    };
+    let mut packages: Vec<PathBuf> = match fs::read_dir(&app_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "tar"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    packages.sort();
+    packages
+}
+
 /// 获取测试状态
 /// 
 /// # 参数
@@ -657,12 +1495,12 @@ pub fn get_test_status(serial: &str, item: &str) -> Result<String, Box<dyn std::
 /// # 返回
 /// - `Ok(())` 如果设置成功
 /// - `Err(错误信息)` 如果设置失败
-#[allow(dead_code)]
 pub fn set_test_log(serial: &str, date: &str, item: &str, log: &str) -> Result<(), Box<dyn std::error::Error>> {
     let root_path = get_app_root()?;
     
     // 构建JSON文件路径
     let json_path = root_path.join("data").join("save").join(format!("{}.json", serial));
+    let _guard = lock_records();
     
     // 读取现有数据或创建新数据
     let mut test_data = if json_path.exists() {
@@ -688,7 +1526,236 @@ pub fn set_test_log(serial: &str, date: &str, item: &str, log: &str) -> Result<(
     
     // 保存数据到JSON文件
     let json_content = serde_json::to_string_pretty(&test_data)?;
-    fs::write(&json_path, json_content)?;
+    write_atomic(&json_path, &json_content)?;
+    
+    Ok(())
+}
+
+/// 获取save目录路径
+/// 
+/// # 返回
+/// - `Ok(PathBuf)` save目录路径（不存在时自动创建）
+/// - `Err(错误信息)` 如果应用程序未初始化或创建失败
+pub fn get_save_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let save_path = get_app_root()?.join("data").join("save");
+    if !save_path.exists() {
+        fs::create_dir_all(&save_path)?;
+    }
+    Ok(save_path)
+}
+
+/// 获取单块板卡的记录目录，存放从板卡取回的文件
+/// 
+/// # 参数
+/// - `serial`: 设备序列号，作为目录名
+/// 
+/// # 返回
+/// - `Ok(PathBuf)` save目录下以串号命名的目录（不存在时自动创建）
+/// - `Err(错误信息)` 如果应用程序未初始化或创建失败
+pub fn get_record_dir(serial: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let record_path = get_save_dir()?.join(serial);
+    if !record_path.exists() {
+        fs::create_dir_all(&record_path)?;
+    }
+    Ok(record_path)
+}
+
+/// 获取JSON记录写锁，持锁线程panic后继续使用
+fn lock_records() -> MutexGuard<'static, ()> {
+    RECORD_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 先写入同目录下的临时文件再重命名，读取方不会看到写了一半的文件
+/// 
+/// # 参数
+/// - `path`: 目标文件路径，已存在时被替换
+/// - `content`: 文件内容
+/// 
+/// # 返回
+/// - `Ok(())` 如果写入成功
+/// - `Err(错误信息)` 如果写入或重命名失败
+fn write_atomic(path: &Path, content: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+    fs::write(&temp_path, content)?;
+    if let Err(e) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// 读取并修改JSON记录后保存
+/// 
+/// # 参数
+/// - `serial`: 设备序列号，作为JSON文件名的索引
+/// - `update`: 修改记录的内容，记录不存在时传入只有串号的新记录
+/// 
+/// # 返回
+/// - `Ok(())` 如果保存成功
+/// - `Err(错误信息)` 如果读取或保存失败
+fn update_record(serial: &str, update: impl FnOnce(&mut TestData)) -> Result<(), Box<dyn std::error::Error>> {
+    let json_path = get_save_dir()?.join(format!("{}.json", serial));
+    let _guard = lock_records();
+    
+    // 读取现有数据或创建新数据
+    let mut test_data = if json_path.exists() {
+        let content = fs::read_to_string(&json_path)?;
+        serde_json::from_str(&content)?
+    } else {
+        let mut data = TestData::default();
+        data.device_info.serial = serial.to_string();
+        data
+    };
+    
+    update(&mut test_data);
+    
+    // 保存数据到JSON文件
+    let json_content = serde_json::to_string_pretty(&test_data)?;
+    write_atomic(&json_path, &json_content)?;
+    
+    Ok(())
+}
+
+/// 在JSON记录中登记从板卡取回的文件
+/// 
+/// # 参数
+/// - `serial`: 设备序列号，作为JSON文件名的索引
+/// - `files`: 取回的文件，路径相对save目录，已登记的文件忽略
+/// 
+/// # 返回
+/// - `Ok(())` 如果设置成功
+/// - `Err(错误信息)` 如果设置失败
+pub fn add_artifacts(serial: &str, files: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    update_record(serial, |test_data| {
+        for file in files {
+            if !test_data.artifacts.contains(file) {
+                test_data.artifacts.push(file.clone());
+            }
+        }
+    })
+}
+
+/// 在JSON记录中关联串口控制台日志
+/// 
+/// # 参数
+/// - `serial`: 设备序列号，作为JSON文件名的索引
+/// - `console_log`: 控制台日志文件信息，同一文件重复关联时忽略
+/// 
+/// # 返回
+/// - `Ok(())` 如果设置成功
+/// - `Err(错误信息)` 如果设置失败
+pub fn add_console_log(serial: &str, console_log: &ConsoleLog) -> Result<(), Box<dyn std::error::Error>> {
+    update_record(serial, |test_data| {
+        if !test_data.console_log.iter().any(|log| log.raw == console_log.raw) {
+            test_data.console_log.push(console_log.clone());
+        }
+    })
+}
+
+/// 在JSON记录中添加开机分析结果
+/// 
+/// # 参数
+/// - `serial`: 设备序列号，作为JSON文件名的索引
+/// - `report`: 开机分析结果，同一起点时间重复添加时覆盖
+/// 
+/// # 返回
+/// - `Ok(())` 如果设置成功
+/// - `Err(错误信息)` 如果设置失败
+pub fn add_boot_report(serial: &str, report: &BootReport) -> Result<(), Box<dyn std::error::Error>> {
+    update_record(serial, |test_data| {
+        match test_data.boot_log.iter_mut().find(|boot| boot.started == report.started && boot.trigger == report.trigger) {
+            Some(boot) => *boot = report.clone(),
+            None => test_data.boot_log.push(report.clone()),
+        }
+    })
+}
+
+/// 在JSON记录中添加SSH命令输出记录
+/// 
+/// # 参数
+/// - `serial`: 设备序列号，作为JSON文件名的索引
+/// - `command_log`: 命令输出记录，同一输出文件重复添加时忽略
+/// 
+/// # 返回
+/// - `Ok(())` 如果设置成功
+/// - `Err(错误信息)` 如果设置失败
+pub fn add_command_log(serial: &str, command_log: &CommandLog) -> Result<(), Box<dyn std::error::Error>> {
+    update_record(serial, |test_data| {
+        if !test_data.command_log.iter().any(|log| log.transcript == command_log.transcript) {
+            test_data.command_log.push(command_log.clone());
+        }
+    })
+}
+
+/// 在JSON记录中保存网速测试结果，同一项目重复测试时以最后一次为准
+/// 
+/// # 参数
+/// - `serial`: 设备序列号，作为JSON文件名的索引
+/// - `measurement`: 客户端和服务端的测量值
+/// 
+/// # 返回
+/// - `Ok(())` 如果设置成功
+/// - `Err(错误信息)` 如果设置失败
+pub fn add_speed_measurement(serial: &str, measurement: &SpeedMeasurement) -> Result<(), Box<dyn std::error::Error>> {
+    update_record(serial, |test_data| {
+        test_data.speed_test.retain(|existing| existing.item != measurement.item);
+        test_data.speed_test.push(measurement.clone());
+    })
+}
+
+/// 获取记录在各结果输出的投递情况
+/// 
+/// # 参数
+/// - `file_name`: save目录中的文件名，如 "NeaZ10003.json"
+/// 
+/// # 返回
+/// - 按输出名称索引的投递情况，文件不存在或无法解析时为空
+pub fn get_deliveries(file_name: &str) -> BTreeMap<String, SinkDelivery> {
+    let Ok(save_dir) = get_save_dir() else { return BTreeMap::new() };
+    fs::read_to_string(save_dir.join(file_name)).ok()
+        .and_then(|content| serde_json::from_str::<TestData>(&content).ok())
+        .map(|test_data| test_data.deliveries)
+        .unwrap_or_default()
+}
+
+/// 在JSON记录中保存一次投递的结果
+/// 
+/// # 参数
+/// - `file_name`: save目录中的文件名，如 "NeaZ10003.json"
+/// - `sink`: 结果输出名称，如 "mes"
+/// - `error`: 失败原因，`None` 表示已接收
+/// 
+/// # 返回
+/// - `Ok(())` 如果保存成功
+/// - `Err(错误信息)` 如果文件不存在或保存失败
+pub fn set_delivery(file_name: &str, sink: &str, error: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let json_path = get_save_dir()?.join(file_name);
+    let _guard = lock_records();
+    if !json_path.exists() {
+        return Err(format!("save目录中的文件不存在: {}", json_path.display()).into());
+    }
+    
+    let content = fs::read_to_string(&json_path)?;
+    let mut test_data: TestData = serde_json::from_str(&content)?;
+    
+    let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let delivery = test_data.deliveries.entry(sink.to_string()).or_default();
+    delivery.attempts += 1;
+    delivery.last_attempt = now.clone();
+    match error {
+        Some(error) => delivery.last_error = error.to_string(),
+        None => {
+            delivery.accepted = true;
+            delivery.accepted_at = now;
+            delivery.last_error.clear();
+        }
+    }
+    
+    // 保存数据到JSON文件
+    let json_content = serde_json::to_string_pretty(&test_data)?;
+    write_atomic(&json_path, &json_content)?;
     
     Ok(())
 }
@@ -0,0 +1,182 @@
+// 产测脚本结果解析：脚本每个结果输出一行 "@@RESULT " 加JSON对象，格式见 doc/ResultProtocol.md
+// 脚本没有输出对应项目的结构化结果时，退回到按中文/英文提示文字查找的旧方式
+use std::collections::BTreeMap;
+use serde::Deserialize;
+use serde_json::Value;
+
+// 结果行前缀
+pub const RESULT_PREFIX: &str = "@@RESULT ";
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = true;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[script_result]{}", msg);
+    }
+}
+
+// 结果状态
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultStatus {
+    Pass,       // 测试通过
+    Fail,       // 测试失败，defects中为不良代码
+    Info,       // 只上报信息（如硬件检测），不判断通过与否
+}
+
+// 一条结构化结果
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptResult {
+    pub item: String,                       // 测试项目，与前端状态名一致，如 "hdmi_io_test"
+    pub status: ResultStatus,
+    #[serde(default)]
+    pub values: BTreeMap<String, Value>,    // 测量值和检测信息
+    #[serde(default)]
+    pub defects: Vec<String>,               // 不良代码，如 "LT6911_I2C"
+    #[serde(default)]
+    pub message: String,
+}
+
+impl ScriptResult {
+    // 取值，数字和布尔值转为字符串，空字符串视为没有
+    pub fn value(&self, key: &str) -> Option<String> {
+        match self.values.get(key)? {
+            Value::String(text) if text.is_empty() => None,
+            Value::String(text) => Some(text.clone()),
+            Value::Null => None,
+            other => Some(other.to_string()),
+        }
+    }
+}
+
+// 一次脚本输出的解析结果，同时保留原始输出用于旧格式的查找
+pub struct ScriptOutput<'a> {
+    results: Vec<ScriptResult>,
+    text: &'a str,
+}
+
+impl<'a> ScriptOutput<'a> {
+    // 解析脚本输出中的所有结果行，格式错误的行忽略
+    pub fn parse(text: &'a str) -> ScriptOutput<'a> {
+        let mut results = Vec::new();
+        for line in text.lines() {
+            let Some(json) = line.trim().strip_prefix(RESULT_PREFIX) else { continue };
+            match serde_json::from_str::<ScriptResult>(json) {
+                Ok(result) => results.push(result),
+                Err(e) => log(&format!("结果行格式错误: {} ({})", json, e)),
+            }
+        }
+        ScriptOutput { results, text }
+    }
+
+    // 项目的结构化结果，同一项目输出多次时以最后一次为准
+    pub fn result(&self, item: &str) -> Option<&ScriptResult> {
+        self.results.iter().rev().find(|result| result.item == item)
+    }
+
+    // 项目是否通过；旧格式下查找成功提示
+    pub fn passed(&self, item: &str, legacy_keyword: &str) -> bool {
+        match self.result(item) {
+            Some(result) => {
+                if result.status == ResultStatus::Fail {
+                    log(&format!("{} 失败: {} {:?}", item, result.message, result.defects));
+                }
+                result.status == ResultStatus::Pass
+            }
+            None => self.text.contains(legacy_keyword),
+        }
+    }
+
+    // 项目的某个值；旧格式下取提示文字之后到行尾的内容
+    pub fn value(&self, item: &str, key: &str, legacy_prefix: &str) -> Option<String> {
+        match self.result(item) {
+            Some(result) => result.value(key),
+            None => {
+                let start = self.text.find(legacy_prefix)? + legacy_prefix.len();
+                let remaining = &self.text[start..];
+                let line = remaining.split('\n').next().unwrap_or_default().trim();
+                Some(line.to_string())
+            }
+        }
+    }
+
+    // 项目的布尔标记；旧格式下查找提示文字
+    pub fn flag(&self, item: &str, key: &str, legacy_text: &str) -> bool {
+        match self.result(item) {
+            Some(result) => result.value(key).as_deref() == Some("true"),
+            None => self.text.contains(legacy_text),
+        }
+    }
+
+    // 项目是否报告了某个不良；旧格式下查找不良提示
+    pub fn has_defect(&self, item: &str, code: &str, legacy_text: &str) -> bool {
+        match self.result(item) {
+            Some(result) => result.defects.iter().any(|defect| defect == code),
+            None => self.text.contains(legacy_text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_result_lines() {
+        let text = "开始测试\n\
+            @@RESULT {\"item\":\"hdmi_io_test\",\"status\":\"pass\",\"values\":{\"width\":1920,\"mode\":\"1080p\"}}\n\
+            \t@@RESULT {\"item\":\"usb_test\",\"status\":\"fail\",\"defects\":[\"USB2_MISSING\"],\"message\":\"no device\"}  \n\
+            测试结束\n";
+        let output = ScriptOutput::parse(text);
+        let hdmi = output.result("hdmi_io_test").unwrap();
+        assert_eq!(hdmi.status, ResultStatus::Pass);
+        assert_eq!(hdmi.value("width").as_deref(), Some("1920"));
+        assert_eq!(hdmi.value("mode").as_deref(), Some("1080p"));
+        assert!(hdmi.defects.is_empty());
+        let usb = output.result("usb_test").unwrap();
+        assert_eq!(usb.status, ResultStatus::Fail);
+        assert_eq!(usb.message, "no device");
+        assert!(output.passed("hdmi_io_test", "HDMI OK"));
+        assert!(!output.passed("usb_test", "USB OK"));
+        assert!(output.has_defect("usb_test", "USB2_MISSING", "USB missing"));
+    }
+
+    #[test]
+    fn parse_ignores_malformed_lines() {
+        let text = "@@RESULT {not json}\n\
+            @@RESULT {\"item\":\"fan_test\",\"status\":\"unknown\"}\n\
+            @@RESULT {\"status\":\"pass\"}\n\
+            xx @@RESULT {\"item\":\"fan_test\",\"status\":\"pass\"}\n";
+        let output = ScriptOutput::parse(text);
+        assert!(output.result("fan_test").is_none());
+        assert!(output.results.is_empty());
+    }
+
+    #[test]
+    fn parse_last_result_wins() {
+        let text = "@@RESULT {\"item\":\"wifi_test\",\"status\":\"fail\"}\n\
+            @@RESULT {\"item\":\"wifi_test\",\"status\":\"pass\",\"values\":{\"rssi\":-40,\"ssid\":\"\",\"ok\":true,\"none\":null}}\n";
+        let output = ScriptOutput::parse(text);
+        let wifi = output.result("wifi_test").unwrap();
+        assert_eq!(wifi.status, ResultStatus::Pass);
+        assert_eq!(wifi.value("rssi").as_deref(), Some("-40"));
+        // 空字符串和null视为没有值
+        assert_eq!(wifi.value("ssid"), None);
+        assert_eq!(wifi.value("none"), None);
+        assert!(output.flag("wifi_test", "ok", "WIFI OK"));
+    }
+
+    #[test]
+    fn legacy_fallback_without_result() {
+        let text = "内存测试通过\n序列号: SN12345 \n发现不良: 风扇\n";
+        let output = ScriptOutput::parse(text);
+        assert!(output.passed("memory_test", "内存测试通过"));
+        assert!(!output.passed("disk_test", "硬盘测试通过"));
+        assert_eq!(output.value("serial", "sn", "序列号:").as_deref(), Some("SN12345"));
+        assert_eq!(output.value("serial", "sn", "MAC:"), None);
+        assert!(output.has_defect("fan_test", "FAN", "不良: 风扇"));
+        assert!(!output.flag("fan_test", "present", "风扇存在"));
+    }
+}
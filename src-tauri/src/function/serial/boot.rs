@@ -0,0 +1,346 @@
+// 开机日志分析：跟踪从上电/复位到登录提示的串口输出
+// 记录到U-Boot、内核启动、登录提示的耗时，检测内核崩溃、Oops、eMMC错误和看门狗复位，结果写入板卡的JSON记录
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+use chrono::Local;
+use lazy_static::lazy_static;
+use regex::Regex;
+use tauri::async_runtime::{spawn, spawn_blocking};
+
+use super::hub::{subscribe_console, SubscribeFrom};
+use super::stream::AnsiStripper;
+use super::capture::write_capture_note;
+use crate::APP_EXIT;
+use crate::function::save::{add_boot_report, BootFault, BootReport};
+
+// 单行最大长度，超过后强制换行分析
+const MAX_BOOT_LINE: usize = 1024;
+// 每次开机最多记录的故障行数
+const MAX_BOOT_FAULTS: usize = 32;
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = true;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[boot]{}", msg);
+    }
+}
+
+// 开机故障类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BootFaultKind {
+    Panic,              // 内核崩溃
+    Oops,               // 内核Oops
+    Mmc,                // eMMC读写错误
+    Watchdog,           // 看门狗复位
+    UnexpectedReset,    // 已进入内核后又重新出现U-Boot
+}
+
+impl BootFaultKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BootFaultKind::Panic => "panic",
+            BootFaultKind::Oops => "oops",
+            BootFaultKind::Mmc => "mmc",
+            BootFaultKind::Watchdog => "watchdog",
+            BootFaultKind::UnexpectedReset => "unexpected_reset",
+        }
+    }
+
+    // 打印在不良贴纸上的描述
+    pub fn description(&self) -> &'static str {
+        match self {
+            BootFaultKind::Panic => "内核崩溃(Kernel panic)",
+            BootFaultKind::Oops => "内核异常(Oops)",
+            BootFaultKind::Mmc => "eMMC读写错误",
+            BootFaultKind::Watchdog => "看门狗复位",
+            BootFaultKind::UnexpectedReset => "启动过程中意外重启",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<BootFaultKind> {
+        [BootFaultKind::Panic, BootFaultKind::Oops, BootFaultKind::Mmc, BootFaultKind::Watchdog, BootFaultKind::UnexpectedReset]
+            .into_iter()
+            .find(|fault| fault.as_str() == kind)
+    }
+}
+
+lazy_static! {
+    static ref UBOOT_PATTERN: Regex = Regex::new(r"U-Boot \d{4}\.\d{2}").unwrap();
+    static ref KERNEL_PATTERN: Regex = Regex::new(r"Starting kernel|Booting Linux on physical CPU|Linux version \d").unwrap();
+    static ref LOGIN_PATTERN: Regex = Regex::new(r"login:\s*$").unwrap();
+    static ref FAULT_PATTERNS: Vec<(BootFaultKind, Regex)> = vec![
+        (BootFaultKind::Panic, Regex::new(r"Kernel panic - not syncing").unwrap()),
+        (BootFaultKind::Oops, Regex::new(r"Internal error: Oops|Oops(\[#\d+\])?:|BUG: unable to handle").unwrap()),
+        (BootFaultKind::Mmc, Regex::new(r"mmcblk\d+.*(I/O error|error -\d+)|I/O error, dev mmcblk|blk_update_request: I/O error|mmc\d+: (cache flush error|error -\d+ (doing|transferring|sending))|EXT4-fs error \(device mmcblk").unwrap()),
+        (BootFaultKind::Watchdog, Regex::new(r"(?i)watchdog: .*did not stop|watchdog.*expired|wdt reset|reset cause:\s*(wdt|watchdog)").unwrap()),
+    ];
+    static ref BOOT_TRACKER: Mutex<BootTracker> = Mutex::new(BootTracker::default());
+}
+
+// 正在进行的一次开机
+struct ActiveBoot {
+    start: Instant,
+    report: BootReport,
+}
+
+impl ActiveBoot {
+    fn new(start: Instant, trigger: &str) -> ActiveBoot {
+        let started = Local::now() - chrono::Duration::from_std(start.elapsed()).unwrap_or_else(|_| chrono::Duration::zero());
+        ActiveBoot {
+            start,
+            report: BootReport {
+                started: started.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+                trigger: trigger.to_string(),
+                ..BootReport::default()
+            },
+        }
+    }
+
+    fn elapsed_ms(&self, at: Instant) -> u64 {
+        at.saturating_duration_since(self.start).as_millis() as u64
+    }
+
+    fn add_fault(&mut self, kind: BootFaultKind, at: Instant, line: &str) {
+        if self.report.faults.len() >= MAX_BOOT_FAULTS {
+            return;
+        }
+        log(&format!("检测到开机故障 {}: {}", kind.as_str(), line));
+        let at_ms = self.elapsed_ms(at);
+        self.report.faults.push(BootFault { kind: kind.as_str().to_string(), at_ms, line: line.to_string() });
+    }
+}
+
+// 开机跟踪状态
+#[derive(Default)]
+struct BootTracker {
+    current: Option<ActiveBoot>,
+    reports: Vec<BootReport>,       // 本块板卡已结束的开机
+    linked: usize,                  // 已写入JSON记录的数量
+    serial: Option<String>,         // 已关联的串号
+    stripper: AnsiStripper,
+    line: Vec<u8>,                  // 尚未换行的内容
+    line_time: Option<Instant>,     // 当前行第一个字节的接收时间
+}
+
+impl BootTracker {
+    fn feed(&mut self, data: &[u8], now: Instant) {
+        let mut cleaned = Vec::with_capacity(data.len());
+        self.stripper.feed(data, &mut cleaned);
+        for byte in cleaned {
+            if self.line_time.is_none() {
+                self.line_time = Some(now);
+            }
+            match byte {
+                b'\n' => self.flush_line(),
+                b'\r' => {}
+                _ => {
+                    self.line.push(byte);
+                    if self.line.len() >= MAX_BOOT_LINE {
+                        self.flush_line();
+                    }
+                }
+            }
+        }
+
+        // 登录提示后面没有换行，需要检查未结束的行
+        if self.current.is_some() {
+            let partial = String::from_utf8_lossy(&self.line).to_string();
+            if LOGIN_PATTERN.is_match(&partial) {
+                let at = self.line_time.unwrap_or(now);
+                self.on_login(at);
+            }
+        }
+    }
+
+    fn flush_line(&mut self) {
+        let at = match self.line_time.take() {
+            Some(at) => at,
+            None => return,
+        };
+        let line = String::from_utf8_lossy(&self.line).trim().to_string();
+        self.line.clear();
+        if !line.is_empty() {
+            self.analyse_line(&line, at);
+        }
+    }
+
+    fn analyse_line(&mut self, line: &str, at: Instant) {
+        if UBOOT_PATTERN.is_match(line) {
+            // 已经出现过U-Boot或已进入内核，说明板卡自己重启了
+            let restarted = matches!(&self.current, Some(boot) if boot.report.uboot_ms.is_some() || boot.report.kernel_ms.is_some());
+            if restarted {
+                self.active(at).add_fault(BootFaultKind::UnexpectedReset, at, line);
+                self.finish();
+            }
+            if self.current.is_none() {
+                self.begin(at, "console");
+            }
+            if let Some(boot) = self.current.as_mut() {
+                if boot.report.uboot_ms.is_none() {
+                    boot.report.uboot_ms = Some(boot.elapsed_ms(at));
+                    log(&format!("U-Boot: {}ms", boot.elapsed_ms(at)));
+                }
+            }
+        }
+
+        if KERNEL_PATTERN.is_match(line) {
+            let boot = self.active(at);
+            if boot.report.kernel_ms.is_none() {
+                boot.report.kernel_ms = Some(boot.elapsed_ms(at));
+                log(&format!("内核启动: {}ms", boot.elapsed_ms(at)));
+            }
+        }
+
+        for (kind, pattern) in FAULT_PATTERNS.iter() {
+            if pattern.is_match(line) {
+                self.active(at).add_fault(*kind, at, line);
+                break;
+            }
+        }
+
+        if self.current.is_some() && LOGIN_PATTERN.is_match(line) {
+            self.on_login(at);
+        }
+    }
+
+    // 当前开机，不存在时从这一行开始计时
+    fn active(&mut self, at: Instant) -> &mut ActiveBoot {
+        self.current.get_or_insert_with(|| ActiveBoot::new(at, "console"))
+    }
+
+    fn begin(&mut self, start: Instant, trigger: &str) {
+        self.current = Some(ActiveBoot::new(start, trigger));
+    }
+
+    fn on_login(&mut self, at: Instant) {
+        if let Some(boot) = self.current.as_mut() {
+            boot.report.login_ms = Some(boot.elapsed_ms(at));
+            log(&format!("登录提示: {}ms", boot.elapsed_ms(at)));
+        }
+        self.finish();
+    }
+
+    // 结束当前开机并写入控制台日志，JSON记录由调用方释放锁后通过take_unsaved写入
+    fn finish(&mut self) {
+        if let Some(boot) = self.current.take() {
+            write_capture_note(&format!("boot {}", summarize(&boot.report)));
+            self.reports.push(boot.report);
+        }
+    }
+
+    // 取出尚未写入JSON记录的开机记录，include_current时进行中的开机也一并取出
+    fn take_unsaved(&mut self, include_current: bool) -> Option<(String, Vec<BootReport>)> {
+        let serial = self.serial.clone()?;
+        let mut reports = self.reports[self.linked..].to_vec();
+        self.linked = self.reports.len();
+        if include_current {
+            if let Some(boot) = &self.current {
+                reports.push(boot.report.clone());
+            }
+        }
+        if reports.is_empty() { None } else { Some((serial, reports)) }
+    }
+
+    // 从最近一次由本程序上电/复位的开机开始，之后所有开机的故障
+    fn recent_faults(&self) -> Vec<BootFault> {
+        let mut boots: Vec<&BootReport> = self.reports.iter().collect();
+        if let Some(boot) = &self.current {
+            boots.push(&boot.report);
+        }
+        let first = boots.iter().rposition(|boot| boot.trigger != "console").unwrap_or(0);
+        boots[first..].iter().flat_map(|boot| boot.faults.iter().cloned()).collect()
+    }
+}
+
+// 把开机记录写入JSON记录；写文件较慢，调用时不能持有BOOT_TRACKER，否则会阻塞串口数据分析
+fn save_reports(unsaved: Option<(String, Vec<BootReport>)>) {
+    if let Some((serial, reports)) = unsaved {
+        for report in &reports {
+            if let Err(e) = add_boot_report(&serial, report) {
+                log(&format!("写入开机记录到 {} 失败: {}", serial, e));
+            }
+        }
+    }
+}
+
+// 一次开机的摘要
+fn summarize(report: &BootReport) -> String {
+    let ms = |value: Option<u64>| value.map_or("-".to_string(), |ms| format!("{}ms", ms));
+    let faults: Vec<&str> = report.faults.iter().map(|fault| fault.kind.as_str()).collect();
+    format!("trigger={} uboot={} kernel={} login={} faults=[{}]",
+        report.trigger, ms(report.uboot_ms), ms(report.kernel_ms), ms(report.login_ms), faults.join(","))
+}
+
+// 新的板卡开始测试时清空之前的开机记录
+pub fn reset_boot_analysis() {
+    let mut tracker = BOOT_TRACKER.lock().unwrap_or_else(|e| e.into_inner());
+    *tracker = BootTracker::default();
+}
+
+// 标记开机起点，由电源控制在上电或释放复位时调用
+pub fn mark_boot_start(trigger: &str) {
+    let unsaved = {
+        let mut tracker = BOOT_TRACKER.lock().unwrap_or_else(|e| e.into_inner());
+        tracker.flush_line();
+        tracker.finish();
+        tracker.begin(Instant::now(), trigger);
+        tracker.take_unsaved(false)
+    };
+    log(&format!("开机起点: {}", trigger));
+    save_reports(unsaved);
+}
+
+// 把开机记录关联到指定串号的JSON记录，之后的开机也会自动写入
+pub fn link_boot_reports(serial: &str) {
+    let unsaved = {
+        let mut tracker = BOOT_TRACKER.lock().unwrap_or_else(|e| e.into_inner());
+        if tracker.serial.as_deref() != Some(serial) {
+            tracker.serial = Some(serial.to_string());
+            tracker.linked = 0;
+        }
+        tracker.take_unsaved(true)
+    };
+    save_reports(unsaved);
+}
+
+// 最近一次开机检测到的故障，用于判定开机不良；没有故障时返回None
+pub fn boot_defect_message() -> Option<String> {
+    let tracker = BOOT_TRACKER.lock().unwrap_or_else(|e| e.into_inner());
+    let mut kinds: Vec<BootFaultKind> = Vec::new();
+    for fault in tracker.recent_faults() {
+        if let Some(kind) = BootFaultKind::parse(&fault.kind) {
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+    }
+    if kinds.is_empty() {
+        return None;
+    }
+    let descriptions: Vec<&str> = kinds.iter().map(|kind| kind.description()).collect();
+    Some(descriptions.join("/"))
+}
+
+// 启动开机分析任务：作为独立的消费者订阅串口数据
+pub fn spawn_boot_task() {
+    spawn(async move {
+        let mut subscription = subscribe_console("boot", SubscribeFrom::Now);
+        while !APP_EXIT.load(Ordering::Relaxed) {
+            let chunk = subscription.recv_timeout(500).await;
+            if !chunk.data.is_empty() {
+                let unsaved = {
+                    let mut tracker = BOOT_TRACKER.lock().unwrap_or_else(|e| e.into_inner());
+                    tracker.feed(&chunk.data, Instant::now());
+                    tracker.take_unsaved(false)
+                };
+                if unsaved.is_some() {
+                    let _ = spawn_blocking(move || save_reports(unsaved)).await;
+                }
+            }
+        }
+    });
+}
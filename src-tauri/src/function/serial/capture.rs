@@ -0,0 +1,310 @@
+// 串口控制台录制：把串口收到的每一个字节带时间戳写入save目录，供维修台查看开机日志
+// 每次测试生成一组文件：console_<时间>.raw.log（原始字节，转义保存）和 console_<时间>.log（清理ANSI后按行保存）
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
+use chrono::{DateTime, Local};
+use lazy_static::lazy_static;
+use tauri::async_runtime::{spawn, spawn_blocking};
+
+use super::hub::{subscribe_console, SubscribeFrom};
+use super::stream::AnsiStripper;
+use crate::APP_EXIT;
+use crate::function::save::{get_save_dir, add_console_log, ConsoleLog};
+
+// 录制文件名前缀
+const CAPTURE_PREFIX: &str = "console_";
+// 单个文件上限，超过后切换到下一个分片
+const MAX_CAPTURE_FILE_BYTES: u64 = 16 * 1024 * 1024;
+// 所有录制文件总大小上限，超过后从最早的文件开始删除
+const MAX_CAPTURE_TOTAL_BYTES: u64 = 1024 * 1024 * 1024;
+// 录制文件数量上限
+const MAX_CAPTURE_FILES: usize = 2000;
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = true;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[capture]{}", msg);
+    }
+}
+
+lazy_static! {
+    static ref CONSOLE_CAPTURE: Mutex<Option<ConsoleCapture>> = Mutex::new(None);    // 当前测试的录制
+}
+
+// 单个录制文件
+struct CaptureFile {
+    name: String,
+    writer: BufWriter<File>,
+    written: u64,
+}
+
+impl CaptureFile {
+    fn create(dir: &Path, name: String) -> std::io::Result<CaptureFile> {
+        let file = File::create(dir.join(&name))?;
+        Ok(CaptureFile { name, writer: BufWriter::new(file), written: 0 })
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.writer.write_all(data)?;
+        self.written += data.len() as u64;
+        Ok(())
+    }
+}
+
+// 一次测试的控制台录制
+struct ConsoleCapture {
+    dir: PathBuf,
+    stamp: String,
+    started: String,
+    part: u32,
+    raw: CaptureFile,
+    clean: CaptureFile,
+    stripper: AnsiStripper,
+    line: Vec<u8>,                          // 清理后尚未换行的内容
+    line_time: Option<DateTime<Local>>,     // 当前行第一个字节的接收时间
+    serial: Option<String>,                 // 已关联的串号
+}
+
+impl ConsoleCapture {
+    fn open(dir: PathBuf) -> std::io::Result<ConsoleCapture> {
+        let now = Local::now();
+        let stamp = now.format("%Y%m%d_%H%M%S").to_string();
+        let (raw, clean) = open_part(&dir, &stamp, 0)?;
+        let mut capture = ConsoleCapture {
+            dir,
+            stamp,
+            started: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            part: 0,
+            raw,
+            clean,
+            stripper: AnsiStripper::default(),
+            line: Vec::new(),
+            line_time: None,
+            serial: None,
+        };
+        capture.write_header()?;
+        Ok(capture)
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()> {
+        let header = format!("# NanoKVM console capture, started {}, part {}\n", self.started, self.part);
+        self.raw.write(header.as_bytes())?;
+        self.raw.write(b"# [time] bytes, non-printable bytes escaped as \\xNN\n")?;
+        self.clean.write(header.as_bytes())?;
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let now = Local::now();
+
+        // 原始数据：每个数据块一行，转义后保存全部字节
+        let mut raw_line = format!("[{}] ", now.format("%Y-%m-%d %H:%M:%S%.3f")).into_bytes();
+        raw_line.extend(data.escape_ascii());
+        raw_line.push(b'\n');
+        self.raw.write(&raw_line)?;
+
+        // 清理后数据：按行保存，时间为该行第一个字节的接收时间
+        let mut cleaned = Vec::with_capacity(data.len());
+        self.stripper.feed(data, &mut cleaned);
+        for byte in cleaned {
+            if self.line_time.is_none() {
+                self.line_time = Some(now);
+            }
+            if byte == b'\n' {
+                self.flush_line()?;
+            } else {
+                self.line.push(byte);
+            }
+        }
+
+        if self.raw.written >= MAX_CAPTURE_FILE_BYTES || self.clean.written >= MAX_CAPTURE_FILE_BYTES {
+            self.next_part()?;
+        }
+        Ok(())
+    }
+
+    // 把缓冲中的内容写入文件
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.raw.writer.flush()?;
+        self.clean.writer.flush()
+    }
+
+    // 在清理后的日志中插入一行说明，以#开头与控制台输出区分
+    fn note(&mut self, note: &str) -> std::io::Result<()> {
+        self.flush_line()?;
+        let line = format!("[{}] # {}\n", Local::now().format("%H:%M:%S%.3f"), note);
+        self.clean.write(line.as_bytes())?;
+        self.clean.writer.flush()
+    }
+
+    fn flush_line(&mut self) -> std::io::Result<()> {
+        if let Some(time) = self.line_time.take() {
+            let mut line = format!("[{}] ", time.format("%H:%M:%S%.3f")).into_bytes();
+            line.append(&mut self.line);
+            line.push(b'\n');
+            self.clean.write(&line)?;
+        }
+        Ok(())
+    }
+
+    // 切换到下一个分片，已关联串号时同时关联新分片
+    fn next_part(&mut self) -> std::io::Result<()> {
+        self.flush_line()?;
+        self.flush()?;
+        self.part += 1;
+        let (raw, clean) = open_part(&self.dir, &self.stamp, self.part)?;
+        self.raw = raw;
+        self.clean = clean;
+        self.write_header()?;
+        if let Some(serial) = self.serial.clone() {
+            self.link(&serial);
+        }
+        Ok(())
+    }
+
+    fn link(&mut self, serial: &str) {
+        self.serial = Some(serial.to_string());
+        let console_log = ConsoleLog {
+            started: self.started.clone(),
+            raw: self.raw.name.clone(),
+            clean: self.clean.name.clone(),
+        };
+        if let Err(e) = add_console_log(serial, &console_log) {
+            log(&format!("关联控制台日志到 {} 失败: {}", serial, e));
+        }
+    }
+
+    fn close(mut self) {
+        let _ = self.flush_line();
+        let _ = self.flush();
+    }
+}
+
+fn open_part(dir: &Path, stamp: &str, part: u32) -> std::io::Result<(CaptureFile, CaptureFile)> {
+    let base = if part == 0 {
+        format!("{}{}", CAPTURE_PREFIX, stamp)
+    } else {
+        format!("{}{}_{}", CAPTURE_PREFIX, stamp, part)
+    };
+    let raw = CaptureFile::create(dir, format!("{}.raw.log", base))?;
+    let clean = CaptureFile::create(dir, format!("{}.log", base))?;
+    Ok((raw, clean))
+}
+
+// 删除最早的录制文件，直到数量和总大小都在上限之内
+fn rotate_captures(dir: &Path) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let mut files: Vec<(PathBuf, u64, std::time::SystemTime)> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.starts_with(CAPTURE_PREFIX) && name.ends_with(".log")
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some((entry.path(), metadata.len(), metadata.modified().ok()?))
+        })
+        .collect();
+    files.sort_by_key(|(_, _, modified)| *modified);
+
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    let mut count = files.len();
+    for (path, size, _) in files {
+        if count <= MAX_CAPTURE_FILES && total <= MAX_CAPTURE_TOTAL_BYTES {
+            break;
+        }
+        match fs::remove_file(&path) {
+            Ok(_) => log(&format!("删除旧的控制台日志: {}", path.display())),
+            Err(e) => log(&format!("删除旧的控制台日志失败 {}: {}", path.display(), e)),
+        }
+        total = total.saturating_sub(size);
+        count -= 1;
+    }
+}
+
+// 开始新的录制，结束上一次未结束的录制；应用数据目录未初始化时不录制
+pub fn start_console_capture() {
+    let mut capture = CONSOLE_CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(previous) = capture.take() {
+        previous.close();
+    }
+    let dir = match get_save_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            log(&format!("无法获取save目录，不录制控制台: {}", e));
+            return;
+        }
+    };
+    rotate_captures(&dir);
+    match ConsoleCapture::open(dir) {
+        Ok(new_capture) => {
+            log(&format!("开始录制控制台: {}", new_capture.clean.name));
+            *capture = Some(new_capture);
+        }
+        Err(e) => log(&format!("创建控制台日志失败: {}", e)),
+    }
+}
+
+// 结束当前录制
+pub fn stop_console_capture() {
+    let mut capture = CONSOLE_CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(previous) = capture.take() {
+        previous.close();
+    }
+}
+
+// 把当前录制关联到指定串号的JSON记录
+pub fn link_console_capture(serial: &str) {
+    let mut capture = CONSOLE_CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(current) = capture.as_mut() {
+        current.link(serial);
+    }
+}
+
+// 在当前录制中写入说明，如开机分析结果
+pub fn write_capture_note(note: &str) {
+    let mut capture = CONSOLE_CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(current) = capture.as_mut() {
+        if let Err(e) = current.note(note) {
+            log(&format!("写入控制台日志说明失败: {}", e));
+        }
+    }
+}
+
+// 启动录制任务：作为独立的消费者订阅串口数据，写入当前录制文件
+// 文件写入在阻塞线程中执行，不占用异步运行时；500ms没有新数据时把缓冲写入文件
+pub fn spawn_capture_task() {
+    spawn(async move {
+        let mut subscription = subscribe_console("capture", SubscribeFrom::Now);
+        while !APP_EXIT.load(Ordering::Relaxed) {
+            let chunk = subscription.recv_timeout(500).await;
+            if chunk.lost > 0 {
+                log(&format!("录制跟不上串口数据，丢失 {} 字节", chunk.lost));
+            }
+            let _ = spawn_blocking(move || capture_console_data(&chunk.data)).await;
+        }
+    });
+}
+
+// 写入串口收到的数据，没有数据时刷新缓冲
+fn capture_console_data(data: &[u8]) {
+    let mut capture = CONSOLE_CAPTURE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(current) = capture.as_mut() {
+        let result = if data.is_empty() { current.flush() } else { current.write(data) };
+        if let Err(e) = result {
+            log(&format!("写入控制台日志失败，停止录制: {}", e));
+            if let Some(previous) = capture.take() {
+                previous.close();
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+// 串口接收广播：串口管理线程把收到的数据写入共享环形缓冲，每个消费者（状态机、日志录制、UI终端）持有自己的读取位置
+// 读取不会移走数据，一个消费者读过的数据其他消费者仍然可以读到；消费者读得太慢时只丢失它自己落后的部分
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use lazy_static::lazy_static;
+use tokio::sync::Notify;
+
+// 环形缓冲保存最近1MB原始串口数据
+const HUB_CAPACITY: usize = 1024 * 1024;
+
+lazy_static! {
+    static ref CONSOLE_HUB: Mutex<ConsoleHub> = Mutex::new(ConsoleHub::new(HUB_CAPACITY));    // 共享环形缓冲
+    static ref HUB_NOTIFY: Notify = Notify::new();                                              // 有新数据时唤醒等待的消费者
+}
+
+// 共享环形缓冲，位置均为整个数据流中的绝对字节位置
+struct ConsoleHub {
+    ring: VecDeque<u8>,
+    base: u64,          // ring[0] 的绝对位置
+    capacity: usize,
+}
+
+impl ConsoleHub {
+    fn new(capacity: usize) -> ConsoleHub {
+        ConsoleHub { ring: VecDeque::with_capacity(capacity), base: 0, capacity }
+    }
+
+    fn end(&self) -> u64 {
+        self.base + self.ring.len() as u64
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        self.ring.extend(data);
+        if self.ring.len() > self.capacity {
+            let overflow = self.ring.len() - self.capacity;
+            self.ring.drain(..overflow);
+            self.base += overflow as u64;
+        }
+    }
+}
+
+// 订阅起点
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubscribeFrom {
+    Now,        // 只接收订阅之后的新数据
+    Recent(usize),  // 从最近的n个字节开始
+}
+
+// 一次读取的结果
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleChunk {
+    pub data: Vec<u8>,
+    pub lost: u64,      // 读取太慢被覆盖而丢失的字节数
+}
+
+impl ConsoleChunk {
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty() && self.lost == 0
+    }
+}
+
+// 串口数据订阅，每个消费者一个
+#[derive(Debug)]
+pub struct ConsoleSubscription {
+    name: String,
+    cursor: u64,
+}
+
+impl ConsoleSubscription {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // 读取全部未读数据，不等待
+    pub fn try_recv(&mut self) -> ConsoleChunk {
+        let hub = CONSOLE_HUB.lock().unwrap_or_else(|e| e.into_inner());
+        let mut chunk = ConsoleChunk::default();
+        if self.cursor < hub.base {
+            chunk.lost = hub.base - self.cursor;
+            self.cursor = hub.base;
+        }
+        let start = (self.cursor - hub.base) as usize;
+        chunk.data = hub.ring.range(start..).copied().collect();
+        self.cursor = hub.end();
+        chunk
+    }
+
+    // 等待新数据，超时返回空结果
+    pub async fn recv_timeout(&mut self, timeout_ms: u64) -> ConsoleChunk {
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(timeout_ms);
+        loop {
+            // 先登记唤醒再检查数据，避免检查之后、等待之前到达的数据被错过
+            let notified = HUB_NOTIFY.notified();
+            let chunk = self.try_recv();
+            if !chunk.is_empty() {
+                return chunk;
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return ConsoleChunk::default();
+            }
+        }
+    }
+
+    // 跳过全部未读数据
+    pub fn skip_to_end(&mut self) {
+        let hub = CONSOLE_HUB.lock().unwrap_or_else(|e| e.into_inner());
+        self.cursor = hub.end();
+    }
+}
+
+// 创建新的订阅
+pub fn subscribe_console(name: &str, from: SubscribeFrom) -> ConsoleSubscription {
+    let hub = CONSOLE_HUB.lock().unwrap_or_else(|e| e.into_inner());
+    let cursor = match from {
+        SubscribeFrom::Now => hub.end(),
+        SubscribeFrom::Recent(bytes) => hub.end().saturating_sub(bytes as u64).max(hub.base),
+    };
+    ConsoleSubscription { name: name.to_string(), cursor }
+}
+
+// 发布串口收到的数据，由串口管理线程调用
+pub fn publish_console_data(data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+    {
+        let mut hub = CONSOLE_HUB.lock().unwrap_or_else(|e| e.into_inner());
+        hub.push(data);
+    }
+    HUB_NOTIFY.notify_waiters();
+}
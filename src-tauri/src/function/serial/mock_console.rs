@@ -0,0 +1,194 @@
+// 脚本控制台：在内存传输的另一端模拟NanoKVM的串口，用于没有USB工具时跑通开机/登录状态机
+// 默认模拟一块正常开机的板卡，也可以通过 NANOKVM_SERIAL_SCRIPT 指定的TOML文件自定义行为
+use std::time::Duration;
+use serde::Deserialize;
+use tauri::async_runtime::spawn;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, WriteHalf};
+use tokio::time::sleep;
+
+// 自定义脚本文件路径的环境变量
+const SCRIPT_ENV: &str = "NANOKVM_SERIAL_SCRIPT";
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = false;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[mock_console]{}", msg);
+    }
+}
+
+// 命令应答规则：输入以 command 开头时回复 reply
+#[derive(Deserialize, Debug, Clone)]
+pub struct ConsoleRule {
+    pub command: String,
+    pub reply: String,
+}
+
+// 脚本控制台配置，TOML文件中所有字段都可省略
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ConsoleScript {
+    pub boot_log: Vec<String>,      // 开机日志，每行间隔 line_delay_ms 输出
+    pub line_delay_ms: u64,
+    pub login_prompt: String,
+    pub username: String,
+    pub password: String,
+    pub shell_prompt: String,
+    pub rule: Vec<ConsoleRule>,
+}
+
+impl Default for ConsoleScript {
+    fn default() -> Self {
+        ConsoleScript {
+            boot_log: vec![
+                "U-Boot 2020.04 (AXERA)".to_string(),
+                "Hit any key to stop autoboot:  0".to_string(),
+                "Starting kernel ...".to_string(),
+                "[    0.000000] Booting Linux on physical CPU 0x0".to_string(),
+                "[  OK  ] Reached target Local Encrypted Volumes.".to_string(),
+                "[  OK  ] Started Serial Getty on ttyS0.".to_string(),
+                "[  OK  ] Reached target Login Prompts.".to_string(),
+                "".to_string(),
+                "Ubuntu 22.04 LTS kvm ttyS0".to_string(),
+            ],
+            line_delay_ms: 200,
+            login_prompt: "kvm login: ".to_string(),
+            username: "root".to_string(),
+            password: "sipeed".to_string(),
+            shell_prompt: "root@kvm:~# ".to_string(),
+            rule: vec![
+                ConsoleRule { command: "ping -c 1".to_string(), reply: "1 packets transmitted, 1 received, 0% packet loss, time 0ms".to_string() },
+                ConsoleRule { command: "lsmod | grep 6911".to_string(), reply: "lt6911_manage          16384  0".to_string() },
+                ConsoleRule { command: "ip a | grep wlan".to_string(), reply: "3: wlan0: <BROADCAST,MULTICAST> mtu 1500".to_string() },
+                ConsoleRule { command: "cat /proc/lt6911_info/version".to_string(), reply: "version: NanoKVM_Pro (Desk-G) NebcA0001".to_string() },
+                ConsoleRule { command: "cat /proc/lt6911_info/status".to_string(), reply: "new res".to_string() },
+                ConsoleRule { command: "ls /etc/test-kvm/wifi".to_string(), reply: "/etc/test-kvm/wifi_exist".to_string() },
+            ],
+        }
+    }
+}
+
+impl ConsoleScript {
+    // 读取环境变量指定的脚本，失败时使用默认脚本
+    pub fn from_env() -> ConsoleScript {
+        let path = match std::env::var(SCRIPT_ENV) {
+            Ok(path) => path,
+            Err(_) => return ConsoleScript::default(),
+        };
+        match std::fs::read_to_string(&path).map_err(|e| e.to_string())
+            .and_then(|content| toml::from_str::<ConsoleScript>(&content).map_err(|e| e.to_string())) {
+            Ok(script) => script,
+            Err(e) => {
+                println!("[mock_console]读取脚本 {} 失败，使用默认脚本: {}", path, e);
+                ConsoleScript::default()
+            }
+        }
+    }
+}
+
+// 控制台所处状态
+#[derive(Debug, PartialEq)]
+enum ConsoleState {
+    Login,
+    Password(String),
+    Shell,
+}
+
+// 在内存管道的另一端启动脚本控制台
+pub fn spawn_mock_console(stream: DuplexStream) {
+    let script = ConsoleScript::from_env();
+    spawn(async move {
+        if let Err(e) = run_mock_console(stream, script).await {
+            log(&format!("脚本控制台退出: {:?}", e));
+        }
+    });
+}
+
+async fn write_str(writer: &mut WriteHalf<DuplexStream>, text: &str) -> std::io::Result<()> {
+    writer.write_all(text.as_bytes()).await
+}
+
+async fn play_boot_log(writer: &mut WriteHalf<DuplexStream>, script: &ConsoleScript) -> std::io::Result<()> {
+    for line in &script.boot_log {
+        write_str(writer, &format!("{}\r\n", line)).await?;
+        sleep(Duration::from_millis(script.line_delay_ms)).await;
+    }
+    write_str(writer, &format!("\r\n{}", script.login_prompt)).await
+}
+
+async fn run_mock_console(stream: DuplexStream, script: ConsoleScript) -> std::io::Result<()> {
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut state = ConsoleState::Login;
+    let mut line = String::new();
+    let mut buffer = [0u8; 256];
+
+    play_boot_log(&mut writer, &script).await?;
+
+    loop {
+        let bytes_read = reader.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            return Ok(());
+        }
+        for &byte in &buffer[..bytes_read] {
+            match byte {
+                // Ctrl+C：丢弃当前行
+                0x03 => {
+                    line.clear();
+                    let prompt = if state == ConsoleState::Shell { &script.shell_prompt } else { &script.login_prompt };
+                    write_str(&mut writer, &format!("^C\r\n{}", prompt)).await?;
+                }
+                b'\r' | b'\n' => {
+                    write_str(&mut writer, "\r\n").await?;
+                    let input = std::mem::take(&mut line);
+                    log(&format!("收到输入: {:?}", input));
+                    state = handle_line(&mut writer, &script, state, input.trim()).await?;
+                }
+                _ => {
+                    line.push(byte as char);
+                    // 密码输入不回显
+                    if !matches!(state, ConsoleState::Password(_)) {
+                        writer.write_all(&[byte]).await?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn handle_line(writer: &mut WriteHalf<DuplexStream>, script: &ConsoleScript, state: ConsoleState, input: &str) -> std::io::Result<ConsoleState> {
+    match state {
+        ConsoleState::Login => {
+            if input.is_empty() {
+                write_str(writer, &script.login_prompt).await?;
+                return Ok(ConsoleState::Login);
+            }
+            write_str(writer, "Password: ").await?;
+            Ok(ConsoleState::Password(input.to_string()))
+        }
+        ConsoleState::Password(user) => {
+            if user == script.username && input == script.password {
+                write_str(writer, &format!("Welcome to NanoKVM Pro (mock console)\r\n\r\n{}", script.shell_prompt)).await?;
+                Ok(ConsoleState::Shell)
+            } else {
+                sleep(Duration::from_millis(500)).await;
+                write_str(writer, &format!("\r\nLogin incorrect\r\n{}", script.login_prompt)).await?;
+                Ok(ConsoleState::Login)
+            }
+        }
+        ConsoleState::Shell => {
+            if input == "reboot" {
+                play_boot_log(writer, script).await?;
+                return Ok(ConsoleState::Login);
+            }
+            if !input.is_empty() {
+                if let Some(rule) = script.rule.iter().find(|rule| input.starts_with(&rule.command)) {
+                    write_str(writer, &format!("{}\r\n", rule.reply)).await?;
+                }
+            }
+            write_str(writer, &script.shell_prompt).await?;
+            Ok(ConsoleState::Shell)
+        }
+    }
+}
@@ -1,6 +1,17 @@
-use serialport::{SerialPortType};
-use tokio_serial::{SerialStream, new, SerialPortBuilderExt};
-use tokio_serial::SerialPort; // 引入特质以使用 DTR/RTS 方法
+pub mod transport;
+pub mod mock_console;
+pub mod stream;
+pub mod script;
+pub mod capture;
+pub mod hub;
+pub mod terminal;
+pub mod power;
+pub mod boot;
+pub mod xmodem;
+pub mod uboot;
+pub mod shell;
+pub mod transfer;
+
 use std::time::{Duration, Instant};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
@@ -9,14 +20,16 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt, ErrorKind};
 use tokio::time::{sleep, timeout};
 use tauri::async_runtime::spawn;
 use lazy_static::lazy_static;
-use strip_ansi_escapes::strip;
 use crate::APP_EXIT;
+use stream::{ConsoleBuffer, ConsolePattern, ConsoleMatch};
+use capture::spawn_capture_task;
+use boot::spawn_boot_task;
+use terminal::wait_terminal_closed;
+use power::{apply_lines, mark_lines_dirty};
+use hub::{ConsoleSubscription, SubscribeFrom, subscribe_console, publish_console_data};
+use transport::{SerialTransport, TransportKind, scan_serial_port, open_usb_transport, open_pty_transport, open_memory_transport, take_reconnect_request};
 
-
-// 定义PID/VID和波特率
-const TARGET_VID: u16 = 0x1a86;
-const TARGET_PID: u16 = 0x55d3;
-const SERIAL_BAUD_RATE: u32 = 115200;
+use crate::function::save::get_serial_config;
 
 // const OVERTIME_ENTER: u64 = 3;          // 回车验活时间:3秒
 // const OVERTIME_LOST_KVM: u64 = 5;       // 最长无响应时间:5秒
@@ -56,10 +69,34 @@ fn log(msg: &str) {
 // 定义全局收发队列和USB工具状态
 lazy_static! {
     pub static ref SEND_QUEUE: Mutex<Option<mpsc::Sender<Vec<u8>>>> = Mutex::new(None);         // 串口数据发送队列
-    pub static ref RECEIVE_QUEUE: Mutex<Option<mpsc::Receiver<Vec<u8>>>> = Mutex::new(None);    // 串口数据接收队列
     pub static ref USB_TOOL_CONNECTED: AtomicBool = AtomicBool::new(false);                         // USB工具状态全局变量
     static ref WINDOW: Mutex<Vec<u32>> = Mutex::new(Vec::with_capacity(FILTER_WINDOW_SIZE));    // 滑动滤波器窗口
     pub static ref DATA_DENSITY: AtomicU32 = AtomicU32::new(0);                                    // 数据密度全局变量
+    static ref CONSOLE_READER: Mutex<ConsoleReader> = Mutex::new(ConsoleReader::new());           // 状态机使用的控制台读取器
+}
+
+// 状态机的控制台读取器：自己的订阅位置加上文本重组缓冲
+struct ConsoleReader {
+    subscription: ConsoleSubscription,
+    buffer: ConsoleBuffer,
+}
+
+impl ConsoleReader {
+    fn new() -> ConsoleReader {
+        ConsoleReader {
+            subscription: subscribe_console("state-machine", SubscribeFrom::Now),
+            buffer: ConsoleBuffer::default(),
+        }
+    }
+}
+
+// 模式检测结果
+#[derive(Debug, Clone)]
+pub enum DetectResult {
+    Matched(ConsoleMatch),  // 命中某个模式
+    Unmatched,              // 有数据但未命中
+    NoData,                 // 超时期间没有任何数据
+    LowDensity,             // 数据密度低于限额
 }
 
 // 滑动滤波器函数，数组位于函数内部，每输入一个数字，就计算当前窗口的总数值，并删除最早的一个
@@ -79,26 +116,26 @@ async fn clear_slide_filter() {
     window.clear();
 }
 
-// 扫描指定PID/VID的串口
-fn scan_serial_port(target_pid: u16, target_vid: u16) -> Option<String> {
-    if let Ok(ports) = serialport::available_ports() {
-        for port_info in ports {
-            if let SerialPortType::UsbPort(usb_info) = port_info.port_type {
-                if usb_info.vid == target_vid && usb_info.pid == target_pid {
-                    return Some(port_info.port_name);
-                }
+// 根据传输方式打开串口，返回统一的传输对象
+async fn connect_transport(kind: TransportKind) -> Option<Arc<Mutex<Box<dyn SerialTransport>>>> {
+    let result = match kind {
+        // 每次连接时重新读取配置，修改工具列表后无需重启
+        TransportKind::Usb => match scan_serial_port(&get_serial_config()) {
+            Some((port_name, tool)) => {
+                log(&format!("找到串口工具 {:04x}:{:04x}: {}, 波特率 {}", tool.vid, tool.pid, port_name, tool.baud_rate));
+                open_usb_transport(&port_name, &tool)
             }
+            None => return None,
+        },
+        TransportKind::Pty => open_pty_transport(),
+        TransportKind::Memory => open_memory_transport(),
+    };
+
+    match result {
+        Ok(transport) => {
+            log(&format!("已打开串口传输: {}", transport.describe()));
+            Some(Arc::new(Mutex::new(transport)))
         }
-    }
-    None
-}
-
-// 连接指定（PID/VID）串口函数
-async fn connect_serial_port(port_name: &str) -> Option<Arc<Mutex<SerialStream>>> {
-    let port = new(port_name, SERIAL_BAUD_RATE);
-    
-    match port.open_native_async() {
-        Ok(serial_port) => Some(Arc::new(Mutex::new(serial_port))),
         Err(e) => {
             log(&format!("打开串口错误: {:?}", e));
             None
@@ -108,7 +145,21 @@ async fn connect_serial_port(port_name: &str) -> Option<Arc<Mutex<SerialStream>>
 
 // 串口发送函数（向写队列写）
 pub async fn serial_send(data: &str) {
-    let send_data = data.as_bytes().to_vec();
+    serial_send_bytes(data.as_bytes()).await;
+}
+
+// 发送原始字节，用于YMODEM等二进制传输
+// 终端打开期间等待其关闭后再发送，自动测试不会向工程师正在操作的终端写入
+pub async fn serial_send_bytes(data: &[u8]) {
+    if wait_terminal_closed().await {
+        log("终端已关闭，继续发送");
+    }
+    send_to_queue(data).await;
+}
+
+// 直接写入发送队列，不检查终端状态，只用于终端按键
+pub(super) async fn send_to_queue(data: &[u8]) {
+    let send_data = data.to_vec();
     
     // 获取全局发送队列
     let send_queue_guard = SEND_QUEUE.lock().await;
@@ -121,69 +172,58 @@ pub async fn serial_send(data: &str) {
     }
 }
 
-// 串口接收函数（从读队列读）
-pub async fn serial_receive() -> String {
-    // 获取全局接收队列的可变引用，不移除队列
-    let mut receive_queue_guard = RECEIVE_QUEUE.lock().await;
-    
-    if let Some(receive_queue) = &mut *receive_queue_guard {
-        match receive_queue.try_recv() {
-            Ok(data) => String::from_utf8_lossy(&data).to_string(),
-            Err(tokio::sync::mpsc::error::TryRecvError::Empty) => String::new(),
-            Err(tokio::sync::mpsc::error::TryRecvError::Disconnected) => String::from("接收队列已关闭"),
-        }
-    } else {
-        String::from("接收队列未初始化")
+// 把订阅中的新数据转移到控制台缓冲，返回新增的文本字节数
+async fn fill_console_buffer() -> usize {
+    let mut reader = CONSOLE_READER.lock().await;
+    let chunk = reader.subscription.try_recv();
+    if chunk.lost > 0 {
+        log(&format!("{} 读取过慢，丢失 {} 字节串口数据", reader.subscription.name(), chunk.lost));
     }
+    reader.buffer.push(&chunk.data)
 }
 
-// 从队列获取数据并清理 ANSI 转义序列
+// 从控制台缓冲获取尚未匹配的数据（已清理 ANSI 转义序列）
 pub async fn serial_receive_clean() -> String {
-    // 先获取原始数据
-    let raw_data = serial_receive().await;
-    
-    // 如果数据为空或包含错误信息，直接返回
-    if raw_data.is_empty() || 
-       raw_data == "接收队列已关闭" || 
-       raw_data == "接收队列未初始化" {
-        return raw_data;
-    }
-    
-    // 清理 ANSI 转义序列
-    let cleaned_bytes = strip(raw_data.as_bytes());
-    match String::from_utf8(cleaned_bytes) {
-        Ok(cleaned_string) => {
-            // 去除首尾空白字符
-            cleaned_string.trim().to_string()
-        }
-        Err(_) => raw_data, // 如果 UTF-8 转换失败，返回原始数据
-    }
+    fill_console_buffer().await;
+    let pending = CONSOLE_READER.lock().await.buffer.take_pending();
+    // 去除首尾空白字符
+    pending.trim().to_string()
+}
+
+// 丢弃控制台缓冲中尚未匹配的数据
+pub async fn clear_console_buffer() {
+    fill_console_buffer().await;
+    CONSOLE_READER.lock().await.buffer.consume_all();
+}
+
+// 控制台最近的输出，用于失败时展示
+pub async fn get_console_tail(bytes: usize) -> String {
+    fill_console_buffer().await;
+    CONSOLE_READER.lock().await.buffer.tail(bytes)
 }
 
 // 串口管理线程函数
 pub fn serial_management_task() {
     spawn(async move {
-        let mut serial_port: Option<Arc<Mutex<SerialStream>>> = None;
+        let transport_kind = TransportKind::from_env();
+        let mut serial_port: Option<Arc<Mutex<Box<dyn SerialTransport>>>> = None;
         let mut serial_connect_err_count = 0;
-        let mut io_value: bool = true;
         clear_slide_filter().await;
         
-        // 创建发送和接收队列
+        // 创建发送队列，接收数据通过广播分发给各个订阅者
         let (send_queue_tx, mut send_queue_rx) = mpsc::channel::<Vec<u8>>(100);
-        let (receive_queue_tx, receive_queue_rx) = mpsc::channel::<Vec<u8>>(100);
         
         // 将队列存储到全局静态变量中
         {
             let mut send_queue_guard = SEND_QUEUE.lock().await;
             *send_queue_guard = Some(send_queue_tx);
         }
-        
-        {
-            let mut receive_queue_guard = RECEIVE_QUEUE.lock().await;
-            *receive_queue_guard = Some(receive_queue_rx);
-        }
 
-        log("串口管理线程已启动");
+        // 启动控制台录制和开机分析消费者
+        spawn_capture_task();
+        spawn_boot_task();
+
+        log(&format!("串口管理线程已启动, 传输方式: {:?}", transport_kind));
    // 主循环
         loop {
             // 检查退出标志
@@ -193,17 +233,23 @@ pub fn serial_management_task() {
                 break;
             }
             
+            // 操作员指定了新的串口，断开当前连接
+            if take_reconnect_request() && serial_port.is_some() {
+                log("指定串口已变更，重新连接");
+                serial_port = None;
+            }
+
             // 如果没有连接就开始尝试连接
             if serial_port.is_none() {
                 log("串口连接错误，尝试连接...");
                 // 尝试连接
-                if let Some(port_name) = scan_serial_port(TARGET_PID, TARGET_VID) {
-                    if let Some(port) = connect_serial_port(&port_name).await {
-                        log("成功连接串口");
-                        USB_TOOL_CONNECTED.store(true, Ordering::Relaxed);
-                        serial_port = Some(port);
-                        serial_connect_err_count = 0;
-                    }
+                if let Some(port) = connect_transport(transport_kind).await {
+                    log("成功连接串口");
+                    USB_TOOL_CONNECTED.store(true, Ordering::Relaxed);
+                    // 新连接的串口需要重新写入电源和复位电平
+                    mark_lines_dirty();
+                    serial_port = Some(port);
+                    serial_connect_err_count = 0;
                 }
                 
                 // 如果还是没有
@@ -243,7 +289,7 @@ pub fn serial_management_task() {
                 }
             }
             
-            // 2. 尝试从串口读取数据并发送到接收队列
+            // 2. 尝试从串口读取数据并广播给所有订阅者
             if let Ok(mut port_guard) = serial_port.as_ref().unwrap().try_lock() {
                 let mut buffer = vec![0; 1024];
                 match timeout(Duration::from_millis(MAX_RECEIVE_DATA_TIMEOUT_MS), port_guard.read(&mut buffer)).await {
@@ -253,21 +299,8 @@ pub fn serial_management_task() {
                         DATA_DENSITY.store(density, Ordering::Relaxed);
 
                         if bytes_read > 0 {
-                            let received_data = buffer[..bytes_read].to_vec();
-                            // log(&format!("接收数据: {:?}", received_data));
-                            // if let Err(e) = receive_queue_tx.send(received_data).await {
-                            //     log(&format!("发送数据到接收队列失败: {:?}", e));
-                            // }
-                            match receive_queue_tx.try_send(received_data) {
-                                Ok(_) => {},
-                                Err(crate::function::serial::mpsc::error::TrySendError::Full(data)) => {
-                                    log("队列已满，丢弃最早数据");
-                                    _ = serial_receive().await;
-                                    // 直接重试发送当前数据
-                                    let _ = receive_queue_tx.try_send(data);
-                                },
-                                Err(_) => {},
-                            }
+                            // log(&format!("接收数据: {:?}", &buffer[..bytes_read]));
+                            publish_console_data(&buffer[..bytes_read]);
                         }
                     }
                     Ok(Err(e)) => {
@@ -285,11 +318,11 @@ pub fn serial_management_task() {
                 }
             }
 
-            // ctrl dtr&rts io
+            // 3. 写入电源(DTR)和复位(RTS)电平
             if let Ok(mut port_guard) = serial_port.as_ref().unwrap().try_lock() {
-                let _ = port_guard.write_data_terminal_ready(io_value);
-                let _ = port_guard.write_request_to_send(io_value);
-                io_value = !io_value;
+                if let Err(e) = apply_lines(&mut **port_guard) {
+                    log(&format!("设置DTR/RTS失败: {:?}", e));
+                }
             }
 
             // log(&format!("当前数据密度: {:?}", *DATA_DENSITY.lock().await));
@@ -309,29 +342,8 @@ pub fn serial_management_task() {
 // 等待指定串口数据，带超时设置，单位：毫秒
 #[allow(dead_code)]
 pub async fn wait_for_serial_data(expected: &[u8], timeout_ms: u64) -> bool {
-    let timeout_time = Instant::now() + Duration::from_millis(timeout_ms);
-    loop {
-        // 检查退出标志
-        if APP_EXIT.load(Ordering::Relaxed) {
-            log("程序退出，中断wait_for_serial_data循环");
-            return false;
-        }
-        
-        let received = serial_receive_clean().await;
-        if !received.is_empty() {
-            if received.as_bytes().windows(expected.len()).any(|window| window == expected) {
-                // log(&format!("当前串口数据: {:?}", received));
-                return true;    
-            }
-        }
-        // log(&format!("等待串口数据{:?}当前超时剩余时间: {:?}", expected, timeout_time - Instant::now()));
-        if Instant::now() >= timeout_time {
-            return false;
-        }
-
-        // 短暂休眠，避免忙等待，使用最长的队列填充时间
-        // sleep(Duration::from_millis(MAX_RECEIVE_DATA_TIMEOUT_MS)).await;
-    }
+    let pattern = ConsolePattern::literal(&String::from_utf8_lossy(expected));
+    matches!(detect_serial_pattern(&[pattern], timeout_ms, 0).await, DetectResult::Matched(_))
 }
 
 // 执行命令并等待完成
@@ -346,15 +358,52 @@ pub async fn execute_command_and_wait(command: &str, ret_str: &str, timeout_ms:
 // 检测接收到的数据中是否包含一个列表中的字符串，如果包含回复匹配的字符串，不包含回复”UNMATCHED“，如果无数据回复”NO-DATA“，如果过程中数据密度过低则返回”LOW-DENSITY“
 // 对于这个函数遇到一个问题：登录期间较长比如给定的30s，如果期间断开KVM连接（无数据/数据密度过低），或断开工具连接需要直接返回一定的数值，而不是硬等（可能需要数据密度做判别了）
 pub async fn detect_serial_string(patterns: &[&str], timeout_ms: u64, min_density: u32) -> String {
-    // log(&format!("detect_serial_string timeout_ms: {}", timeout_ms));
-    let timeout_time = Instant::now() + Duration::from_millis(timeout_ms);
-    let mut has_data = false;
+    let patterns: Vec<ConsolePattern> = patterns.iter().map(|pattern| ConsolePattern::literal(pattern)).collect();
+    match detect_serial_pattern(&patterns, timeout_ms, min_density).await {
+        DetectResult::Matched(found) => found.pattern,
+        DetectResult::Unmatched => "UNMATCHED".to_string(),
+        DetectResult::NoData => "NO-DATA".to_string(),
+        DetectResult::LowDensity => "LOW-DENSITY".to_string(),
+    }
+}
+
+// 检测正则表达式，命中时返回匹配结果（含捕获组），超时或无数据返回None
+pub async fn detect_serial_regex(patterns: &[&str], timeout_ms: u64) -> Option<ConsoleMatch> {
+    let mut compiled = Vec::new();
+    for pattern in patterns {
+        match ConsolePattern::regex(pattern) {
+            Ok(regex) => compiled.push(regex),
+            Err(e) => {
+                log(&format!("正则表达式错误 {}: {}", pattern, e));
+                return None;
+            }
+        }
+    }
+    match detect_serial_pattern(&compiled, timeout_ms, 0).await {
+        DetectResult::Matched(found) => Some(found),
+        _ => None,
+    }
+}
+
+// 在控制台数据流中检测模式，模式可以跨越多个数据块
+// 命中后只消费到命中位置，未命中超时时丢弃期间收到的全部数据
+pub async fn detect_serial_pattern(patterns: &[ConsolePattern], timeout_ms: u64, min_density: u32) -> DetectResult {
+    // log(&format!("detect_serial_pattern timeout_ms: {}", timeout_ms));
+    let mut timeout_time = Instant::now() + Duration::from_millis(timeout_ms);
+    let mut has_data = CONSOLE_READER.lock().await.buffer.pending_len() > 0;
     
     loop {
         // 检查退出标志
         if APP_EXIT.load(Ordering::Relaxed) {
-            log("程序退出，中断detect_serial_string循环");
-            return "UNMATCHED".to_string();
+            log("程序退出，中断detect_serial_pattern循环");
+            return DetectResult::Unmatched;
+        }
+
+        // 终端打开期间暂停计时，关闭后丢弃期间的输出并重新计时
+        if wait_terminal_closed().await {
+            clear_console_buffer().await;
+            timeout_time = Instant::now() + Duration::from_millis(timeout_ms);
+            has_data = false;
         }
         
         // log("判断数据密度");
@@ -362,31 +411,31 @@ pub async fn detect_serial_string(patterns: &[&str], timeout_ms: u64, min_densit
             let density = get_current_data_density().await;
             if density < min_density {
                 // log(&format!("当前数据密度: {:?}", density));
-                return "LOW-DENSITY".to_string();
+                return DetectResult::LowDensity;
             }
         }
         // log("等待串口数据");
-        let received = serial_receive_clean().await;
-        if !received.is_empty() {
+        if fill_console_buffer().await > 0 {
             has_data = true;
+        }
 
-            log(&format!("当前串口数据: {:?}", received));
-            
-            // 检查所有匹配模式
-            for pattern in patterns {
-                if received.contains(pattern) {
-                    return pattern.to_string();
-                }
+        {
+            let mut reader = CONSOLE_READER.lock().await;
+            let console = &mut reader.buffer;
+            if let Some(found) = console.find(patterns) {
+                log(&format!("命中模式: {:?}", found.pattern));
+                return DetectResult::Matched(found);
             }
-        }
-        
-        // 检查超时
-        // log(&format!("等待串口数据超时剩余时间: {:?}", timeout_time - Instant::now()));
-        if Instant::now() >= timeout_time {
-            if !has_data {
-                return "NO-DATA".to_string();
-            } else {
-                return "UNMATCHED".to_string();
+
+            // 检查超时
+            if Instant::now() >= timeout_time {
+                log(&format!("未命中模式，当前串口数据: {:?}", console.pending_text()));
+                console.consume_all();
+                if !has_data {
+                    return DetectResult::NoData;
+                } else {
+                    return DetectResult::Unmatched;
+                }
             }
         }
         
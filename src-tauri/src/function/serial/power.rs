@@ -0,0 +1,139 @@
+// 待测板卡电源和复位控制：治具把USB工具的DTR接到KVM电源，RTS接到KVM复位
+// 上层只修改期望电平，由串口管理线程在持有串口时写入，并在重新连接后自动恢复
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+use super::transport::SerialTransport;
+use super::is_usb_tool_connected;
+use super::boot::mark_boot_start;
+
+// DTR为该电平时KVM上电，治具极性相反时修改这里
+const POWER_ON_DTR_LEVEL: bool = true;
+// RTS为该电平时KVM保持复位
+const RESET_HOLD_RTS_LEVEL: bool = true;
+// 等待串口管理线程写入电平的最长时间
+const APPLY_TIMEOUT_MS: u64 = 500;
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = true;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[power]{}", msg);
+    }
+}
+
+static POWER_ON: AtomicBool = AtomicBool::new(true);        // 期望电源状态，默认上电
+static RESET_HELD: AtomicBool = AtomicBool::new(false);     // 期望复位状态，默认释放
+static LINE_REQUEST: AtomicU64 = AtomicU64::new(1);         // 期望电平版本号，每次修改加一
+static LINE_APPLIED: AtomicU64 = AtomicU64::new(0);         // 已写入串口的版本号
+
+// 复位脉冲时序
+#[derive(Debug, Clone, Copy)]
+pub struct ResetTiming {
+    pub hold_ms: u64,       // 保持复位的时间
+    pub settle_ms: u64,     // 释放复位后等待稳定的时间
+}
+
+impl Default for ResetTiming {
+    fn default() -> Self {
+        ResetTiming { hold_ms: 200, settle_ms: 100 }
+    }
+}
+
+// 修改期望电平并等待串口管理线程写入，工具未连接或超时返回false
+async fn request_lines(power_on: bool, reset_held: bool) -> bool {
+    POWER_ON.store(power_on, Ordering::SeqCst);
+    RESET_HELD.store(reset_held, Ordering::SeqCst);
+    let request = LINE_REQUEST.fetch_add(1, Ordering::SeqCst) + 1;
+
+    if !is_usb_tool_connected().await {
+        log("USB工具未连接，电平将在连接后写入");
+        return false;
+    }
+    let deadline = Instant::now() + Duration::from_millis(APPLY_TIMEOUT_MS);
+    while LINE_APPLIED.load(Ordering::SeqCst) < request {
+        if Instant::now() >= deadline {
+            log("等待写入DTR/RTS超时");
+            return false;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    true
+}
+
+// 上电
+#[allow(dead_code)]
+pub async fn power_on() -> bool {
+    log("KVM上电");
+    request_lines(true, RESET_HELD.load(Ordering::SeqCst)).await
+}
+
+// 断电
+#[allow(dead_code)]
+pub async fn power_off() -> bool {
+    log("KVM断电");
+    request_lines(false, RESET_HELD.load(Ordering::SeqCst)).await
+}
+
+// 保持复位
+pub async fn hold_reset() -> bool {
+    log("KVM保持复位");
+    request_lines(POWER_ON.load(Ordering::SeqCst), true).await
+}
+
+// 释放复位
+pub async fn release_reset() -> bool {
+    log("KVM释放复位");
+    request_lines(POWER_ON.load(Ordering::SeqCst), false).await
+}
+
+// 复位脉冲：保持复位 hold_ms 后释放，再等待 settle_ms
+pub async fn pulse_reset(timing: ResetTiming) -> bool {
+    log(&format!("KVM复位脉冲: {:?}", timing));
+    if !hold_reset().await {
+        return false;
+    }
+    sleep(Duration::from_millis(timing.hold_ms)).await;
+    let released = release_reset().await;
+    if released {
+        mark_boot_start("reset");
+    }
+    sleep(Duration::from_millis(timing.settle_ms)).await;
+    released
+}
+
+// 断电重启：断电 off_ms 后重新上电，并确保复位已释放
+pub async fn power_cycle(off_ms: u64) -> bool {
+    log(&format!("KVM断电重启, 断电 {}ms", off_ms));
+    if !request_lines(false, false).await {
+        return false;
+    }
+    sleep(Duration::from_millis(off_ms)).await;
+    let powered = request_lines(true, false).await;
+    if powered {
+        mark_boot_start("power_cycle");
+    }
+    powered
+}
+
+// 串口重新连接后需要重新写入电平
+pub(super) fn mark_lines_dirty() {
+    LINE_APPLIED.store(0, Ordering::SeqCst);
+}
+
+// 由串口管理线程调用：期望电平有变化时写入DTR/RTS
+pub(super) fn apply_lines(port: &mut dyn SerialTransport) -> std::io::Result<()> {
+    let request = LINE_REQUEST.load(Ordering::SeqCst);
+    if LINE_APPLIED.load(Ordering::SeqCst) >= request {
+        return Ok(());
+    }
+    let power_on = POWER_ON.load(Ordering::SeqCst);
+    let reset_held = RESET_HELD.load(Ordering::SeqCst);
+    port.set_dtr(if power_on { POWER_ON_DTR_LEVEL } else { !POWER_ON_DTR_LEVEL })?;
+    port.set_rts(if reset_held { RESET_HOLD_RTS_LEVEL } else { !RESET_HOLD_RTS_LEVEL })?;
+    LINE_APPLIED.store(request, Ordering::SeqCst);
+    Ok(())
+}
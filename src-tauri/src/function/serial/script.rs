@@ -0,0 +1,354 @@
+// 串口期望脚本：把“发送-等待-判断”的命令链写成一组步骤，由同一个执行器顺序执行
+// 每一步包含发送内容、期望模式、超时、失败分支和捕获变量，失败时报告具体步骤和当时的控制台输出
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use super::stream::ConsolePattern;
+use super::{serial_send, detect_serial_pattern, get_console_tail, DetectResult};
+
+// 默认单步超时时间
+const DEFAULT_STEP_TIMEOUT_MS: u64 = 1000;
+// 串口登录被拒绝时的提示
+const LOGIN_INCORRECT: &str = "Login incorrect";
+const LOGIN_LOCKED: &str = r"(?i)account (is )?locked|locked due to|maximum number of tries exceeded|too many (failed|authentication)";
+// 失败报告中附带的控制台输出长度
+const FAILURE_CONSOLE_TAIL: usize = 1024;
+// 单个步骤最多执行次数，防止失败分支互相跳转形成死循环
+const MAX_STEP_VISITS: u32 = 16;
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = false;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[script]{}", msg);
+    }
+}
+
+// 步骤失败后的处理方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum OnFail {
+    Abort,              // 终止脚本并返回失败
+    Continue,           // 忽略失败，继续下一步
+    Retry(u32),         // 重试本步骤指定次数，仍失败则终止
+    Goto(String),       // 跳转到指定名称的步骤
+}
+
+// 单个脚本步骤
+#[derive(Debug, Clone)]
+pub struct ScriptStep {
+    pub name: String,
+    pub send: String,                   // 发送内容，可用 ${变量名} 引用已捕获的变量
+    pub expect: Vec<ConsolePattern>,    // 期望模式，任意一个命中即成功；为空时只发送不等待
+    pub timeout_ms: u64,
+    pub delay_ms: u64,                  // 发送前等待时间
+    pub on_fail: OnFail,
+    pub goto: Vec<Option<String>>,      // 命中第i个模式后跳转的步骤，None表示继续下一步
+    pub reject: Vec<bool>,              // 命中第i个模式表示失败，如"Login incorrect"
+}
+
+impl ScriptStep {
+    pub fn new(name: &str, send: &str) -> ScriptStep {
+        ScriptStep {
+            name: name.to_string(),
+            send: send.to_string(),
+            expect: Vec::new(),
+            timeout_ms: DEFAULT_STEP_TIMEOUT_MS,
+            delay_ms: 0,
+            on_fail: OnFail::Abort,
+            goto: Vec::new(),
+            reject: Vec::new(),
+        }
+    }
+
+    // 添加普通字符串期望
+    pub fn expect(mut self, text: &str) -> ScriptStep {
+        self.expect.push(ConsolePattern::literal(text));
+        self
+    }
+
+    // 添加正则期望，命名捕获组会保存为脚本变量
+    // 脚本中的正则都是代码里的常量，写错时直接panic，避免步骤悄悄少一个期望
+    pub fn expect_regex(mut self, expr: &str) -> ScriptStep {
+        let pattern = ConsolePattern::regex(expr)
+            .unwrap_or_else(|e| panic!("步骤 {} 正则表达式错误 {}: {}", self.name, expr, e));
+        log(&format!("步骤 {} 添加正则期望 {}", self.name, expr));
+        self.expect.push(pattern);
+        self
+    }
+
+    // 命中刚添加的期望后跳转到指定步骤
+    pub fn then_goto(mut self, step: &str) -> ScriptStep {
+        if !self.expect.is_empty() {
+            self.goto.resize(self.expect.len(), None);
+            self.goto[self.expect.len() - 1] = Some(step.to_string());
+        }
+        self
+    }
+
+    // 刚添加的期望表示失败，命中后按 Rejected 处理
+    pub fn rejected(mut self) -> ScriptStep {
+        if !self.expect.is_empty() {
+            self.reject.resize(self.expect.len(), false);
+            self.reject[self.expect.len() - 1] = true;
+        }
+        self
+    }
+
+    pub fn timeout(mut self, timeout_ms: u64) -> ScriptStep {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    pub fn delay(mut self, delay_ms: u64) -> ScriptStep {
+        self.delay_ms = delay_ms;
+        self
+    }
+
+    pub fn on_fail(mut self, on_fail: OnFail) -> ScriptStep {
+        self.on_fail = on_fail;
+        self
+    }
+}
+
+// 步骤失败原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum FailReason {
+    Unmatched,              // 有数据但未命中
+    NoData,                 // 超时期间没有任何数据
+    UnknownStep(String),    // 跳转目标不存在
+    TooManyVisits,          // 步骤执行次数过多
+    Rejected(String),       // 命中了表示失败的期望，内容为命中的模式
+}
+
+impl fmt::Display for FailReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailReason::Unmatched => write!(f, "未匹配到期望内容"),
+            FailReason::NoData => write!(f, "没有收到串口数据"),
+            FailReason::UnknownStep(name) => write!(f, "跳转目标步骤 {} 不存在", name),
+            FailReason::TooManyVisits => write!(f, "步骤重复执行超过 {} 次", MAX_STEP_VISITS),
+            FailReason::Rejected(pattern) => write!(f, "命中失败内容 {:?}", pattern),
+        }
+    }
+}
+
+// 脚本失败报告
+#[derive(Debug, Clone)]
+pub struct ScriptFailure {
+    pub script: String,
+    pub step: String,
+    pub send: String,
+    pub expect: Vec<String>,
+    pub reason: FailReason,
+    pub console: String,        // 失败时控制台最近的输出（已清理ANSI）
+}
+
+impl fmt::Display for ScriptFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "脚本 {} 在步骤 {} 失败: {}，发送 {:?}，期望 {:?}\n控制台输出:\n{}",
+            self.script, self.step, self.reason, self.send, self.expect, self.console)
+    }
+}
+
+// 脚本执行结果
+#[derive(Debug, Clone, Default)]
+pub struct ScriptOutcome {
+    pub vars: HashMap<String, String>,          // 执行过程中捕获的变量
+}
+
+// 期望脚本
+#[derive(Debug, Clone)]
+pub struct ExpectScript {
+    pub name: String,
+    pub steps: Vec<ScriptStep>,
+    pub vars: HashMap<String, String>,  // 初始变量，供发送内容引用
+}
+
+impl ExpectScript {
+    pub fn new(name: &str) -> ExpectScript {
+        ExpectScript { name: name.to_string(), steps: Vec::new(), vars: HashMap::new() }
+    }
+
+    pub fn step(mut self, step: ScriptStep) -> ExpectScript {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn var(mut self, name: &str, value: &str) -> ExpectScript {
+        self.vars.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    // 按顺序执行步骤，命中带跳转的期望或按失败分支跳转时改变执行位置
+    // 命中表示失败的期望（如"Login incorrect"）时不重试也不跳转，避免重复输入密码导致账户锁定
+    pub async fn run(&self) -> Result<ScriptOutcome, ScriptFailure> {
+        let mut outcome = ScriptOutcome { vars: self.vars.clone() };
+        let mut visits = vec![0u32; self.steps.len()];
+        let mut index = 0;
+
+        while index < self.steps.len() {
+            let step = &self.steps[index];
+            visits[index] += 1;
+            if visits[index] > MAX_STEP_VISITS {
+                return Err(self.failure(step, &outcome, FailReason::TooManyVisits).await);
+            }
+
+            let reason = match self.run_step(step, &mut outcome).await {
+                Ok(Some(target)) => match self.find_step(target) {
+                    Some(next) => { index = next; continue; }
+                    None => FailReason::UnknownStep(target.to_string()),
+                },
+                Ok(None) => { index += 1; continue; }
+                Err(reason) => reason,
+            };
+
+            log(&format!("脚本 {} 步骤 {} 失败: {}", self.name, step.name, reason));
+            let branchable = !matches!(reason, FailReason::Rejected(_) | FailReason::UnknownStep(_));
+            match &step.on_fail {
+                OnFail::Continue => index += 1,
+                OnFail::Retry(count) if branchable && visits[index] <= *count => {}
+                OnFail::Goto(target) if branchable => match self.find_step(target) {
+                    Some(next) => index = next,
+                    None => return Err(self.failure(step, &outcome, FailReason::UnknownStep(target.clone())).await),
+                },
+                _ => return Err(self.failure(step, &outcome, reason).await),
+            }
+        }
+        Ok(outcome)
+    }
+
+    // 执行单个步骤，成功时返回需要跳转的步骤名
+    async fn run_step<'a>(&self, step: &'a ScriptStep, outcome: &mut ScriptOutcome) -> Result<Option<&'a str>, FailReason> {
+        if step.delay_ms > 0 {
+            sleep(Duration::from_millis(step.delay_ms)).await;
+        }
+        if !step.send.is_empty() {
+            let text = substitute_vars(&step.send, &outcome.vars);
+            log(&format!("步骤 {} 发送: {:?}", step.name, text));
+            serial_send(&text).await;
+        }
+        if step.expect.is_empty() {
+            return Ok(None);
+        }
+
+        match detect_serial_pattern(&step.expect, step.timeout_ms, 0).await {
+            DetectResult::Matched(found) => {
+                log(&format!("步骤 {} 命中: {:?}", step.name, found.pattern));
+                if step.reject.get(found.index).copied().unwrap_or(false) {
+                    return Err(FailReason::Rejected(found.pattern));
+                }
+                outcome.vars.extend(found.named);
+                Ok(step.goto.get(found.index).and_then(|target| target.as_deref()))
+            }
+            // 脚本步骤不检查数据密度（min_density为0），不会返回LowDensity
+            DetectResult::Unmatched | DetectResult::LowDensity => Err(FailReason::Unmatched),
+            DetectResult::NoData => Err(FailReason::NoData),
+        }
+    }
+
+    fn find_step(&self, name: &str) -> Option<usize> {
+        self.steps.iter().position(|step| step.name == name)
+    }
+
+    async fn failure(&self, step: &ScriptStep, outcome: &ScriptOutcome, reason: FailReason) -> ScriptFailure {
+        ScriptFailure {
+            script: self.name.clone(),
+            step: step.name.clone(),
+            send: substitute_vars(&step.send, &outcome.vars),
+            expect: step.expect.iter().map(|pattern| pattern.as_str().to_string()).collect(),
+            reason,
+            console: get_console_tail(FAILURE_CONSOLE_TAIL).await,
+        }
+    }
+}
+
+// 把 ${变量名} 替换为变量值，未定义的变量保持原样
+fn substitute_vars(text: &str, vars: &HashMap<String, String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find('}') {
+            Some(end) => {
+                let name = &after[..end];
+                match vars.get(name) {
+                    Some(value) => result.push_str(value),
+                    None => result.push_str(&rest[start..start + 3 + end]),
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+// 串口登录失败类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoginFailure {
+    Incorrect,      // 用户名或密码错误
+    Locked,         // 多次失败后账户被锁定
+    NoResponse,     // 没有出现期望的提示，可能还在开机或串口异常
+}
+
+impl LoginFailure {
+    // 根据登录脚本的失败报告判断失败类型
+    pub fn from_script(failure: &ScriptFailure) -> LoginFailure {
+        match &failure.reason {
+            FailReason::Rejected(pattern) if pattern == LOGIN_INCORRECT => LoginFailure::Incorrect,
+            FailReason::Rejected(_) => LoginFailure::Locked,
+            _ => LoginFailure::NoResponse,
+        }
+    }
+}
+
+// 串口登录脚本：输入用户名、密码，等待shell提示符
+// 出现"Login incorrect"或账户锁定提示时立即失败，不再等待超时
+// 用户名没有等到Password提示时（开机日志刷掉了输入、串口丢字节），先回车唤醒login提示再重新输入
+pub fn login_script(username: &str, password: &str) -> ExpectScript {
+    ExpectScript::new("login")
+        .var("username", username)
+        .var("password", password)
+        .step(ScriptStep::new("username", "${username}\n")
+            .expect("Password")
+            .expect_regex(LOGIN_LOCKED).rejected()
+            .on_fail(OnFail::Goto("wake".to_string())))
+        .step(ScriptStep::new("password", "${password}\n")
+            .expect("Welcome").then_goto("prompt")
+            .expect(LOGIN_INCORRECT).rejected()
+            .expect_regex(LOGIN_LOCKED).rejected()
+            .timeout(5000))
+        // 回车直到重新出现login提示，再回到用户名步骤
+        .step(ScriptStep::new("wake", "\n")
+            .expect("login:").then_goto("username")
+            .timeout(2000)
+            .on_fail(OnFail::Retry(3)))
+        // 等待一部分初始信息后再确认提示符，没等到时再回车重试
+        .step(ScriptStep::new("prompt", "\n").expect(":~#").delay(100).on_fail(OnFail::Retry(3)))
+}
+
+// 以太网静态IP配置脚本：关闭DHCP，清空地址，设置本机IP和到测试机的路由
+// interrupt为true时先发送Ctrl+C，确保退出上一次未结束的ping
+pub fn static_ip_script(target_ip: &str, host_ip: &str, interrupt: bool) -> ExpectScript {
+    let mut script = ExpectScript::new("static_ip")
+        .var("target_ip", target_ip)
+        .var("host_ip", host_ip);
+    if interrupt {
+        script = script.step(ScriptStep::new("interrupt", "\x03").expect(":~#").on_fail(OnFail::Continue));
+    } else {
+        script = script.step(ScriptStep::new("stop_dhcp", "pkill dhclient\n").expect(":~#").on_fail(OnFail::Continue));
+    }
+    script
+        .step(ScriptStep::new("flush", "ip addr flush dev eth0\n").expect(":~#").on_fail(OnFail::Continue))
+        .step(ScriptStep::new("address", "ip addr add ${target_ip} dev eth0\n").expect(":~#").on_fail(OnFail::Continue))
+        .step(ScriptStep::new("route", "ip route add ${host_ip} dev eth0\n").expect(":~#").on_fail(OnFail::Continue))
+}
@@ -0,0 +1,158 @@
+// 串口Shell：在已登录的串口控制台上执行命令并取回输出和退出码，网口不可用时代替SSH
+// 命令前后各输出一个带序号的标记，标记在命令行中被引号拆开，回显不会误命中
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use lazy_static::lazy_static;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use super::hub::{subscribe_console, SubscribeFrom};
+use super::stream::{ConsoleBuffer, ConsolePattern};
+use super::serial_send;
+
+// 单条命令输出保留的最大长度
+const OUTPUT_CAPACITY: usize = 256 * 1024;
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = true;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[shell]{}", msg);
+    }
+}
+
+lazy_static! {
+    static ref SHELL_LOCK: Mutex<()> = Mutex::new(());     // 同一时间只执行一条命令
+}
+
+static COMMAND_SEQ: AtomicU64 = AtomicU64::new(1);          // 命令序号，用于区分标记
+
+// 串口命令失败原因
+#[derive(Debug, Clone)]
+pub enum ShellError {
+    Timeout(String),        // 命令没有在超时时间内结束
+    Exit(i32, String),      // 命令退出码非0，内容为退出码和输出
+    Cancelled,              // 被调用方取消
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShellError::Timeout(command) => write!(f, "串口命令 {:?} 执行超时", command),
+            ShellError::Exit(code, output) => write!(f, "命令执行失败，退出状态: {}\n输出: {}", code, output),
+            ShellError::Cancelled => write!(f, "串口命令已取消"),
+        }
+    }
+}
+
+impl std::error::Error for ShellError {}
+
+// 执行命令，返回退出码和输出（不含命令回显）
+pub async fn serial_shell_raw(command: &str, timeout_ms: u64) -> Result<(i32, String), ShellError> {
+    serial_shell_cancellable(command, timeout_ms, || false, |_| {}).await
+}
+
+// 把收到的数据按行交给on_line：开始标记所在行之前的回显跳过，遇到结束标记后不再输出
+struct LineFeed {
+    begin: String,
+    end: String,
+    pending: Vec<u8>,
+    started: bool,
+    finished: bool,
+}
+
+impl LineFeed {
+    fn new(seq: u64) -> LineFeed {
+        LineFeed {
+            begin: format!("__BEGIN_{}__", seq),
+            end: format!("__END_{}_", seq),
+            pending: Vec::new(),
+            started: false,
+            finished: false,
+        }
+    }
+
+    fn push(&mut self, data: &[u8], on_line: &mut impl FnMut(&str)) {
+        if self.finished {
+            return;
+        }
+        self.pending.extend_from_slice(data);
+        while let Some(newline) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+            if !self.started {
+                self.started = line.contains(&self.begin);
+                continue;
+            }
+            // 输出不以换行结尾时结束标记紧跟在最后一行之后
+            if let Some((last, _)) = line.split_once(&self.end) {
+                if !last.is_empty() {
+                    on_line(last);
+                }
+                self.finished = true;
+                self.pending.clear();
+                return;
+            }
+            on_line(line);
+        }
+    }
+}
+
+// 执行命令，is_cancelled返回true时中断；超时或取消时发送Ctrl-C结束前台命令
+// 命令执行期间输出逐行交给on_line，返回值仍包含完整输出
+pub async fn serial_shell_cancellable(command: &str, timeout_ms: u64, is_cancelled: impl Fn() -> bool, mut on_line: impl FnMut(&str)) -> Result<(i32, String), ShellError> {
+    let _guard = SHELL_LOCK.lock().await;
+    let seq = COMMAND_SEQ.fetch_add(1, Ordering::SeqCst);
+    let begin = format!("__BEGIN_{}__", seq);
+    let end_pattern = ConsolePattern::regex(&format!(r"__END_{}_(\d+)__", seq)).expect("结束标记正则无效");
+
+    // 订阅后再发送，保证不会漏掉输出
+    let mut subscription = subscribe_console("shell", SubscribeFrom::Now);
+    let mut buffer = ConsoleBuffer::new(OUTPUT_CAPACITY);
+    let mut feed = LineFeed::new(seq);
+    let body = command.trim().trim_end_matches(';');
+    serial_send(&format!("echo __BEGIN_\"\"{}__; {}; echo \"__END_\"\"{}_$?__\"\n", seq, body, seq)).await;
+
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    let mut begun = false;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            log(&format!("命令超时: {}", command));
+            serial_send("\x03").await;
+            return Err(ShellError::Timeout(command.to_string()));
+        }
+        if is_cancelled() {
+            log(&format!("命令已取消: {}", command));
+            serial_send("\x03").await;
+            return Err(ShellError::Cancelled);
+        }
+        let chunk = subscription.recv_timeout((deadline - now).as_millis().min(500) as u64).await;
+        if chunk.lost > 0 {
+            log(&format!("读取过慢，丢失 {} 字节输出", chunk.lost));
+        }
+        buffer.push(&chunk.data);
+        feed.push(&chunk.data, &mut on_line);
+
+        if !begun {
+            if buffer.find(&[ConsolePattern::literal(&begin)]).is_none() {
+                continue;
+            }
+            begun = true;
+        }
+        if let Some(found) = buffer.find(std::slice::from_ref(&end_pattern)) {
+            let code = found.captures.first().cloned().flatten()
+                .and_then(|code| code.parse::<i32>().ok())
+                .unwrap_or(-1);
+            // 命令回显在开始标记之前，去掉开始标记所在行的剩余部分即为输出
+            let output = found.before.replace('\r', "");
+            let output = output.split_once('\n').map(|(_, rest)| rest.trim_end().to_string()).unwrap_or_default();
+            log(&format!("命令结束({}): {}", code, command));
+            return Ok((code, output));
+        }
+    }
+}
@@ -0,0 +1,341 @@
+// 串口数据重组缓冲：把零散的串口数据块拼成连续的控制台文本流
+// 保留最近一段输出的滚动窗口，匹配可以跨越数据块边界，支持普通字符串和带捕获组的正则表达式
+use std::collections::HashMap;
+use regex::Regex;
+
+// 默认滚动窗口大小：64KB清理后的文本
+pub const DEFAULT_WINDOW_SIZE: usize = 64 * 1024;
+
+// ANSI转义序列解析状态
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AnsiState {
+    Ground,     // 普通文本
+    Escape,     // 收到ESC
+    Charset,    // ESC ( 等字符集指定，再吞掉一个字节
+    Csi,        // ESC [ ... 结束字节
+    Str,        // ESC ] / ESC P 等字符串序列，以BEL或ESC \ 结束
+    StrEscape,  // 字符串序列中收到ESC
+}
+
+// 流式ANSI清理器：状态跨数据块保留，转义序列被拆成两块时也能正确去除
+#[derive(Debug, Clone)]
+pub struct AnsiStripper {
+    state: AnsiState,
+}
+
+impl Default for AnsiStripper {
+    fn default() -> Self {
+        AnsiStripper { state: AnsiState::Ground }
+    }
+}
+
+impl AnsiStripper {
+    // 清理一段原始数据，结果追加到out；保留换行和制表符，丢弃其余控制字符
+    pub fn feed(&mut self, input: &[u8], out: &mut Vec<u8>) {
+        for &byte in input {
+            self.state = match self.state {
+                AnsiState::Ground => match byte {
+                    0x1b => AnsiState::Escape,
+                    b'\n' | b'\t' => { out.push(byte); AnsiState::Ground }
+                    0x00..=0x1f | 0x7f => AnsiState::Ground,
+                    _ => { out.push(byte); AnsiState::Ground }
+                },
+                AnsiState::Escape => match byte {
+                    b'[' => AnsiState::Csi,
+                    b']' | b'P' | b'X' | b'^' | b'_' => AnsiState::Str,
+                    b'(' | b')' | b'*' | b'+' => AnsiState::Charset,
+                    _ => AnsiState::Ground,
+                },
+                AnsiState::Charset => AnsiState::Ground,
+                AnsiState::Csi => match byte {
+                    0x40..=0x7e => AnsiState::Ground,
+                    _ => AnsiState::Csi,
+                },
+                AnsiState::Str => match byte {
+                    0x07 => AnsiState::Ground,
+                    0x1b => AnsiState::StrEscape,
+                    _ => AnsiState::Str,
+                },
+                AnsiState::StrEscape => match byte {
+                    b'\\' => AnsiState::Ground,
+                    _ => AnsiState::Str,
+                },
+            };
+        }
+    }
+}
+
+// 单个模式的原始匹配结果：(起始, 结束, 捕获组, 命名捕获组)
+type RawMatch = (usize, usize, Vec<Option<String>>, HashMap<String, String>);
+
+// 匹配模式：普通字符串或正则表达式
+#[derive(Debug, Clone)]
+pub enum ConsolePattern {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl ConsolePattern {
+    pub fn literal(text: &str) -> ConsolePattern {
+        ConsolePattern::Literal(text.to_string())
+    }
+
+    pub fn regex(expr: &str) -> Result<ConsolePattern, regex::Error> {
+        Ok(ConsolePattern::Regex(Regex::new(expr)?))
+    }
+
+    // 模式的原始文本，用于日志和返回值
+    pub fn as_str(&self) -> &str {
+        match self {
+            ConsolePattern::Literal(text) => text,
+            ConsolePattern::Regex(regex) => regex.as_str(),
+        }
+    }
+
+    // 在text中查找，返回(起始, 结束, 捕获组, 命名捕获组)
+    fn find_in(&self, text: &str) -> Option<RawMatch> {
+        match self {
+            ConsolePattern::Literal(literal) => {
+                let start = text.find(literal.as_str())?;
+                Some((start, start + literal.len(), Vec::new(), HashMap::new()))
+            }
+            ConsolePattern::Regex(regex) => {
+                let caps = regex.captures(text)?;
+                let whole = caps.get(0)?;
+                let groups = caps.iter().skip(1).map(|group| group.map(|m| m.as_str().to_string())).collect();
+                let mut named = HashMap::new();
+                for name in regex.capture_names().flatten() {
+                    if let Some(value) = caps.name(name) {
+                        named.insert(name.to_string(), value.as_str().to_string());
+                    }
+                }
+                Some((whole.start(), whole.end(), groups, named))
+            }
+        }
+    }
+}
+
+// 一次匹配的结果
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleMatch {
+    pub index: usize,                       // 命中的是第几个模式
+    pub pattern: String,                    // 命中模式的原始文本
+    pub before: String,                     // 上次匹配位置到本次命中之间的文本
+    pub captures: Vec<Option<String>>,      // 正则捕获组（不含第0组）
+    pub named: HashMap<String, String>,     // 正则命名捕获组
+}
+
+// 控制台滚动窗口：保存清理后的文本，并记录已经匹配消费到的位置
+#[derive(Debug)]
+pub struct ConsoleBuffer {
+    stripper: AnsiStripper,
+    window: Vec<u8>,
+    base: u64,          // window[0] 在整个数据流中的绝对位置
+    cursor: u64,        // 已匹配消费到的绝对位置，之后的文本为待匹配文本
+    capacity: usize,
+}
+
+impl ConsoleBuffer {
+    pub fn new(capacity: usize) -> ConsoleBuffer {
+        ConsoleBuffer {
+            stripper: AnsiStripper::default(),
+            window: Vec::with_capacity(capacity),
+            base: 0,
+            cursor: 0,
+            capacity,
+        }
+    }
+
+    // 追加一块原始串口数据，返回清理后新增的字节数
+    pub fn push(&mut self, raw: &[u8]) -> usize {
+        let before = self.window.len();
+        self.stripper.feed(raw, &mut self.window);
+        let added = self.window.len() - before;
+
+        // 超出窗口大小时丢弃最早的数据
+        if self.window.len() > self.capacity {
+            let overflow = self.window.len() - self.capacity;
+            self.window.drain(..overflow);
+            self.base += overflow as u64;
+            if self.cursor < self.base {
+                self.cursor = self.base;
+            }
+        }
+        added
+    }
+
+    // 数据流总长度（绝对位置）
+    pub fn end(&self) -> u64 {
+        self.base + self.window.len() as u64
+    }
+
+    // 待匹配文本的长度
+    pub fn pending_len(&self) -> usize {
+        (self.end() - self.cursor) as usize
+    }
+
+    fn pending_bytes(&self) -> &[u8] {
+        &self.window[(self.cursor - self.base) as usize..]
+    }
+
+    // 待匹配的文本（不消费）
+    pub fn pending_text(&self) -> String {
+        String::from_utf8_lossy(self.pending_bytes()).to_string()
+    }
+
+    // 取出全部待匹配文本并消费
+    pub fn take_pending(&mut self) -> String {
+        let text = self.pending_text();
+        self.cursor = self.end();
+        text
+    }
+
+    // 丢弃全部待匹配文本
+    pub fn consume_all(&mut self) {
+        self.cursor = self.end();
+    }
+
+    // 最近的n个字节文本，用于失败时展示控制台内容
+    pub fn tail(&self, n: usize) -> String {
+        let start = self.window.len().saturating_sub(n);
+        String::from_utf8_lossy(&self.window[start..]).to_string()
+    }
+
+    // 在待匹配文本中查找最早出现的模式，命中后消费到命中结尾
+    pub fn find(&mut self, patterns: &[ConsolePattern]) -> Option<ConsoleMatch> {
+        let text = self.pending_text();
+        let mut best: Option<(usize, RawMatch)> = None;
+        for (index, pattern) in patterns.iter().enumerate() {
+            if let Some(found) = pattern.find_in(&text) {
+                let earlier = match &best {
+                    Some((_, (best_start, ..))) => found.0 < *best_start,
+                    None => true,
+                };
+                if earlier {
+                    best = Some((index, found));
+                }
+            }
+        }
+
+        let (index, (start, end, captures, named)) = best?;
+        // lossy转换可能改变字节长度，按转换后文本前缀的原始字节数推进
+        let consumed = prefix_byte_len(self.pending_bytes(), &text[..end]);
+        self.cursor += consumed as u64;
+        Some(ConsoleMatch {
+            index,
+            pattern: patterns[index].as_str().to_string(),
+            before: text[..start].to_string(),
+            captures,
+            named,
+        })
+    }
+}
+
+impl Default for ConsoleBuffer {
+    fn default() -> Self {
+        ConsoleBuffer::new(DEFAULT_WINDOW_SIZE)
+    }
+}
+
+// 计算lossy文本前缀对应的原始字节数（无效UTF-8被替换为3字节的U+FFFD）
+fn prefix_byte_len(raw: &[u8], prefix: &str) -> usize {
+    if raw.starts_with(prefix.as_bytes()) {
+        return prefix.len();
+    }
+    let mut consumed = 0;
+    let mut produced = 0;
+    for chunk in raw.utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            if produced >= prefix.len() {
+                return consumed;
+            }
+            produced += ch.len_utf8();
+            consumed += ch.len_utf8();
+        }
+        if !chunk.invalid().is_empty() {
+            if produced >= prefix.len() {
+                return consumed;
+            }
+            produced += char::REPLACEMENT_CHARACTER.len_utf8();
+            consumed += chunk.invalid().len();
+        }
+    }
+    consumed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_literal_consumes_up_to_match() {
+        let mut buffer = ConsoleBuffer::default();
+        buffer.push(b"U-Boot 2023.04\nHit any key to stop autoboot: 3\n");
+        let found = buffer.find(&[ConsolePattern::literal("Hit any key")]).unwrap();
+        assert_eq!(found.index, 0);
+        assert_eq!(found.pattern, "Hit any key");
+        assert_eq!(found.before, "U-Boot 2023.04\n");
+        assert_eq!(buffer.pending_text(), " to stop autoboot: 3\n");
+        // 已消费的文本不会再次命中
+        assert!(buffer.find(&[ConsolePattern::literal("Hit any key")]).is_none());
+    }
+
+    #[test]
+    fn find_across_chunks_and_ansi() {
+        let mut buffer = ConsoleBuffer::default();
+        buffer.push(b"\x1b[1;32mlog");
+        assert!(buffer.find(&[ConsolePattern::literal("login:")]).is_none());
+        // 转义序列被拆到两块中
+        buffer.push(b"\x1b[");
+        buffer.push(b"0min: ");
+        let found = buffer.find(&[ConsolePattern::literal("login:")]).unwrap();
+        assert_eq!(found.before, "");
+        assert_eq!(buffer.pending_text(), " ");
+    }
+
+    #[test]
+    fn find_earliest_pattern_wins() {
+        let mut buffer = ConsoleBuffer::default();
+        buffer.push(b"Password: Login incorrect\n");
+        let patterns = [ConsolePattern::literal("Login incorrect"), ConsolePattern::literal("Password:")];
+        let found = buffer.find(&patterns).unwrap();
+        assert_eq!(found.index, 1);
+        let found = buffer.find(&patterns).unwrap();
+        assert_eq!(found.index, 0);
+        assert_eq!(found.before, " ");
+    }
+
+    #[test]
+    fn find_regex_captures() {
+        let mut buffer = ConsoleBuffer::default();
+        buffer.push(b"eth0: ip=192.168.1.20 mask=24\n");
+        let pattern = ConsolePattern::regex(r"ip=(?P<ip>[\d.]+) mask=(\d+)").unwrap();
+        let found = buffer.find(&[pattern]).unwrap();
+        assert_eq!(found.before, "eth0: ");
+        assert_eq!(found.captures, vec![Some("192.168.1.20".to_string()), Some("24".to_string())]);
+        assert_eq!(found.named.get("ip").map(String::as_str), Some("192.168.1.20"));
+        assert_eq!(buffer.pending_text(), "\n");
+    }
+
+    #[test]
+    fn find_after_invalid_utf8() {
+        let mut buffer = ConsoleBuffer::default();
+        buffer.push(b"\xff\xfeboot> ok");
+        let found = buffer.find(&[ConsolePattern::literal("boot>")]).unwrap();
+        assert_eq!(found.before, "\u{fffd}\u{fffd}");
+        // 按原始字节数推进，剩余文本不受替换字符长度影响
+        assert_eq!(buffer.pending_len(), 3);
+        assert_eq!(buffer.pending_text(), " ok");
+    }
+
+    #[test]
+    fn find_after_window_overflow() {
+        let mut buffer = ConsoleBuffer::new(16);
+        buffer.push(b"0123456789abcdef");
+        buffer.push(b"ghij");
+        assert_eq!(buffer.pending_text(), "456789abcdefghij");
+        let found = buffer.find(&[ConsolePattern::literal("fgh")]).unwrap();
+        assert_eq!(found.before, "456789abcde");
+        assert_eq!(buffer.pending_text(), "ij");
+    }
+}
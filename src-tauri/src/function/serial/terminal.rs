@@ -0,0 +1,117 @@
+// 前端串口终端：把KVM控制台实时推送给前端，并把前端按键写回串口
+// 终端打开期间自动测试状态机暂停，工程师可以直接调试卡住的板卡，关闭后状态机丢弃期间的输出继续运行
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+use tauri::async_runtime::spawn;
+use tauri::{AppHandle, Emitter};
+use tokio::time::sleep;
+
+use super::hub::{subscribe_console, SubscribeFrom};
+use super::{send_to_queue, is_usb_tool_connected};
+use crate::APP_EXIT;
+
+// 打开终端时回放的历史输出长度
+const TERMINAL_HISTORY_BYTES: usize = 16 * 1024;
+// 推送间隔上限，等待新数据的超时时间
+const TERMINAL_POLL_MS: u64 = 50;
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = false;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[terminal]{}", msg);
+    }
+}
+
+static TERMINAL_OPEN: AtomicBool = AtomicBool::new(false);     // 终端是否打开
+static TERMINAL_SESSION: AtomicU64 = AtomicU64::new(0);        // 终端会话编号，重新打开时旧的推送任务退出
+
+// 打开终端：开始推送串口输出（事件 terminal-output），并暂停自动测试
+#[tauri::command]
+pub fn open_terminal(app_handle: AppHandle) {
+    let session = TERMINAL_SESSION.fetch_add(1, Ordering::SeqCst) + 1;
+    TERMINAL_OPEN.store(true, Ordering::SeqCst);
+    log(&format!("终端已打开, 会话: {}", session));
+    spawn_terminal_forwarder(app_handle, session);
+}
+
+// 关闭终端：停止推送并恢复自动测试
+#[tauri::command]
+pub fn close_terminal() {
+    TERMINAL_SESSION.fetch_add(1, Ordering::SeqCst);
+    TERMINAL_OPEN.store(false, Ordering::SeqCst);
+    log("终端已关闭");
+}
+
+// 前端按键输入，原样写入串口；自动测试的发送在终端打开期间等待，只有这里可以写入
+#[tauri::command]
+pub async fn terminal_input(data: String) {
+    if TERMINAL_OPEN.load(Ordering::SeqCst) {
+        send_to_queue(data.as_bytes()).await;
+    }
+}
+
+// 终端是否打开
+pub fn is_terminal_open() -> bool {
+    TERMINAL_OPEN.load(Ordering::SeqCst)
+}
+
+// 终端打开时等待其关闭，返回是否发生过等待
+pub async fn wait_terminal_closed() -> bool {
+    if !is_terminal_open() {
+        return false;
+    }
+    log("终端已打开，自动测试暂停");
+    while is_terminal_open() && !APP_EXIT.load(Ordering::Relaxed) {
+        sleep(Duration::from_millis(100)).await;
+    }
+    log("终端已关闭，自动测试继续");
+    true
+}
+
+fn spawn_terminal_forwarder(app_handle: AppHandle, session: u64) {
+    spawn(async move {
+        let mut subscription = subscribe_console("terminal", SubscribeFrom::Recent(TERMINAL_HISTORY_BYTES));
+        let mut pending: Vec<u8> = Vec::new();
+        let mut connected = is_usb_tool_connected().await;
+        emit_output(&app_handle, if connected { "[connected]\r\n" } else { "[disconnected]\r\n" });
+
+        while TERMINAL_SESSION.load(Ordering::SeqCst) == session && !APP_EXIT.load(Ordering::Relaxed) {
+            let chunk = subscription.recv_timeout(TERMINAL_POLL_MS).await;
+            if !chunk.data.is_empty() {
+                pending.extend_from_slice(&chunk.data);
+                let text = take_utf8(&mut pending);
+                if !text.is_empty() {
+                    emit_output(&app_handle, &text);
+                }
+            }
+
+            let now_connected = is_usb_tool_connected().await;
+            if now_connected != connected {
+                connected = now_connected;
+                emit_output(&app_handle, if connected { "\r\n[connected]\r\n" } else { "\r\n[disconnected]\r\n" });
+            }
+        }
+        log(&format!("终端推送任务退出, 会话: {}", session));
+    });
+}
+
+fn emit_output(app_handle: &AppHandle, text: &str) {
+    if let Err(e) = app_handle.emit("terminal-output", text) {
+        log(&format!("推送终端输出失败: {}", e));
+    }
+}
+
+// 取出缓冲中可以完整解码的UTF-8文本，末尾被截断的多字节字符留到下一次
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let valid = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => pending.len(),
+    };
+    let text = String::from_utf8_lossy(&pending[..valid]).to_string();
+    pending.drain(..valid);
+    text
+}
@@ -0,0 +1,148 @@
+// 串口文件传输：网口不可用时把产测包通过已登录的串口控制台推送到板卡
+// 文件按块base64编码后逐行发送，板卡端解码并校验每块的SHA-256前缀后追加写入，最后比对整个文件的SHA-256
+use std::fmt;
+use std::fs;
+use std::time::Instant;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use sha2::{Digest, Sha256};
+
+use super::shell::{serial_shell_raw, ShellError};
+use crate::function::save::get_app_file_path;
+
+// 每块原始数据大小，编码后约2.7K字符，低于终端单行4095字符的限制
+const CHUNK_SIZE: usize = 2048;
+// 单块命令超时时间
+const CHUNK_TIMEOUT_MS: u64 = 10_000;
+// 单块最多重发次数
+const MAX_RETRIES: u32 = 5;
+// 准备和收尾命令超时时间
+const COMMAND_TIMEOUT_MS: u64 = 30_000;
+// 每块校验使用的SHA-256前缀长度（十六进制字符）
+const CHUNK_HASH_LEN: usize = 16;
+// 板卡端写入一块数据的函数：已写入则跳过，偏移不一致返回3，校验失败返回非0
+// 参数：块哈希 base64数据 目标文件 偏移 长度
+const CHUNK_FUNCTION: &str = "st(){ s=$(wc -c <\"$3\"); [ \"$s\" -eq $(($4+$5)) ]&&return 0; [ \"$s\" -eq \"$4\" ]||return 3; \
+echo \"$2\"|base64 -d >/tmp/st.chunk&&[ \"$(sha256sum </tmp/st.chunk|cut -c1-16)\" = \"$1\" ]&&cat /tmp/st.chunk >>\"$3\"; }";
+// 偏移不一致的退出码
+const OFFSET_MISMATCH: i32 = 3;
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = true;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[transfer]{}", msg);
+    }
+}
+
+// 串口推送失败原因
+#[derive(Debug, Clone)]
+pub enum PushError {
+    File(String),               // 本地文件读取失败
+    Shell(ShellError),          // 准备或收尾命令失败
+    Chunk(usize),               // 第n块重发次数过多
+    Offset(usize),              // 第n块写入位置与板卡端文件大小不一致
+    Verify(String, String),     // 整体校验失败，内容为本地和板卡端SHA-256
+}
+
+impl fmt::Display for PushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::File(msg) => write!(f, "读取本地文件失败: {}", msg),
+            PushError::Shell(e) => write!(f, "{}", e),
+            PushError::Chunk(index) => write!(f, "第 {} 块重发超过 {} 次", index, MAX_RETRIES),
+            PushError::Offset(index) => write!(f, "第 {} 块写入位置与板卡端文件不一致", index),
+            PushError::Verify(local, remote) => write!(f, "文件校验失败，本地 {} 板卡 {}", local, remote),
+        }
+    }
+}
+
+impl From<ShellError> for PushError {
+    fn from(e: ShellError) -> Self {
+        PushError::Shell(e)
+    }
+}
+
+// 十六进制小写字符串，与sha256sum输出格式一致
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// 执行准备或收尾命令，退出码非0时返回失败
+async fn run(command: &str) -> Result<String, PushError> {
+    match serial_shell_raw(command, COMMAND_TIMEOUT_MS).await? {
+        (0, output) => Ok(output),
+        (code, output) => Err(PushError::Shell(ShellError::Exit(code, output))),
+    }
+}
+
+// 发送一块数据，失败时重发
+async fn push_chunk(index: usize, offset: usize, chunk: &[u8], part_path: &str) -> Result<(), PushError> {
+    let hash = to_hex(&Sha256::digest(chunk));
+    let command = format!("st {} {} {} {} {}", &hash[..CHUNK_HASH_LEN], BASE64.encode(chunk), part_path, offset, chunk.len());
+    for retry in 0..MAX_RETRIES {
+        match serial_shell_raw(&command, CHUNK_TIMEOUT_MS).await {
+            Ok((0, _)) => return Ok(()),
+            Ok((OFFSET_MISMATCH, _)) => return Err(PushError::Offset(index)),
+            Ok((code, output)) => log(&format!("第 {} 块校验失败({}): {}，第 {} 次重发", index, code, output, retry + 1)),
+            Err(e) => log(&format!("第 {} 块发送失败: {}，第 {} 次重发", index, e, retry + 1)),
+        }
+    }
+    Err(PushError::Chunk(index))
+}
+
+// 把数据推送到板卡的remote_path，先写入临时文件，整体校验通过后再改名
+pub async fn push_data(data: &[u8], remote_path: &str) -> Result<(), PushError> {
+    let part_path = format!("{}.part", remote_path);
+    let total = data.len().div_ceil(CHUNK_SIZE);
+    let local_hash = to_hex(&Sha256::digest(data));
+    log(&format!("开始推送 {} ({} 字节, {} 块)", remote_path, data.len(), total));
+
+    // 关闭回显减少一半串口流量，并定义写入函数
+    run("stty -echo").await?;
+    let result = async {
+        run(CHUNK_FUNCTION).await?;
+        run(&format!("rm -f {} && touch {}", part_path, part_path)).await?;
+
+        let started = Instant::now();
+        for (index, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+            push_chunk(index + 1, index * CHUNK_SIZE, chunk, &part_path).await?;
+            if (index + 1) % 50 == 0 || index + 1 == total {
+                let sent = (index * CHUNK_SIZE + chunk.len()) as f64;
+                let rate = sent / started.elapsed().as_secs_f64().max(0.001);
+                let remain = (data.len() as f64 - sent) / rate.max(1.0);
+                log(&format!("已发送 {}/{} 块, {:.1} KB/s, 剩余约 {:.0} 秒", index + 1, total, rate / 1024.0, remain));
+            }
+        }
+
+        let remote_hash = run(&format!("sha256sum {} | cut -d' ' -f1", part_path)).await?;
+        let remote_hash = remote_hash.trim().to_string();
+        if remote_hash != local_hash {
+            return Err(PushError::Verify(local_hash.clone(), remote_hash));
+        }
+        run(&format!("mv -f {} {} && rm -f /tmp/st.chunk", part_path, remote_path)).await?;
+        Ok(())
+    }.await;
+    // 无论成功与否都恢复回显，避免影响后续的串口交互
+    if let Err(e) = run("stty echo").await {
+        log(&format!("恢复回显失败: {}", e));
+    }
+
+    match &result {
+        Ok(()) => log(&format!("{} 推送完成, SHA-256 {}", remote_path, local_hash)),
+        Err(e) => log(&format!("{} 推送失败: {}", remote_path, e)),
+    }
+    result
+}
+
+// 把app目录中的产测包推送到板卡
+pub async fn push_app_file(remote_path: &str) -> Result<(), PushError> {
+    let file_path = get_app_file_path();
+    if file_path.as_os_str().is_empty() {
+        return Err(PushError::File("app目录中没有产测包".to_string()));
+    }
+    let data = fs::read(&file_path).map_err(|e| PushError::File(format!("{}: {}", file_path.display(), e)))?;
+    push_data(&data, remote_path).await
+}
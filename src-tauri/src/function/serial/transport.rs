@@ -0,0 +1,404 @@
+// 串口传输层：把真实USB串口、Linux伪终端、内存管道统一成同一个读写接口
+// 串口管理线程只和 SerialTransport 打交道，发送队列和接收广播的上层逻辑不需要关心底层是什么
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serialport::SerialPortType;
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio_serial::{SerialStream, new, SerialPortBuilderExt, FlowControl};
+use tokio_serial::SerialPort; // 引入特质以使用 DTR/RTS 方法
+
+use super::mock_console::spawn_mock_console;
+use crate::function::save::{get_serial_config, SerialConfig, SerialToolConfig};
+
+// 选择传输方式的环境变量：usb（默认）/ pty / memory
+const TRANSPORT_ENV: &str = "NANOKVM_SERIAL_TRANSPORT";
+// 内存管道每个方向的缓冲大小
+const MEMORY_PIPE_SIZE: usize = 64 * 1024;
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = true;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[transport]{}", msg);
+    }
+}
+
+lazy_static! {
+    static ref SELECTED_PORT: Mutex<Option<String>> = Mutex::new(None);    // 操作员在界面上指定的串口
+}
+static RECONNECT_REQUESTED: AtomicBool = AtomicBool::new(false);            // 指定串口变化后需要重新连接
+
+// 串口传输特质：可异步读写，并且可以控制DTR/RTS
+pub trait SerialTransport: AsyncRead + AsyncWrite + Unpin + Send {
+    // 传输名称，用于日志
+    fn describe(&self) -> String;
+    // 设置DTR电平
+    fn set_dtr(&mut self, level: bool) -> io::Result<()>;
+    // 设置RTS电平
+    fn set_rts(&mut self, level: bool) -> io::Result<()>;
+}
+
+// 传输方式枚举
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransportKind {
+    Usb,        // 真实的USB串口工具（CH343）
+    Pty,        // Linux伪终端，外部脚本连接从设备端模拟KVM
+    Memory,     // 内存管道，另一端由内置的脚本控制台驱动
+}
+
+impl TransportKind {
+    // 从环境变量读取传输方式，未设置或无法识别时使用USB串口
+    pub fn from_env() -> TransportKind {
+        match std::env::var(TRANSPORT_ENV) {
+            Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "pty" => TransportKind::Pty,
+                "memory" | "mock" => TransportKind::Memory,
+                _ => TransportKind::Usb,
+            },
+            Err(_) => TransportKind::Usb,
+        }
+    }
+}
+
+// 真实串口直接实现传输特质
+impl SerialTransport for SerialStream {
+    fn describe(&self) -> String {
+        self.name().unwrap_or_else(|| "usb-serial".to_string())
+    }
+
+    fn set_dtr(&mut self, level: bool) -> io::Result<()> {
+        self.write_data_terminal_ready(level).map_err(io::Error::from)
+    }
+
+    fn set_rts(&mut self, level: bool) -> io::Result<()> {
+        self.write_request_to_send(level).map_err(io::Error::from)
+    }
+}
+
+// 候选串口信息，供前端列出让操作员选择
+#[derive(Debug, Clone, Serialize)]
+pub struct SerialPortCandidate {
+    pub port_name: String,
+    pub vid: u16,
+    pub pid: u16,
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub tool: Option<String>,       // 匹配到的工具名称，未匹配时为None
+    pub selected: bool,             // 是否为当前指定的串口
+}
+
+// 判断USB串口是否匹配某个工具配置
+fn tool_matches(tool: &SerialToolConfig, vid: u16, pid: u16, serial_number: Option<&str>) -> bool {
+    if vid != tool.vid || pid != tool.pid {
+        return false;
+    }
+    match &tool.serial_number {
+        Some(expected) if !expected.is_empty() => serial_number == Some(expected.as_str()),
+        _ => true,
+    }
+}
+
+// 工具显示名称，未配置名称时显示VID:PID
+fn tool_name(tool: &SerialToolConfig) -> String {
+    if tool.name.is_empty() {
+        format!("{:04x}:{:04x}", tool.vid, tool.pid)
+    } else {
+        tool.name.clone()
+    }
+}
+
+// 列出所有USB串口，并标注匹配到的工具
+pub fn list_serial_candidates(config: &SerialConfig) -> Vec<SerialPortCandidate> {
+    let mut candidates = Vec::new();
+    let selected = selected_port_name(config);
+    if let Ok(ports) = serialport::available_ports() {
+        for port_info in ports {
+            if let SerialPortType::UsbPort(usb_info) = port_info.port_type {
+                let tool = config.tools.iter()
+                    .find(|tool| tool_matches(tool, usb_info.vid, usb_info.pid, usb_info.serial_number.as_deref()));
+                candidates.push(SerialPortCandidate {
+                    selected: port_info.port_name == selected,
+                    port_name: port_info.port_name,
+                    vid: usb_info.vid,
+                    pid: usb_info.pid,
+                    serial_number: usb_info.serial_number.clone(),
+                    manufacturer: usb_info.manufacturer.clone(),
+                    product: usb_info.product.clone(),
+                    tool: tool.map(tool_name),
+                });
+            }
+        }
+    }
+    candidates
+}
+
+// 当前指定的串口：操作员在界面上选择的优先，其次是配置文件中的port
+fn selected_port_name(config: &SerialConfig) -> String {
+    let selected = SELECTED_PORT.lock().unwrap_or_else(|e| e.into_inner());
+    match &*selected {
+        Some(port) => port.clone(),
+        None => config.port.trim().to_string(),
+    }
+}
+
+// 扫描串口，返回要连接的串口名和对应的工具配置
+// 指定了串口时只连接该串口；否则按工具列表顺序匹配第一个存在的串口
+pub fn scan_serial_port(config: &SerialConfig) -> Option<(String, SerialToolConfig)> {
+    let candidates = list_serial_candidates(config);
+    let selected = selected_port_name(config);
+    if !selected.is_empty() {
+        let candidate = candidates.iter().find(|candidate| candidate.port_name == selected)?;
+        // 指定串口不在工具列表中时使用第一个工具的参数
+        let tool = config.tools.iter()
+            .find(|tool| tool.vid == candidate.vid && tool.pid == candidate.pid)
+            .or(config.tools.first())
+            .cloned()?;
+        return Some((candidate.port_name.clone(), tool));
+    }
+
+    for tool in &config.tools {
+        let matched = candidates.iter()
+            .find(|candidate| tool_matches(tool, candidate.vid, candidate.pid, candidate.serial_number.as_deref()));
+        if let Some(candidate) = matched {
+            return Some((candidate.port_name.clone(), tool.clone()));
+        }
+    }
+    None
+}
+
+// 打开指定名称的真实串口
+pub fn open_usb_transport(port_name: &str, tool: &SerialToolConfig) -> io::Result<Box<dyn SerialTransport>> {
+    let flow_control = match tool.flow_control.trim().to_ascii_lowercase().as_str() {
+        "software" => FlowControl::Software,
+        "hardware" => FlowControl::Hardware,
+        _ => FlowControl::None,
+    };
+    let port = new(port_name, tool.baud_rate).flow_control(flow_control);
+    let serial_port = port.open_native_async().map_err(io::Error::from)?;
+    Ok(Box::new(serial_port))
+}
+
+// 列出候选串口
+#[tauri::command]
+pub fn list_serial_ports() -> Vec<SerialPortCandidate> {
+    list_serial_candidates(&get_serial_config())
+}
+
+// 操作员指定串口，传空字符串恢复自动扫描；串口管理线程会断开当前连接并重新连接
+#[tauri::command]
+pub fn select_serial_port(port_name: String) {
+    let port_name = port_name.trim().to_string();
+    log(&format!("操作员指定串口: {}", if port_name.is_empty() { "自动扫描" } else { &port_name }));
+    {
+        let mut selected = SELECTED_PORT.lock().unwrap_or_else(|e| e.into_inner());
+        *selected = if port_name.is_empty() { None } else { Some(port_name) };
+    }
+    RECONNECT_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+// 串口管理线程检查是否需要重新连接
+pub(super) fn take_reconnect_request() -> bool {
+    RECONNECT_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+// 内存传输：tokio双工管道的一端，另一端交给脚本控制台
+pub struct MemoryTransport {
+    stream: DuplexStream,
+    dtr: bool,
+    rts: bool,
+}
+
+impl MemoryTransport {
+    // 创建一对内存管道，返回传输端和控制台端
+    pub fn pair() -> (MemoryTransport, DuplexStream) {
+        let (local, remote) = tokio::io::duplex(MEMORY_PIPE_SIZE);
+        (MemoryTransport { stream: local, dtr: false, rts: false }, remote)
+    }
+}
+
+impl AsyncRead for MemoryTransport {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for MemoryTransport {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+impl SerialTransport for MemoryTransport {
+    fn describe(&self) -> String {
+        format!("memory(dtr={}, rts={})", self.dtr, self.rts)
+    }
+
+    fn set_dtr(&mut self, level: bool) -> io::Result<()> {
+        self.dtr = level;
+        Ok(())
+    }
+
+    fn set_rts(&mut self, level: bool) -> io::Result<()> {
+        self.rts = level;
+        Ok(())
+    }
+}
+
+// 打开内存传输，并在另一端启动脚本控制台
+pub fn open_memory_transport() -> io::Result<Box<dyn SerialTransport>> {
+    let (transport, console_end) = MemoryTransport::pair();
+    spawn_mock_console(console_end);
+    Ok(Box::new(transport))
+}
+
+#[cfg(target_os = "linux")]
+pub use pty::open_pty_transport;
+
+#[cfg(not(target_os = "linux"))]
+pub fn open_pty_transport() -> io::Result<Box<dyn SerialTransport>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "伪终端传输仅支持Linux"))
+}
+
+#[cfg(target_os = "linux")]
+mod pty {
+    use std::ffi::CStr;
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+    use std::os::unix::io::FromRawFd;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, ready};
+    use tokio::io::unix::AsyncFd;
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use super::{log, SerialTransport};
+
+    // Linux伪终端传输：本程序持有主设备，外部程序打开从设备模拟KVM串口
+    pub struct PtyTransport {
+        master: AsyncFd<File>,
+        // 保持从设备打开，避免外部程序断开时主设备读到EIO
+        _slave: File,
+        slave_name: String,
+    }
+
+    impl PtyTransport {
+        pub fn open() -> io::Result<PtyTransport> {
+            unsafe {
+                let master_fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+                if master_fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // 交给File管理，出错返回时自动关闭
+                let master = File::from_raw_fd(master_fd);
+                if libc::grantpt(master_fd) != 0 || libc::unlockpt(master_fd) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                let mut name_buf = [0 as libc::c_char; 128];
+                if libc::ptsname_r(master_fd, name_buf.as_mut_ptr(), name_buf.len()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let slave_name = CStr::from_ptr(name_buf.as_ptr()).to_string_lossy().to_string();
+
+                // 设置为原始模式，避免回显和行缓冲干扰串口数据
+                let slave = std::fs::OpenOptions::new().read(true).write(true).open(&slave_name)?;
+                let mut termios: libc::termios = std::mem::zeroed();
+                let slave_fd = std::os::unix::io::AsRawFd::as_raw_fd(&slave);
+                if libc::tcgetattr(slave_fd, &mut termios) == 0 {
+                    libc::cfmakeraw(&mut termios);
+                    libc::tcsetattr(slave_fd, libc::TCSANOW, &termios);
+                }
+
+                // 主设备设为非阻塞，交给tokio轮询
+                let flags = libc::fcntl(master_fd, libc::F_GETFL);
+                if flags < 0 || libc::fcntl(master_fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(PtyTransport {
+                    master: AsyncFd::new(master)?,
+                    _slave: slave,
+                    slave_name,
+                })
+            }
+        }
+
+        // 外部程序需要打开的从设备路径
+        pub fn slave_name(&self) -> &str {
+            &self.slave_name
+        }
+    }
+
+    impl AsyncRead for PtyTransport {
+        fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+            loop {
+                let mut guard = ready!(self.master.poll_read_ready(cx))?;
+                let unfilled = buf.initialize_unfilled();
+                match guard.try_io(|inner| inner.get_ref().read(unfilled)) {
+                    Ok(Ok(len)) => {
+                        buf.advance(len);
+                        return Poll::Ready(Ok(()));
+                    }
+                    Ok(Err(e)) => return Poll::Ready(Err(e)),
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+    }
+
+    impl AsyncWrite for PtyTransport {
+        fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            loop {
+                let mut guard = ready!(self.master.poll_write_ready(cx))?;
+                match guard.try_io(|inner| inner.get_ref().write(buf)) {
+                    Ok(result) => return Poll::Ready(result),
+                    Err(_would_block) => continue,
+                }
+            }
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl SerialTransport for PtyTransport {
+        fn describe(&self) -> String {
+            format!("pty({})", self.slave_name)
+        }
+
+        // 伪终端没有真实的控制线，忽略即可
+        fn set_dtr(&mut self, _level: bool) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn set_rts(&mut self, _level: bool) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // 打开伪终端传输，并打印从设备路径供外部脚本连接
+    pub fn open_pty_transport() -> io::Result<Box<dyn SerialTransport>> {
+        let transport = PtyTransport::open()?;
+        log(&format!("伪终端已创建，请将模拟控制台连接到: {}", transport.slave_name()));
+        Ok(Box::new(transport))
+    }
+}
@@ -0,0 +1,425 @@
+// U-Boot控制台操作：打断自动启动、读写环境变量，以及通过loady把固件写回eMMC救砖
+// 固件来自产测包中的 NanoKVM_Pro_Testing/firmware，写入分区与 04_update_file.sh 一致，写入后回读校验CRC32
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+use super::stream::ConsolePattern;
+use super::xmodem::{send_file, TransferError};
+use super::power::{pulse_reset, ResetTiming};
+use super::{serial_send, detect_serial_pattern, clear_console_buffer, DetectResult};
+use crate::function::save::get_app_file_path;
+
+// U-Boot命令提示符
+pub const UBOOT_PROMPT: &str = "AXERA-UBOOT=>";
+// 复位后等待进入U-Boot的时间
+const ENTER_TIMEOUT_MS: u64 = 15_000;
+// 打断自动启动时发送按键的间隔
+const INTERRUPT_INTERVAL_MS: u64 = 50;
+// 普通命令超时时间
+const COMMAND_TIMEOUT_MS: u64 = 5_000;
+// 写入/读取eMMC的超时时间
+const MMC_TIMEOUT_MS: u64 = 60_000;
+// 环境变量中没有loadaddr时使用的加载地址
+const DEFAULT_LOAD_ADDR: u64 = 0x4800_0000;
+// 回读校验使用的地址偏移
+const VERIFY_OFFSET: u64 = 0x0100_0000;
+// eMMC块大小
+const MMC_BLOCK_SIZE: usize = 512;
+// eMMC设备号
+const MMC_DEVICE: u32 = 0;
+// 产测包中固件所在目录
+const FIRMWARE_DIR: &str = "NanoKVM_Pro_Testing/firmware";
+
+// 需要恢复的固件：文件名前缀、扩展名和写入的分区（主分区和备份分区）
+const RECOVERY_IMAGES: [(&str, &str, [u32; 2]); 2] = [
+    ("u-boot_signed", ".bin", [5, 6]),
+    ("AX630C_emmc_arm64_k419_sipeed_nanokvm_signed", ".dtb", [12, 13]),
+];
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = true;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[uboot]{}", msg);
+    }
+}
+
+// U-Boot操作失败原因
+#[derive(Debug, Clone)]
+pub enum UbootError {
+    NoPrompt,                       // 没有进入U-Boot命令行
+    Timeout(String),                // 命令执行后没有回到提示符
+    Command(String, String),        // 命令返回错误，内容为命令和输出
+    Transfer(TransferError),        // 串口传输失败
+    Firmware(String),               // 找不到或无法读取固件
+    Verify(String),                 // 写入后校验失败
+}
+
+impl fmt::Display for UbootError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UbootError::NoPrompt => write!(f, "没有进入U-Boot命令行"),
+            UbootError::Timeout(command) => write!(f, "命令 {:?} 执行超时", command),
+            UbootError::Command(command, output) => write!(f, "命令 {:?} 执行失败: {}", command, output),
+            UbootError::Transfer(e) => write!(f, "串口传输失败: {}", e),
+            UbootError::Firmware(msg) => write!(f, "固件错误: {}", msg),
+            UbootError::Verify(msg) => write!(f, "校验失败: {}", msg),
+        }
+    }
+}
+
+impl From<TransferError> for UbootError {
+    fn from(e: TransferError) -> Self {
+        UbootError::Transfer(e)
+    }
+}
+
+// 复位板卡并打断自动启动，进入U-Boot命令行
+pub async fn enter_uboot() -> Result<(), UbootError> {
+    log("复位并进入U-Boot");
+    clear_console_buffer().await;
+    let _ = pulse_reset(ResetTiming::default()).await;
+    interrupt_autoboot(ENTER_TIMEOUT_MS).await
+}
+
+// 不断发送按键直到出现U-Boot提示符
+pub async fn interrupt_autoboot(timeout_ms: u64) -> Result<(), UbootError> {
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    let prompt = [ConsolePattern::literal(UBOOT_PROMPT)];
+    while Instant::now() < deadline {
+        serial_send("\n").await;
+        if let DetectResult::Matched(_) = detect_serial_pattern(&prompt, INTERRUPT_INTERVAL_MS, 0).await {
+            // 多发的回车会产生多个提示符，等输出稳定后再清空
+            sleep(Duration::from_millis(200)).await;
+            clear_console_buffer().await;
+            log("已进入U-Boot");
+            return Ok(());
+        }
+    }
+    Err(UbootError::NoPrompt)
+}
+
+// 执行命令并返回输出（不含命令回显和提示符）
+pub async fn run_command(command: &str, timeout_ms: u64) -> Result<String, UbootError> {
+    clear_console_buffer().await;
+    serial_send(&format!("{}\n", command)).await;
+    match detect_serial_pattern(&[ConsolePattern::literal(UBOOT_PROMPT)], timeout_ms, 0).await {
+        DetectResult::Matched(found) => {
+            let output = found.before.replace('\r', "");
+            // 第一行是命令回显
+            let output = match output.split_once('\n') {
+                Some((_, rest)) => rest.trim().to_string(),
+                None => String::new(),
+            };
+            log(&format!("{} => {}", command, output));
+            Ok(output)
+        }
+        _ => Err(UbootError::Timeout(command.to_string())),
+    }
+}
+
+// 执行命令，输出中包含错误提示时返回失败
+async fn run_checked(command: &str, timeout_ms: u64) -> Result<String, UbootError> {
+    let output = run_command(command, timeout_ms).await?;
+    let lower = output.to_ascii_lowercase();
+    if lower.contains("error") || lower.contains("unknown command") || lower.contains("usage:") {
+        return Err(UbootError::Command(command.to_string(), output));
+    }
+    Ok(output)
+}
+
+// 读取全部环境变量
+#[allow(dead_code)]
+pub async fn printenv() -> Result<HashMap<String, String>, UbootError> {
+    let output = run_command("printenv", COMMAND_TIMEOUT_MS).await?;
+    Ok(output
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), value.to_string()))
+        .collect())
+}
+
+// 读取单个环境变量，未定义时返回None
+pub async fn getenv(name: &str) -> Result<Option<String>, UbootError> {
+    let output = run_command(&format!("printenv {}", name), COMMAND_TIMEOUT_MS).await?;
+    let prefix = format!("{}=", name);
+    Ok(output.lines().find_map(|line| line.strip_prefix(&prefix).map(|value| value.to_string())))
+}
+
+// 设置环境变量，只在内存中生效，需要保存时调用saveenv
+#[allow(dead_code)]
+pub async fn setenv(name: &str, value: &str) -> Result<(), UbootError> {
+    run_checked(&format!("setenv {} {}", name, value), COMMAND_TIMEOUT_MS).await.map(|_| ())
+}
+
+// 保存环境变量到eMMC
+#[allow(dead_code)]
+pub async fn saveenv() -> Result<(), UbootError> {
+    run_checked("saveenv", MMC_TIMEOUT_MS).await.map(|_| ())
+}
+
+// 退出U-Boot继续启动
+pub async fn boot() {
+    serial_send("boot\n").await;
+}
+
+// 通过loady把数据加载到内存指定地址，并用CRC32校验
+pub async fn load_binary(addr: u64, file_name: &str, data: &[u8]) -> Result<(), UbootError> {
+    let command = format!("loady {:#x}", addr);
+    clear_console_buffer().await;
+    serial_send(&format!("{}\n", command)).await;
+    send_file(file_name, data).await?;
+    if let DetectResult::Matched(_) = detect_serial_pattern(&[ConsolePattern::literal(UBOOT_PROMPT)], COMMAND_TIMEOUT_MS, 0).await {
+        verify_crc32(addr, data).await
+    } else {
+        Err(UbootError::Timeout(command))
+    }
+}
+
+// 比较内存中数据的CRC32与本地计算结果
+async fn verify_crc32(addr: u64, data: &[u8]) -> Result<(), UbootError> {
+    let command = format!("crc32 {:#x} {:#x}", addr, data.len());
+    let output = run_checked(&command, COMMAND_TIMEOUT_MS).await?;
+    // 输出格式：crc32 for 48000000 ... 4809d8ff ==> 1a2b3c4d
+    let actual = output
+        .rsplit("==>")
+        .next()
+        .and_then(|value| u32::from_str_radix(value.trim(), 16).ok())
+        .ok_or_else(|| UbootError::Command(command.clone(), output.clone()))?;
+    let expected = crc32(data);
+    if actual != expected {
+        return Err(UbootError::Verify(format!("{:#x} 处CRC32为 {:08x}，期望 {:08x}", addr, actual, expected)));
+    }
+    Ok(())
+}
+
+// 把内存中的数据写入eMMC分区，并回读校验
+async fn write_partition(addr: u64, partition: u32, data: &[u8]) -> Result<(), UbootError> {
+    run_checked(&format!("mmc dev {}", MMC_DEVICE), COMMAND_TIMEOUT_MS).await?;
+    run_checked(&format!("part start mmc {} {} recovery_start", MMC_DEVICE, partition), COMMAND_TIMEOUT_MS).await?;
+    run_checked(&format!("part size mmc {} {} recovery_size", MMC_DEVICE, partition), COMMAND_TIMEOUT_MS).await?;
+    let start = read_hex_env("recovery_start").await?;
+    let size = read_hex_env("recovery_size").await?;
+    let blocks = data.len().div_ceil(MMC_BLOCK_SIZE) as u64;
+    if blocks > size {
+        return Err(UbootError::Firmware(format!("分区 {} 只有 {} 块，固件需要 {} 块", partition, size, blocks)));
+    }
+
+    log(&format!("写入分区 {}: 起始块 {:#x}, {} 块", partition, start, blocks));
+    let output = run_checked(&format!("mmc write {:#x} {:#x} {:#x}", addr, start, blocks), MMC_TIMEOUT_MS).await?;
+    if !output.contains("OK") {
+        return Err(UbootError::Command("mmc write".to_string(), output));
+    }
+    let verify_addr = addr + VERIFY_OFFSET;
+    run_checked(&format!("mmc read {:#x} {:#x} {:#x}", verify_addr, start, blocks), MMC_TIMEOUT_MS).await?;
+    verify_crc32(verify_addr, data).await
+}
+
+// 读取十六进制的环境变量
+async fn read_hex_env(name: &str) -> Result<u64, UbootError> {
+    let value = getenv(name).await?.unwrap_or_default();
+    u64::from_str_radix(value.trim().trim_start_matches("0x"), 16)
+        .map_err(|_| UbootError::Command(format!("printenv {}", name), value))
+}
+
+// 救砖：把产测包中的U-Boot和DTB加载到内存并写入主/备分区，调用前需要已进入U-Boot命令行
+pub async fn recover_firmware() -> Result<(), UbootError> {
+    let addr = match getenv("loadaddr").await? {
+        Some(value) => u64::from_str_radix(value.trim().trim_start_matches("0x"), 16).unwrap_or(DEFAULT_LOAD_ADDR),
+        None => DEFAULT_LOAD_ADDR,
+    };
+    for (prefix, extension, partitions) in RECOVERY_IMAGES {
+        let (file_name, data) = load_firmware(prefix, extension)?;
+        log(&format!("恢复固件 {} ({} 字节)", file_name, data.len()));
+        load_binary(addr, &file_name, &data).await?;
+        for partition in partitions {
+            write_partition(addr, partition, &data).await?;
+        }
+    }
+    log("固件恢复完成");
+    Ok(())
+}
+
+// 查找固件：优先使用app目录下已解压的产测包，否则直接从tar中读取
+fn load_firmware(prefix: &str, extension: &str) -> Result<(String, Vec<u8>), UbootError> {
+    let is_target = |name: &str| name.starts_with(prefix) && name.ends_with(extension);
+    let tar_path = get_app_file_path();
+    if tar_path.as_os_str().is_empty() {
+        return Err(UbootError::Firmware("app目录中没有产测包".to_string()));
+    }
+
+    if let Some(app_dir) = tar_path.parent() {
+        if let Ok(entries) = fs::read_dir(app_dir.join(FIRMWARE_DIR)) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if is_target(&name) {
+                    let data = fs::read(entry.path()).map_err(|e| UbootError::Firmware(format!("读取 {} 失败: {}", name, e)))?;
+                    return Ok((name, data));
+                }
+            }
+        }
+    }
+
+    read_from_tar(&tar_path, |path| {
+        let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+        dir.ends_with(FIRMWARE_DIR) && is_target(name)
+    })
+    .map_err(|e| UbootError::Firmware(format!("读取 {} 失败: {}", tar_path.display(), e)))?
+    .ok_or_else(|| UbootError::Firmware(format!("产测包中没有 {}*{}", prefix, extension)))
+}
+
+// 从tar包中读取第一个匹配的普通文件，返回文件名和内容
+fn read_from_tar(tar_path: &Path, is_target: impl Fn(&str) -> bool) -> std::io::Result<Option<(String, Vec<u8>)>> {
+    let mut file = File::open(tar_path)?;
+    let mut header = [0u8; 512];
+    let mut long_name: Option<String> = None;
+    loop {
+        if file.read_exact(&mut header).is_err() || header.iter().all(|byte| *byte == 0) {
+            return Ok(None);
+        }
+        let size = parse_octal(&header[124..136]);
+        let padded = size.div_ceil(512) * 512;
+        let type_flag = header[156];
+
+        // GNU长文件名：内容是下一个条目的完整路径
+        if type_flag == b'L' {
+            let mut name = vec![0u8; padded as usize];
+            file.read_exact(&mut name)?;
+            name.truncate(size as usize);
+            long_name = Some(c_string(&name));
+            continue;
+        }
+
+        let mut path = c_string(&header[0..100]);
+        if header[257..].starts_with(b"ustar") {
+            let prefix = c_string(&header[345..500]);
+            if !prefix.is_empty() {
+                path = format!("{}/{}", prefix, path);
+            }
+        }
+        let path = long_name.take().unwrap_or(path);
+        let path = path.trim_start_matches("./").to_string();
+
+        if (type_flag == b'0' || type_flag == 0) && is_target(&path) {
+            let mut data = vec![0u8; size as usize];
+            file.read_exact(&mut data)?;
+            let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+            return Ok(Some((name, data)));
+        }
+        file.seek(SeekFrom::Current(padded as i64))?;
+    }
+}
+
+fn parse_octal(field: &[u8]) -> u64 {
+    let text = c_string(field);
+    u64::from_str_radix(text.trim(), 8).unwrap_or(0)
+}
+
+fn c_string(field: &[u8]) -> String {
+    let end = field.iter().position(|byte| *byte == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+// CRC32（IEEE），与U-Boot的crc32命令一致
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xffff_ffff;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    #[test]
+    fn crc32_matches_ieee_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    // 生成tar条目头，只填写解析用到的字段
+    fn tar_header(name: &str, size: usize, type_flag: u8, prefix: &str) -> [u8; 512] {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let size = format!("{:011o}", size);
+        header[124..124 + size.len()].copy_from_slice(size.as_bytes());
+        header[156] = type_flag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        header
+    }
+
+    // 条目头加内容，内容补齐到512字节
+    fn tar_entry(tar: &mut Vec<u8>, header: [u8; 512], data: &[u8]) {
+        tar.extend_from_slice(&header);
+        tar.extend_from_slice(data);
+        tar.resize(tar.len().div_ceil(512) * 512, 0);
+    }
+
+    fn write_tar(test: &str, tar: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("uboot_{}_{}.tar", test, std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(tar).unwrap();
+        file.write_all(&[0u8; 1024]).unwrap();
+        path
+    }
+
+    #[test]
+    fn read_from_tar_skips_other_entries() {
+        let mut tar = Vec::new();
+        tar_entry(&mut tar, tar_header("./NanoKVM_Pro_Testing/", 0, b'5', ""), b"");
+        tar_entry(&mut tar, tar_header("./NanoKVM_Pro_Testing/firmware/readme.txt", 600, b'0', ""), &[b'r'; 600]);
+        tar_entry(&mut tar, tar_header("./NanoKVM_Pro_Testing/firmware/u-boot.bin", 5, b'0', ""), b"uboot");
+        let path = write_tar("skip", &tar);
+
+        let found = read_from_tar(&path, |path| path.ends_with(".bin")).unwrap();
+        assert_eq!(found, Some(("u-boot.bin".to_string(), b"uboot".to_vec())));
+        assert_eq!(read_from_tar(&path, |path| path.ends_with(".dtb")).unwrap(), None);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn read_from_tar_joins_ustar_prefix() {
+        let mut tar = Vec::new();
+        tar_entry(&mut tar, tar_header("u-boot.bin", 3, b'0', "NanoKVM_Pro_Testing/firmware"), b"abc");
+        let path = write_tar("prefix", &tar);
+
+        let found = read_from_tar(&path, |path| path == "NanoKVM_Pro_Testing/firmware/u-boot.bin").unwrap();
+        assert_eq!(found, Some(("u-boot.bin".to_string(), b"abc".to_vec())));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn read_from_tar_uses_gnu_long_name() {
+        let long_path = format!("NanoKVM_Pro_Testing/firmware/{}.dtb", "d".repeat(120));
+        let mut tar = Vec::new();
+        tar_entry(&mut tar, tar_header("././@LongLink", long_path.len() + 1, b'L', ""), format!("{}\0", long_path).as_bytes());
+        tar_entry(&mut tar, tar_header(&long_path[..100], 4, b'0', ""), b"dtb!");
+        let path = write_tar("long", &tar);
+
+        let found = read_from_tar(&path, |path| path == long_path).unwrap();
+        assert_eq!(found, Some((format!("{}.dtb", "d".repeat(120)), b"dtb!".to_vec())));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn read_from_tar_stops_at_end_marker() {
+        let path = write_tar("empty", &[]);
+        assert_eq!(read_from_tar(&path, |_| true).unwrap(), None);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,249 @@
+// YMODEM发送端：配合U-Boot的loady命令，通过串口把固件发送到板卡内存
+// 第0包为文件名和大小，数据包使用1K块和CRC16校验
+use std::fmt;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use super::hub::{subscribe_console, ConsoleSubscription, SubscribeFrom};
+use super::serial_send_bytes;
+
+const SOH: u8 = 0x01;       // 128字节数据包
+const STX: u8 = 0x02;       // 1024字节数据包
+const EOT: u8 = 0x04;       // 传输结束
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;       // 取消传输
+const CRC_MODE: u8 = b'C';  // 接收端请求CRC模式
+const PAD: u8 = 0x1a;       // 最后一包的填充字节
+
+// 等待接收端开始的时间
+const START_TIMEOUT_MS: u64 = 60_000;
+// YMODEM结束时等待接收端请求空包的时间
+const FINISH_TIMEOUT_MS: u64 = 3_000;
+// 等待单个数据包应答的时间
+const PACKET_TIMEOUT_MS: u64 = 10_000;
+// 单个数据包最多重发次数
+const MAX_RETRIES: u32 = 10;
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = true;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[xmodem]{}", msg);
+    }
+}
+
+// 传输失败原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransferError {
+    StartTimeout,           // 接收端没有发出开始信号
+    Timeout(usize),         // 等待第n个数据包应答超时
+    TooManyRetries(usize),  // 第n个数据包重发次数过多
+    Cancelled,              // 接收端取消传输
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferError::StartTimeout => write!(f, "等待接收端开始超时"),
+            TransferError::Timeout(block) => write!(f, "等待第 {} 包应答超时", block),
+            TransferError::TooManyRetries(block) => write!(f, "第 {} 包重发超过 {} 次", block, MAX_RETRIES),
+            TransferError::Cancelled => write!(f, "接收端取消了传输"),
+        }
+    }
+}
+
+// 接收端的应答
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reply {
+    Ack,
+    Nak,
+    Crc,
+    Cancel,
+    Timeout,
+}
+
+// 从串口数据中读取接收端应答，忽略其他字节（如U-Boot打印的提示）
+struct ReplyReader {
+    subscription: ConsoleSubscription,
+    pending: Vec<u8>,
+}
+
+impl ReplyReader {
+    fn new() -> ReplyReader {
+        ReplyReader { subscription: subscribe_console("xmodem", SubscribeFrom::Now), pending: Vec::new() }
+    }
+
+    async fn next(&mut self, timeout_ms: u64) -> Reply {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        loop {
+            while !self.pending.is_empty() {
+                let byte = self.pending.remove(0);
+                match byte {
+                    ACK => return Reply::Ack,
+                    NAK => return Reply::Nak,
+                    CRC_MODE => return Reply::Crc,
+                    // 连续两个CAN才认为是取消，避免数据中的单个0x18误判
+                    CAN if self.pending.first() == Some(&CAN) => {
+                        self.pending.remove(0);
+                        return Reply::Cancel;
+                    }
+                    _ => {}
+                }
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Reply::Timeout;
+            }
+            let chunk = self.subscription.recv_timeout((deadline - now).as_millis() as u64).await;
+            self.pending.extend_from_slice(&chunk.data);
+        }
+    }
+
+    // 丢弃已收到但未处理的应答
+    fn clear(&mut self) {
+        self.subscription.skip_to_end();
+        self.pending.clear();
+    }
+}
+
+// CRC16-CCITT（XMODEM），多项式0x1021，初值0
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+// 组装数据包，数据不足时用pad填充
+fn build_packet(block: u8, data: &[u8], size: usize, pad: u8) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(size + 5);
+    packet.push(if size == 1024 { STX } else { SOH });
+    packet.push(block);
+    packet.push(!block);
+    let start = packet.len();
+    packet.extend_from_slice(data);
+    packet.resize(start + size, pad);
+    let crc = crc16(&packet[start..]);
+    packet.extend_from_slice(&crc.to_be_bytes());
+    packet
+}
+
+// 发送一个数据包并等待ACK，被拒绝或应答超时时重发
+async fn send_packet(reader: &mut ReplyReader, packet: &[u8], index: usize) -> Result<(), TransferError> {
+    let mut timed_out = false;
+    for _ in 0..MAX_RETRIES {
+        serial_send_bytes(packet).await;
+        match reader.next(PACKET_TIMEOUT_MS).await {
+            Reply::Ack => return Ok(()),
+            Reply::Cancel => return Err(TransferError::Cancelled),
+            Reply::Timeout => {
+                log(&format!("第 {} 包应答超时，重发", index));
+                timed_out = true;
+            }
+            Reply::Nak | Reply::Crc => {
+                log(&format!("第 {} 包被拒绝，重发", index));
+                timed_out = false;
+            }
+        }
+    }
+    // 最后一次仍然没有应答时报告超时，否则报告重发次数过多
+    if timed_out {
+        Err(TransferError::Timeout(index))
+    } else {
+        Err(TransferError::TooManyRetries(index))
+    }
+}
+
+// 等待接收端请求CRC模式的开始信号
+async fn wait_start(reader: &mut ReplyReader, timeout_ms: u64) -> Result<(), TransferError> {
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    while Instant::now() < deadline {
+        match reader.next(1000).await {
+            Reply::Crc => return Ok(()),
+            Reply::Cancel => return Err(TransferError::Cancelled),
+            Reply::Ack | Reply::Nak | Reply::Timeout => {}
+        }
+    }
+    Err(TransferError::StartTimeout)
+}
+
+// 发送传输结束，接收端可能先回NAK要求再次确认
+async fn send_eot(reader: &mut ReplyReader) -> Result<(), TransferError> {
+    for _ in 0..MAX_RETRIES {
+        serial_send_bytes(&[EOT]).await;
+        match reader.next(PACKET_TIMEOUT_MS).await {
+            Reply::Ack => return Ok(()),
+            Reply::Cancel => return Err(TransferError::Cancelled),
+            _ => {}
+        }
+    }
+    Err(TransferError::TooManyRetries(0))
+}
+
+// 发送文件，调用前需要先让接收端进入接收状态（如发送 "loady\n"）
+pub async fn send_file(file_name: &str, data: &[u8]) -> Result<(), TransferError> {
+    let mut reader = ReplyReader::new();
+    log(&format!("开始发送 {} ({} 字节)", file_name, data.len()));
+    wait_start(&mut reader, START_TIMEOUT_MS).await?;
+
+    // 第0包：文件名和大小
+    let mut header = Vec::new();
+    header.extend_from_slice(file_name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(data.len().to_string().as_bytes());
+    header.push(0);
+    send_packet(&mut reader, &build_packet(0, &header, 128, 0), 0).await?;
+    // 第0包应答后接收端再次发出'C'开始接收数据
+    wait_start(&mut reader, PACKET_TIMEOUT_MS).await?;
+
+    let size = 1024;
+    let total = data.len().div_ceil(size);
+    for (index, chunk) in data.chunks(size).enumerate() {
+        let block = ((index + 1) % 256) as u8;
+        send_packet(&mut reader, &build_packet(block, chunk, size, PAD), index + 1).await?;
+        if (index + 1) % 64 == 0 || index + 1 == total {
+            log(&format!("已发送 {}/{} 包", index + 1, total));
+        }
+    }
+    send_eot(&mut reader).await?;
+
+    // 用空的第0包结束整个会话
+    if wait_start(&mut reader, FINISH_TIMEOUT_MS).await.is_ok() {
+        send_packet(&mut reader, &build_packet(0, &[], 128, 0), 0).await?;
+    }
+    reader.clear();
+    log(&format!("{} 发送完成", file_name));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_matches_xmodem_check_value() {
+        assert_eq!(crc16(b""), 0);
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+    }
+
+    #[test]
+    fn build_packet_pads_data_and_appends_crc() {
+        let packet = build_packet(1, b"abc", 128, 0x1a);
+        assert_eq!(packet.len(), 128 + 5);
+        assert_eq!(&packet[..3], &[SOH, 1, 0xfe]);
+        assert_eq!(&packet[3..6], b"abc");
+        assert!(packet[6..131].iter().all(|byte| *byte == 0x1a));
+        assert_eq!(&packet[131..], &crc16(&packet[3..131]).to_be_bytes());
+
+        let packet = build_packet(2, &[0u8; 1024], 1024, 0);
+        assert_eq!(packet[0], STX);
+        assert_eq!(packet.len(), 1024 + 5);
+    }
+}
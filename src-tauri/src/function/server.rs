@@ -1,12 +1,46 @@
 use tauri::async_runtime::{spawn, JoinHandle};
+use tauri::AppHandle;
 
 
 use warp::Filter;
-use bytes::Bytes;
+use warp::http::{header, Method, Response, StatusCode};
+use warp::hyper::Body;
+use bytes::{Buf, Bytes};
+use futures::{stream, Stream, StreamExt};
+use lazy_static::lazy_static;
+use rand::{RngCore, SeedableRng};
+use rand::rngs::StdRng;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read, SeekFrom};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::time::timeout_at;
 
-use crate::function::save::get_app_file_path;
+use crate::function::save::{get_app_file_path, get_app_packages, get_config_str, get_server_config};
+use crate::function::dialog_test::show_dialog;
+
+// 下载时每次从磁盘读取或生成的大小
+const STREAM_CHUNK_SIZE: u64 = 256 * 1024;
+// 测速下载的默认和最大数据量，单位MB
+const SPEED_TEST_DEFAULT_MB: u64 = 5;
+const SPEED_TEST_MAX_MB: u64 = 1024;
+
+// 网络测试命令行的读取超时
+const NET_COMMAND_TIMEOUT_MS: u64 = 5_000;
+// TCP测试最长时间，单位秒
+const NET_TEST_MAX_SECONDS: u64 = 60;
+// UDP测试最多的包数
+const UDP_MAX_PACKETS: u64 = 100_000;
+
+// 板卡下载产测包的服务端测量值使用的项目名，与前端状态名一致
+pub const DUT_DOWNLOAD_ITEM: &str = "download_test";
 
 // 日志控制：false=关闭日志，true=开启日志
 const LOG_ENABLE: bool = true;
@@ -18,33 +52,249 @@ fn log(msg: &str) {
     }
 }
 
-pub fn spawn_file_server_task() -> JoinHandle<()> {
+// 已计算的产测包校验值，文件大小或修改时间变化时重新计算
+struct PackageHash {
+    size: u64,
+    modified: Option<SystemTime>,
+    sha256: String,
+}
+
+// 服务端测得的一次测速结果
+#[derive(Debug, Clone, Copy)]
+pub struct ServerMeasurement {
+    pub bytes: u64,
+    pub millis: u64,
+    pub mbps: f64,
+}
+
+// 一次网络测试的结果，rx为本机接收（板卡上传），tx为本机发送（板卡下载），速度单位Mbps
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NetTestResult {
+    pub item: String,
+    pub protocol: String,               // "tcp" / "udp"
+    pub mode: String,                   // TCP："upload" / "download" / "bidir"；UDP："loss"
+    pub millis: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_mbps: f64,
+    pub tx_mbps: f64,
+    pub client_mbps: Option<f64>,       // 板卡端测得的下载速度
+    pub packets_expected: u64,
+    pub packets_received: u64,
+    pub loss_percent: Option<f64>,
+    pub jitter_ms: Option<f64>,
+    pub passed: bool,
+    pub defects: Vec<String>,
+}
+
+impl NetTestResult {
+    // 按测试方向取本机测得的速度，双向测试取较慢的方向
+    pub fn mbps(&self) -> f64 {
+        match self.mode.as_str() {
+            "upload" => self.rx_mbps,
+            "download" => self.tx_mbps,
+            _ => self.rx_mbps.min(self.tx_mbps),
+        }
+    }
+
+    // 转为测试脚本的结构化结果行，格式见 doc/ResultProtocol.md
+    fn to_result_line(&self) -> String {
+        let result = serde_json::json!({
+            "item": self.item,
+            "status": if self.passed { "pass" } else { "fail" },
+            "values": self,
+            "defects": self.defects,
+            "message": "",
+        });
+        format!("@@RESULT {}\n", result)
+    }
+}
+
+// 按客户端IP统计的文件服务器访问情况
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClientStats {
+    pub ip: String,
+    pub requests: u64,
+    pub failures: u64,          // 4xx/5xx响应和中断的传输
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub transfer_ms: u64,       // 传输累计用时
+    pub active: u32,            // 进行中的传输
+    pub last_path: String,
+    pub last_status: u16,
+    pub last_seen: String,      // 最后一次请求的本地时间
+    pub is_dut: bool,           // 是否为当前测试的板卡
+}
+
+// 文件服务器状态，供前端和 /status 查询
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FileServerStatus {
+    pub dut_ip: Option<String>,
+    pub clients: Vec<ClientStats>,
+}
+
+// 一次数据传输，结束或中断（连接断开时流被丢弃）时计入客户端统计
+struct Transfer {
+    ip: Option<IpAddr>,
+    item: Option<&'static str>,     // 完整传输后记录服务端测量值的项目
+    expected: u64,
+    bytes: u64,
+    started: Instant,
+}
+
+impl Transfer {
+    fn new(ip: Option<IpAddr>, item: Option<&'static str>, expected: u64) -> Transfer {
+        if let Some(ip) = ip {
+            client_entry(ip, |stats| stats.active += 1);
+        }
+        Transfer { ip, item, expected, bytes: 0, started: Instant::now() }
+    }
+
+    fn add(&mut self, bytes: usize) {
+        self.bytes += bytes as u64;
+    }
+}
+
+impl Drop for Transfer {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        let complete = self.bytes >= self.expected;
+        if let Some(ip) = self.ip {
+            client_entry(ip, |stats| {
+                stats.active = stats.active.saturating_sub(1);
+                stats.bytes_sent += self.bytes;
+                stats.transfer_ms += elapsed.as_millis() as u64;
+                if !complete {
+                    stats.failures += 1;
+                }
+            });
+        }
+        if !complete {
+            log(&format!("❌ {:?} 传输中断 {}/{} 字节", self.ip, self.bytes, self.expected));
+        } else if let Some(item) = self.item {
+            let measurement = record_measurement(Some(item), self.bytes, elapsed);
+            log(&format!("✅ {:?} {} 完成 {} 字节, {} ms, {:.1} Mbps", self.ip, item, measurement.bytes, measurement.millis, measurement.mbps));
+        }
+    }
+}
+
+// 进行中的UDP测试
+struct UdpSession {
+    expected: u64,
+    received: Vec<bool>,        // 按序号记录是否收到，重复的包只算一次
+    last_transit_us: Option<i64>,
+    jitter_us: f64,             // RFC 3550 的平滑抖动
+    started: Instant,
+}
+
+lazy_static! {
+    static ref PACKAGE_HASHES: Mutex<HashMap<PathBuf, PackageHash>> = Mutex::new(HashMap::new());
+    static ref SPEED_MEASUREMENTS: Mutex<HashMap<String, ServerMeasurement>> = Mutex::new(HashMap::new());   // 按测试项目保存最后一次
+    static ref NET_TEST_RESULTS: Mutex<HashMap<String, NetTestResult>> = Mutex::new(HashMap::new());        // 按测试项目保存最后一次
+    static ref UDP_SESSIONS: Mutex<HashMap<String, UdpSession>> = Mutex::new(HashMap::new());
+    static ref CLIENT_STATS: Mutex<HashMap<IpAddr, ClientStats>> = Mutex::new(HashMap::new());
+    static ref DUT_IP: Mutex<Option<IpAddr>> = Mutex::new(None);       // 当前测试的板卡
+}
+
+// 包列表中的一项
+#[derive(Serialize)]
+struct PackageEntry {
+    name: String,
+    size: u64,
+    sha256: String,
+    default: bool,      // 不指定文件名时/download下载的包
+}
+
+// Range请求的处理方式
+#[derive(Debug, PartialEq)]
+enum RangeRequest {
+    Full,                   // 没有Range或格式不支持，发送完整文件
+    Partial(u64, u64),      // 发送[start, end]，包含end
+    Unsatisfiable,          // 范围超出文件大小
+}
+
+// 板卡访问文件服务器的URL，host为板卡所在网络中本机的地址，端口来自配置 [server]
+pub fn server_url(host: &str, path: &str) -> String {
+    format!("http://{}:{}{}", host, get_server_config().port, path)
+}
+
+// 服务启动失败时弹窗提示，避免任务静默退出后板卡下载和测速全部失败
+fn report_start_failure(app_handle: &AppHandle, message: String) {
+    log(&format!("❌ {}", message));
+    show_dialog(app_handle.clone(), format!("⚠️ {}\n请关闭占用端口的程序，或修改配置文件[server]中的端口后重启程序", message), vec![
+        serde_json::json!({ "text": "确定" })
+    ], |_| {});
+}
+
+pub fn spawn_file_server_task(app_handle: AppHandle) -> JoinHandle<()> {
     spawn(async move {
         log("文件服务器任务开始");
-        // 下载路由
+        let config = get_server_config();
+        let bind_ip = match config.bind.parse::<IpAddr>() {
+            Ok(ip) => ip,
+            Err(e) => {
+                report_start_failure(&app_handle, format!("文件服务器监听地址 {:?} 无效: {}", config.bind, e));
+                return;
+            }
+        };
+        // 下载路由，支持Range断点续传
         let download = warp::path("download")
-            .and(warp::get())
+            .and(warp::get().or(warp::head()).unify())
             .and(warp::query::<DownloadParams>())
+            .and(warp::header::optional::<String>("range"))
+            .and(warp::header::optional::<String>("if-range"))
+            .and(warp::method())
+            .and(warp::addr::remote())
             .and_then(download_handler);
 
-        // 10MB测试数据路由
+        // 产测包列表路由
+        let manifest = warp::path("manifest")
+            .and(warp::get())
+            .and_then(manifest_handler);
+
+        // 测速下载路由，数据量由size_mb指定
         let download_small = warp::path("download_small")
             .and(warp::get())
+            .and(warp::query::<SpeedParams>())
+            .and(warp::addr::remote())
             .and_then(download_small_handler);
 
-        // 上传路由
+        // 测速上传路由
         let upload = warp::path("upload")
             .and(warp::post())
-            .and(warp::body::bytes())
+            .and(warp::query::<SpeedParams>())
+            .and(warp::addr::remote())
+            .and(warp::body::stream())
             .and_then(upload_handler);
 
+        // 各客户端访问统计路由
+        let status = warp::path("status")
+            .and(warp::get())
+            .map(|| warp::reply::json(&get_file_server_status()));
+
         // 组合路由
         let routes = download
+            .or(manifest)
             .or(download_small)
             .or(upload)
+            .or(status)
+            .with(warp::log::custom(record_request))
             .with(warp::cors().allow_any_origin());
 
-        warp::serve(routes).run(([0, 0, 0, 0], 8080)).await;    // 所有地址
+        // 端口被占用时直接报错，不使用会panic的run
+        let server = match warp::serve(routes).try_bind_ephemeral(SocketAddr::new(bind_ip, config.port)) {
+            Ok((addr, server)) => {
+                log(&format!("文件服务器启动，监听 {}", addr));
+                server
+            }
+            Err(e) => {
+                report_start_failure(&app_handle, format!("文件服务器无法监听端口 {}: {}", config.port, e));
+                return;
+            }
+        };
+
+        // 网络测试服务与文件服务器一起运行，任务终止时一起关闭
+        tokio::join!(server, net_test_server(&app_handle, bind_ip, config.net_test_port));
     })
 }
 
@@ -52,69 +302,682 @@ pub fn spawn_file_server_task() -> JoinHandle<()> {
 #[derive(serde::Deserialize)]
 struct DownloadParams {
     // size_mb: Option<usize>,
+    file: Option<String>,   // 包文件名，不指定时使用app目录中的第一个包
+}
+
+// 计算文件的SHA-256
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
 }
 
-// 下载处理 - 读取固定文件
-async fn download_handler(_params: DownloadParams) -> Result<impl warp::Reply, warp::Rejection> {
+// 获取文件大小和SHA-256，未变化的文件使用缓存
+async fn package_hash(path: &Path) -> io::Result<(u64, String)> {
+    let metadata = tokio::fs::metadata(path).await?;
+    let modified = metadata.modified().ok();
+    let cached = PACKAGE_HASHES.lock().unwrap_or_else(|e| e.into_inner()).get(path)
+        .filter(|cached| cached.size == metadata.len() && cached.modified == modified)
+        .map(|cached| cached.sha256.clone());
+    if let Some(sha256) = cached {
+        return Ok((metadata.len(), sha256));
+    }
+
+    log(&format!("计算校验值: {}", path.display()));
+    let file_path = path.to_path_buf();
+    let sha256 = tokio::task::spawn_blocking(move || hash_file(&file_path)).await.map_err(io::Error::other)??;
+    PACKAGE_HASHES.lock().unwrap_or_else(|e| e.into_inner()).insert(path.to_path_buf(), PackageHash {
+        size: metadata.len(),
+        modified,
+        sha256: sha256.clone(),
+    });
+    Ok((metadata.len(), sha256))
+}
+
+// 解析Range头，只支持单个范围：bytes=start-end、bytes=start-、bytes=-suffix
+fn parse_range(range: Option<&str>, size: u64) -> RangeRequest {
+    let Some(spec) = range.and_then(|range| range.trim().strip_prefix("bytes=")) else { return RangeRequest::Full };
+    // 多个范围不支持，按规范可以忽略Range发送完整文件
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((first, last)) = spec.split_once('-') else { return RangeRequest::Full };
+    let (first, last) = (first.trim(), last.trim());
+
+    if first.is_empty() {
+        // 最后suffix字节
+        let Ok(suffix) = last.parse::<u64>() else { return RangeRequest::Full };
+        if suffix == 0 || size == 0 {
+            return RangeRequest::Unsatisfiable;
+        }
+        return RangeRequest::Partial(size.saturating_sub(suffix), size - 1);
+    }
+
+    let Ok(start) = first.parse::<u64>() else { return RangeRequest::Full };
+    let end = if last.is_empty() {
+        u64::MAX
+    } else {
+        match last.parse::<u64>() {
+            Ok(end) if end >= start => end,
+            _ => return RangeRequest::Full,
+        }
+    };
+    if start >= size {
+        return RangeRequest::Unsatisfiable;
+    }
+    RangeRequest::Partial(start, end.min(size - 1))
+}
+
+// 从文件当前位置读取remaining字节，按块输出
+fn file_stream(file: tokio::fs::File, remaining: u64) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+    stream::try_unfold((file, remaining), |(mut file, remaining)| async move {
+        if remaining == 0 {
+            return Ok(None);
+        }
+        let mut buf = vec![0u8; remaining.min(STREAM_CHUNK_SIZE) as usize];
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            log("❌ 文件在下载过程中被截断");
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "文件在下载过程中被截断"));
+        }
+        buf.truncate(n);
+        Ok(Some((Bytes::from(buf), (file, remaining - n as u64))))
+    })
+}
+
+// 按文件名查找app目录中的包，只接受包列表中的文件名
+fn find_package(name: &str) -> Option<PathBuf> {
+    get_app_packages().into_iter().find(|path| path.file_name().is_some_and(|file_name| file_name == name))
+}
+
+// 下载处理 - 从磁盘流式发送产测包，支持Range断点续传
+async fn download_handler(params: DownloadParams, range: Option<String>, if_range: Option<String>, method: Method, remote: Option<SocketAddr>) -> Result<Response<Body>, warp::Rejection> {
     // "C:\Users\BuGu\AppData\Local\NanoKVM-Testing\app\NanoKVM_Pro_Testing_V2_0.tar"
-    // let file_path = "C:\\Users\\BuGu\\AppData\\Local\\NanoKVM-Testing\\app\\NanoKVM_Pro_Testing_V2_0.tar"
-    let file_path = get_app_file_path();
+    let file_path = match &params.file {
+        Some(name) => find_package(name).unwrap_or_default(),
+        None => get_app_file_path(),
+    };
     log(&format!("获取到的文件路径: {:?}", file_path));
+    if file_path.as_os_str().is_empty() {
+        log("❌ 没有可下载的产测包");
+        return Err(warp::reject::not_found());
+    }
 
-    log("开始下载测试");
-    
-    // 读取文件内容
-    let mut file = match File::open(file_path) {
+    let (size, sha256) = match package_hash(&file_path).await {
+        Ok(hash) => hash,
+        Err(e) => {
+            log(&format!("❌ 无法读取文件: {}", e));
+            return Err(warp::reject::not_found());
+        }
+    };
+    let etag = format!("\"{}\"", sha256);
+
+    // If-Range与当前文件不一致时说明文件已更新，忽略Range重新发送完整文件
+    let range = match if_range {
+        Some(tag) if tag.trim() != etag => None,
+        _ => range,
+    };
+    let (status, start, end) = match parse_range(range.as_deref(), size) {
+        RangeRequest::Full => (StatusCode::OK, 0, size),
+        RangeRequest::Partial(start, end) => (StatusCode::PARTIAL_CONTENT, start, end + 1),
+        RangeRequest::Unsatisfiable => {
+            log(&format!("❌ 请求范围无效: {:?}, 文件大小 {}", range, size));
+            return Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                .body(Body::empty())
+                .map_err(|_| warp::reject::not_found());
+        }
+    };
+
+    let mut file = match tokio::fs::File::open(&file_path).await {
         Ok(file) => file,
         Err(e) => {
             log(&format!("❌ 无法打开文件: {}", e));
             return Err(warp::reject::not_found());
         }
     };
-    
-    let mut data = Vec::new();
-    if let Err(e) = file.read_to_end(&mut data) {
-        log(&format!("❌ 读取文件失败: {}", e));
-        return Err(warp::reject::not_found());
+    if start > 0 {
+        if let Err(e) = file.seek(SeekFrom::Start(start)).await {
+            log(&format!("❌ 定位文件失败: {}", e));
+            return Err(warp::reject::not_found());
+        }
     }
-    
-    log("✅ 下载完成");
-    
-    Ok(data)
+
+    if status == StatusCode::PARTIAL_CONTENT {
+        log(&format!("开始下载测试 {}-{}/{}", start, end - 1, size));
+    } else {
+        log(&format!("开始下载测试 {} 字节", size));
+    }
+    let name = file_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/x-tar")
+        .header(header::CONTENT_LENGTH, end - start)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, &etag)
+        .header("X-Content-SHA256", &sha256)
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", name));
+    if status == StatusCode::PARTIAL_CONTENT {
+        response = response.header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end - 1, size));
+    }
+    // HEAD请求不发送内容，不计入传输统计
+    if method == Method::HEAD {
+        return response
+            .body(Body::wrap_stream(file_stream(file, end - start)))
+            .map_err(|e| {
+                log(&format!("❌ 生成响应失败: {}", e));
+                warp::reject::not_found()
+            });
+    }
+    // 当前测试的板卡完整下载时记录服务端测量值，写入该板卡的记录
+    let ip = remote.map(|remote| remote.ip());
+    let item = if status == StatusCode::OK && ip.is_some() && ip == dut_ip() { Some(DUT_DOWNLOAD_ITEM) } else { None };
+    response
+        .body(Body::wrap_stream(metered(file_stream(file, end - start), Transfer::new(ip, item, end - start))))
+        .map_err(|e| {
+            log(&format!("❌ 生成响应失败: {}", e));
+            warp::reject::not_found()
+        })
 }
 
-// 上传处理 - 虚拟内存，不会真的存到文件系统
-async fn upload_handler(
-    body: Bytes
-) -> Result<impl warp::Reply, warp::Rejection> {
+// 产测包列表 - 返回app目录中所有包的文件名、大小和SHA-256
+async fn manifest_handler() -> Result<impl warp::Reply, warp::Rejection> {
+    let default_path = get_app_file_path();
+    let mut packages = Vec::new();
+    for path in get_app_packages() {
+        match package_hash(&path).await {
+            Ok((size, sha256)) => packages.push(PackageEntry {
+                name: path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+                size,
+                sha256,
+                default: path == default_path,
+            }),
+            Err(e) => log(&format!("❌ 无法读取 {}: {}", path.display(), e)),
+        }
+    }
+    Ok(warp::reply::json(&serde_json::json!({ "packages": packages })))
+}
+
+// 测速参数
+#[derive(serde::Deserialize)]
+struct SpeedParams {
+    item: Option<String>,       // 测试项目，用于关联服务端测量值，如 "eth_upload_test"
+    size_mb: Option<u64>,       // 下载数据量
+}
+
+// 记录服务端测量值
+fn record_measurement(item: Option<&str>, bytes: u64, elapsed: Duration) -> ServerMeasurement {
+    let millis = elapsed.as_millis() as u64;
+    let measurement = ServerMeasurement { bytes, millis, mbps: rate_mbps(bytes, elapsed) };
+    if let Some(item) = item {
+        SPEED_MEASUREMENTS.lock().unwrap_or_else(|e| e.into_inner()).insert(item.to_string(), measurement);
+    }
+    measurement
+}
+
+// 取出测试项目最后一次的服务端测量值，取出后清除，避免用到上一块板卡的结果
+pub fn take_server_measurement(item: &str) -> Option<ServerMeasurement> {
+    SPEED_MEASUREMENTS.lock().unwrap_or_else(|e| e.into_inner()).remove(item)
+}
+
+// 上传处理 - 边接收边丢弃，计时从收到请求头到收完数据
+async fn upload_handler<S, B>(params: SpeedParams, remote: Option<SocketAddr>, body: S) -> Result<impl warp::Reply, warp::Rejection>
+where
+    S: Stream<Item = Result<B, warp::Error>> + Send,
+    B: Buf + Send,
+{
     log("📤 开始接收上传数据...");
+    let started = Instant::now();
+    let mut total_bytes = 0u64;
+    let mut body = Box::pin(body);
+    while let Some(chunk) = body.next().await {
+        match chunk {
+            Ok(chunk) => total_bytes += chunk.remaining() as u64,
+            Err(e) => {
+                log(&format!("❌ 接收上传数据失败: {}", e));
+                record_upload(remote, total_bytes, started.elapsed(), false);
+                return Ok(warp::reply::json(&serde_json::json!({
+                    "success": false,
+                    "message": format!("接收失败: {}", e),
+                    "bytes": total_bytes,
+                })));
+            }
+        }
+    }
+    let measurement = record_measurement(params.item.as_deref(), total_bytes, started.elapsed());
+    record_upload(remote, total_bytes, started.elapsed(), true);
     
-    let total_bytes = body.len();
-    
-    // 模拟网络传输延迟（基于数据大小）
-    let simulated_delay_ms = (total_bytes as f64 / (1024.0 * 1024.0) * 10.0).max(10.0); // 每MB延迟10ms，最少10ms
-    tokio::time::sleep(std::time::Duration::from_millis(simulated_delay_ms as u64)).await;
-    
-    log("✅ 上传完成");
+    log(&format!("✅ 上传完成 {} 字节, {} ms, {:.1} Mbps", measurement.bytes, measurement.millis, measurement.mbps));
     
     Ok(warp::reply::json(&serde_json::json!({
         "success": true,
-        "message": "上传完成"
+        "message": "上传完成",
+        "bytes": measurement.bytes,
+        "millis": measurement.millis,
+        "mbps": measurement.mbps,
     })))
 }
 
-// 10MB测试数据下载处理
-async fn download_small_handler() -> Result<impl warp::Reply, warp::Rejection> {
-    log("📥 开始小文件测试数据下载...");
-    
-    // 生成5MB的零数据
-    let size_mb = 5;
+// 生成total字节的随机数据，不可压缩；发送完最后一块时记录服务端测量值
+// 最后一块交给系统缓冲区后就开始计算，数据量较小时服务端速度会偏高
+fn random_stream(total: u64, item: Option<String>) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+    let started = Instant::now();
+    stream::unfold((StdRng::from_entropy(), total), move |(mut rng, remaining)| {
+        let item = item.clone();
+        async move {
+            if remaining == 0 {
+                let measurement = record_measurement(item.as_deref(), total, started.elapsed());
+                log(&format!("✅ 测速数据发送完成 {} 字节, {} ms, {:.1} Mbps", measurement.bytes, measurement.millis, measurement.mbps));
+                return None;
+            }
+            let mut buf = vec![0u8; remaining.min(STREAM_CHUNK_SIZE) as usize];
+            rng.fill_bytes(&mut buf);
+            let sent = buf.len() as u64;
+            Some((Ok(Bytes::from(buf)), (rng, remaining - sent)))
+        }
+    })
+}
+
+// 测速数据下载处理 - 按需生成随机数据，不占用内存
+async fn download_small_handler(params: SpeedParams, remote: Option<SocketAddr>) -> Result<Response<Body>, warp::Rejection> {
+    let size_mb = params.size_mb.unwrap_or(SPEED_TEST_DEFAULT_MB).clamp(1, SPEED_TEST_MAX_MB);
     let total_bytes = size_mb * 1024 * 1024;
-    
-    // 创建10MB的零数据向量
-    let data = vec![0u8; total_bytes];
-    
-    log(&format!("✅ 小文件测试数据生成完成，大小: {} 字节", total_bytes));
-    
-    Ok(data)
-}
\ No newline at end of file
+    log(&format!("📥 开始测速数据下载，大小: {} 字节", total_bytes));
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, total_bytes)
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::wrap_stream(metered(random_stream(total_bytes, params.item), Transfer::new(remote.map(|remote| remote.ip()), None, total_bytes))))
+        .map_err(|e| {
+            log(&format!("❌ 生成响应失败: {}", e));
+            warp::reject::not_found()
+        })
+}
+
+// 取出客户端统计项并修改，没有时新建
+fn client_entry(ip: IpAddr, update: impl FnOnce(&mut ClientStats)) {
+    let mut clients = CLIENT_STATS.lock().unwrap_or_else(|e| e.into_inner());
+    let stats = clients.entry(ip).or_insert_with(|| ClientStats { ip: ip.to_string(), ..Default::default() });
+    update(stats);
+}
+
+// 记录每个请求的路径和状态，4xx/5xx计为失败
+fn record_request(info: warp::log::Info) {
+    let Some(remote) = info.remote_addr() else { return };
+    let status = info.status();
+    client_entry(remote.ip(), |stats| {
+        stats.requests += 1;
+        if status.is_client_error() || status.is_server_error() {
+            stats.failures += 1;
+        }
+        stats.last_path = info.path().to_string();
+        stats.last_status = status.as_u16();
+        stats.last_seen = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    });
+}
+
+// 记录一次上传，中途失败时计为失败
+fn record_upload(remote: Option<SocketAddr>, bytes: u64, elapsed: Duration, ok: bool) {
+    let Some(remote) = remote else { return };
+    client_entry(remote.ip(), |stats| {
+        stats.bytes_received += bytes;
+        stats.transfer_ms += elapsed.as_millis() as u64;
+        if !ok {
+            stats.failures += 1;
+        }
+    });
+}
+
+// 统计发送的字节数，流结束或被丢弃时由Transfer计入客户端统计
+fn metered<S>(stream: S, mut transfer: Transfer) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static
+where
+    S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+{
+    // 通过方法调用修改，闭包持有整个Transfer，流被丢弃时才会计入统计
+    stream.inspect(move |chunk| {
+        if let Ok(chunk) = chunk {
+            transfer.add(chunk.len());
+        }
+    })
+}
+
+fn dut_ip() -> Option<IpAddr> {
+    *DUT_IP.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// 设置当前测试的板卡IP，该IP完整下载产测包的速度记为 DUT_DOWNLOAD_ITEM，同时清除上一块板卡的测量值
+pub fn set_dut_ip(ip: &str) {
+    *DUT_IP.lock().unwrap_or_else(|e| e.into_inner()) = ip.parse().ok();
+    let _ = take_server_measurement(DUT_DOWNLOAD_ITEM);
+}
+
+// 各客户端的访问统计，按IP排序
+pub fn get_file_server_status() -> FileServerStatus {
+    let dut_ip = dut_ip();
+    let mut clients: Vec<(IpAddr, ClientStats)> = CLIENT_STATS.lock().unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(ip, stats)| (*ip, ClientStats { is_dut: Some(*ip) == dut_ip, ..stats.clone() }))
+        .collect();
+    clients.sort_by_key(|(ip, _)| *ip);
+    FileServerStatus {
+        dut_ip: dut_ip.map(|ip| ip.to_string()),
+        clients: clients.into_iter().map(|(_, stats)| stats).collect(),
+    }
+}
+
+// 前端查询文件服务器各客户端的访问统计
+#[tauri::command]
+pub fn file_server_status() -> FileServerStatus {
+    get_file_server_status()
+}
+
+// 网速测试的阈值配置：项目名以wifi开头的使用WiFi阈值，其余使用以太网阈值
+fn speed_threshold(item: &str, direction: &str) -> f64 {
+    let prefix = if item.starts_with("wifi") { "wifi" } else { "eth" };
+    let (key, default) = match (prefix, direction) {
+        ("wifi", "up") => ("wifi_up_speed", 10.0),
+        ("wifi", _) => ("wifi_down_speed", 10.0),
+        (_, "up") => ("eth_up_speed", 300.0),
+        _ => ("eth_down_speed", 500.0),
+    };
+    get_config_str("testing", key).and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+// 不良代码前缀，与脚本一致，如 "ETH"、"WIFI"
+fn defect_prefix(item: &str) -> String {
+    item.split('_').next().unwrap_or_default().to_uppercase()
+}
+
+fn rate_mbps(bytes: u64, elapsed: Duration) -> f64 {
+    bytes as f64 * 8.0 / elapsed.as_secs_f64().max(0.000_001) / 1_000_000.0
+}
+
+// 网络测试服务（类似iperf）：TCP端口接收命令，同一UDP端口接收测试包，板卡端客户端见 test_sh/net_test.sh
+async fn net_test_server(app_handle: &AppHandle, bind_ip: IpAddr, port: u16) {
+    let listener = match TcpListener::bind((bind_ip, port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            report_start_failure(app_handle, format!("网络测试服务无法监听TCP端口 {}: {}", port, e));
+            return;
+        }
+    };
+    let udp = match UdpSocket::bind((bind_ip, port)).await {
+        Ok(udp) => udp,
+        Err(e) => {
+            report_start_failure(app_handle, format!("网络测试服务无法监听UDP端口 {}: {}", port, e));
+            return;
+        }
+    };
+    log(&format!("网络测试服务启动，端口 {}", port));
+
+    let accept = async {
+        loop {
+            match listener.accept().await {
+                Ok((stream, addr)) => {
+                    spawn(handle_net_command(stream, addr));
+                }
+                Err(e) => log(&format!("❌ 接受连接失败: {}", e)),
+            }
+        }
+    };
+    tokio::join!(accept, udp_receiver(udp));
+}
+
+// 处理一条命令：
+// TCP <项目> <upload|download|bidir> <秒数>   开始TCP测试，结束时关闭连接
+// UDP <项目> <包数>                           准备UDP测试，回复OK后板卡开始发包
+// RESULT <项目> [板卡收到的字节数 微秒数]       回复一行结构化结果
+async fn handle_net_command(stream: TcpStream, addr: SocketAddr) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(NET_COMMAND_TIMEOUT_MS);
+    if !matches!(timeout_at(deadline, reader.read_line(&mut line)).await, Ok(Ok(n)) if n > 0) {
+        return;
+    }
+    let args: Vec<&str> = line.split_whitespace().collect();
+    log(&format!("{} 网络测试命令: {}", addr, line.trim()));
+
+    let reply = match args.as_slice() {
+        ["TCP", item, mode, seconds] if matches!(*mode, "upload" | "download" | "bidir") => {
+            let seconds = seconds.parse::<u64>().unwrap_or(5).clamp(1, NET_TEST_MAX_SECONDS);
+            tcp_test(reader, writer, item, mode, seconds).await;
+            return;
+        }
+        ["UDP", item, count] => {
+            let expected = count.parse::<u64>().unwrap_or(0).min(UDP_MAX_PACKETS);
+            UDP_SESSIONS.lock().unwrap_or_else(|e| e.into_inner()).insert(item.to_string(), UdpSession {
+                expected,
+                received: vec![false; expected as usize],
+                last_transit_us: None,
+                jitter_us: 0.0,
+                started: Instant::now(),
+            });
+            "OK\n".to_string()
+        }
+        ["RESULT", item, rest @ ..] => {
+            finish_udp_test(item);
+            let mut results = NET_TEST_RESULTS.lock().unwrap_or_else(|e| e.into_inner());
+            match results.get_mut(*item) {
+                Some(result) => {
+                    // 板卡端下载测得的字节数和时间
+                    if let [bytes, micros] = rest {
+                        if let (Ok(bytes), Ok(micros)) = (bytes.parse::<u64>(), micros.parse::<u64>()) {
+                            result.client_mbps = Some(rate_mbps(bytes, Duration::from_micros(micros)));
+                        }
+                    }
+                    result.to_result_line()
+                }
+                None => format!("@@RESULT {}\n", serde_json::json!({
+                    "item": item,
+                    "status": "fail",
+                    "defects": [format!("{}_NET_TEST", defect_prefix(item))],
+                    "message": "没有测试结果",
+                })),
+            }
+        }
+        _ => "ERROR 未知命令\n".to_string(),
+    };
+    let _ = writer.write_all(reply.as_bytes()).await;
+    let _ = writer.shutdown().await;
+}
+
+// TCP吞吐测试：在指定时间内接收和/或发送数据，到时关闭连接
+async fn tcp_test(mut reader: BufReader<tokio::net::tcp::OwnedReadHalf>, mut writer: tokio::net::tcp::OwnedWriteHalf, item: &str, mode: &str, seconds: u64) {
+    let started = Instant::now();
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(seconds);
+    let receives = mode != "download";
+    let sends = mode != "upload";
+
+    let receive = async {
+        let mut total = 0u64;
+        let mut buf = vec![0u8; STREAM_CHUNK_SIZE as usize];
+        if receives {
+            loop {
+                match timeout_at(deadline, reader.read(&mut buf)).await {
+                    Ok(Ok(n)) if n > 0 => total += n as u64,
+                    _ => break,
+                }
+            }
+        }
+        total
+    };
+    let send = async {
+        let mut total = 0u64;
+        // 随机数据只生成一块，循环发送
+        let mut block = vec![0u8; STREAM_CHUNK_SIZE as usize];
+        StdRng::from_entropy().fill_bytes(&mut block);
+        if sends {
+            while let Ok(Ok(())) = timeout_at(deadline, writer.write_all(&block)).await {
+                total += block.len() as u64;
+            }
+        }
+        total
+    };
+    let (rx_bytes, tx_bytes) = tokio::join!(receive, send);
+    let elapsed = started.elapsed();
+    drop(writer);
+
+    let mut result = NetTestResult {
+        item: item.to_string(),
+        protocol: "tcp".to_string(),
+        mode: mode.to_string(),
+        millis: elapsed.as_millis() as u64,
+        rx_bytes,
+        tx_bytes,
+        rx_mbps: rate_mbps(rx_bytes, elapsed),
+        tx_mbps: rate_mbps(tx_bytes, elapsed),
+        passed: true,
+        ..Default::default()
+    };
+    if receives && result.rx_mbps < speed_threshold(item, "up") {
+        result.passed = false;
+        result.defects.push(format!("{}_UPLOAD_SPEED", defect_prefix(item)));
+    }
+    if sends && result.tx_mbps < speed_threshold(item, "down") {
+        result.passed = false;
+        result.defects.push(format!("{}_DOWNLOAD_SPEED", defect_prefix(item)));
+    }
+    log(&format!("✅ {} TCP {} 完成: 接收 {:.1} Mbps, 发送 {:.1} Mbps", item, mode, result.rx_mbps, result.tx_mbps));
+    NET_TEST_RESULTS.lock().unwrap_or_else(|e| e.into_inner()).insert(item.to_string(), result);
+}
+
+// 接收UDP测试包，包内容为文本 "<项目> <序号> <发送时间微秒>"
+async fn udp_receiver(udp: UdpSocket) {
+    let mut buf = vec![0u8; 2048];
+    loop {
+        let n = match udp.recv_from(&mut buf).await {
+            Ok((n, _)) => n,
+            Err(e) => {
+                log(&format!("❌ 接收UDP包失败: {}", e));
+                continue;
+            }
+        };
+        let arrival_us = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|now| now.as_micros() as i64).unwrap_or_default();
+        let text = String::from_utf8_lossy(&buf[..n]);
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let [item, seq, sent_us] = fields.as_slice() else { continue };
+        let (Ok(seq), Ok(sent_us)) = (seq.parse::<usize>(), sent_us.parse::<i64>()) else { continue };
+
+        let mut sessions = UDP_SESSIONS.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(session) = sessions.get_mut(*item) else { continue };
+        if seq >= session.received.len() || session.received[seq] {
+            continue;
+        }
+        session.received[seq] = true;
+        // 两台主机时钟不同步，只使用传输时间的差值
+        let transit_us = arrival_us - sent_us;
+        if let Some(last) = session.last_transit_us {
+            let delta = (transit_us - last).abs() as f64;
+            session.jitter_us += (delta - session.jitter_us) / 16.0;
+        }
+        session.last_transit_us = Some(transit_us);
+    }
+}
+
+// 结束UDP测试，计算丢包率和抖动并保存结果
+fn finish_udp_test(item: &str) {
+    let Some(session) = UDP_SESSIONS.lock().unwrap_or_else(|e| e.into_inner()).remove(item) else { return };
+    let received = session.received.iter().filter(|received| **received).count() as u64;
+    let loss_percent = if session.expected == 0 {
+        100.0
+    } else {
+        (session.expected - received) as f64 * 100.0 / session.expected as f64
+    };
+    let jitter_ms = session.jitter_us / 1000.0;
+    let max_loss = get_config_str("testing", "udp_max_loss").and_then(|value| value.parse().ok()).unwrap_or(1.0);
+    let max_jitter = get_config_str("testing", "udp_max_jitter_ms").and_then(|value| value.parse().ok()).unwrap_or(5.0);
+
+    let mut result = NetTestResult {
+        item: item.to_string(),
+        protocol: "udp".to_string(),
+        mode: "loss".to_string(),
+        millis: session.started.elapsed().as_millis() as u64,
+        packets_expected: session.expected,
+        packets_received: received,
+        loss_percent: Some(loss_percent),
+        jitter_ms: Some(jitter_ms),
+        passed: true,
+        ..Default::default()
+    };
+    if loss_percent > max_loss {
+        result.passed = false;
+        result.defects.push(format!("{}_UDP_LOSS", defect_prefix(item)));
+    }
+    if jitter_ms > max_jitter {
+        result.passed = false;
+        result.defects.push(format!("{}_UDP_JITTER", defect_prefix(item)));
+    }
+    log(&format!("✅ {} UDP 完成: 收到 {}/{}, 丢包 {:.2}%, 抖动 {:.3} ms", item, received, session.expected, loss_percent, jitter_ms));
+    NET_TEST_RESULTS.lock().unwrap_or_else(|e| e.into_inner()).insert(item.to_string(), result);
+}
+
+// 取出测试项目最后一次的网络测试结果，取出后清除
+pub fn take_net_test_result(item: &str) -> Option<NetTestResult> {
+    NET_TEST_RESULTS.lock().unwrap_or_else(|e| e.into_inner()).remove(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_without_header_sends_full_file() {
+        assert_eq!(parse_range(None, 100), RangeRequest::Full);
+        assert_eq!(parse_range(Some("items=0-10"), 100), RangeRequest::Full);
+        assert_eq!(parse_range(Some("bytes=abc-"), 100), RangeRequest::Full);
+    }
+
+    #[test]
+    fn parse_range_start_end() {
+        assert_eq!(parse_range(Some("bytes=0-9"), 100), RangeRequest::Partial(0, 9));
+        assert_eq!(parse_range(Some(" bytes=10 - 19 "), 100), RangeRequest::Partial(10, 19));
+        // 结束位置超出文件大小时截断到最后一个字节
+        assert_eq!(parse_range(Some("bytes=90-200"), 100), RangeRequest::Partial(90, 99));
+        // 结束位置小于开始位置的范围无效，发送完整文件
+        assert_eq!(parse_range(Some("bytes=20-10"), 100), RangeRequest::Full);
+    }
+
+    #[test]
+    fn parse_range_open_end() {
+        assert_eq!(parse_range(Some("bytes=40-"), 100), RangeRequest::Partial(40, 99));
+        assert_eq!(parse_range(Some("bytes=99-"), 100), RangeRequest::Partial(99, 99));
+    }
+
+    #[test]
+    fn parse_range_suffix() {
+        assert_eq!(parse_range(Some("bytes=-10"), 100), RangeRequest::Partial(90, 99));
+        // 后缀长度超过文件大小时发送整个文件
+        assert_eq!(parse_range(Some("bytes=-500"), 100), RangeRequest::Partial(0, 99));
+        assert_eq!(parse_range(Some("bytes=-0"), 100), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn parse_range_start_beyond_size() {
+        assert_eq!(parse_range(Some("bytes=100-"), 100), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=100-150"), 100), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn parse_range_empty_file() {
+        assert_eq!(parse_range(Some("bytes=0-"), 0), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range(Some("bytes=-10"), 0), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range(None, 0), RangeRequest::Full);
+    }
+
+    #[test]
+    fn parse_range_multiple_ranges_sends_full_file() {
+        assert_eq!(parse_range(Some("bytes=0-9,20-29"), 100), RangeRequest::Full);
+        assert_eq!(parse_range(Some("bytes=-10, 0-5"), 100), RangeRequest::Full);
+    }
+}
@@ -1,14 +1,73 @@
-use ssh2::Session;
-use std::io::Read;
-use std::net::TcpStream;
+// SSH命令执行：到待测板卡保持少量长连接，step2的各个测试任务在同一连接上各自打开通道并行执行
+// 连接建立后一直处于非阻塞模式（阻塞模式是整个会话的设置），各通道的读写遇到EAGAIN时稍后重试，
+// 一条命令等待输出不会卡住同一连接上的其他命令；一个连接的通道借满后才建立新连接
+// 连接断开（如板卡重启）时不再借出该连接，已借出的通道归还后关闭，之后的命令自动重连
+// 远程命令在独立的进程组中运行，超时或取消时只关闭该命令的通道，并在同一连接上另开通道结束整个进程组
+use ssh2::{Channel, ErrorCode, File as SftpFile, Session, Sftp};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
 use tokio::task;
+use crate::function::save::get_credentials;
+use crate::function::serial::shell::{serial_shell_cancellable, ShellError};
+use crate::function::transcript::Transcript;
 
 // const HOST: &str = "192.168.1.109";
 // const HOST: &str = "192.168.1.15";
 // const HOST: &str = "192.168.1.19";
 const HOST: &str = "172.168.100.2";  // 静态IP
-const USER: &str = "root";
-const PASSWORD: &str = "sipeed"; // 密码认证
+
+const MAX_SESSIONS: usize = 4;              // 最多同时保持的连接数
+const CHANNELS_PER_SESSION: usize = 4;      // 每个连接最多同时执行的命令数，每条命令另需一个结束进程组或校验用的辅助通道，不超过OpenSSH默认的MaxSessions(10)
+const DEFAULT_TIMEOUT_MS: u64 = 30_000;     // 没有指定时单条命令的超时时间，长时间运行的脚本需自行指定
+const CONNECT_TIMEOUT_MS: u64 = 5_000;      // TCP连接超时时间
+const OPEN_TIMEOUT_MS: u32 = 10_000;        // 握手、认证和打开、关闭通道的超时时间，命令执行期间不限时
+const KEEPALIVE_INTERVAL_S: u32 = 15;       // 空闲连接的保活间隔
+const IDLE_CHECK_MS: u64 = 5_000;           // 空闲超过该时间的连接在使用前先检查是否可用
+const STREAM_POLL_MS: u64 = 20;             // 读取输出时没有新数据的等待间隔
+const RETRY_POLL_MS: u64 = 1;               // 非阻塞操作返回EAGAIN后重试的间隔
+const LIBSSH2_ERROR_EAGAIN: i32 = -37;      // libssh2非阻塞模式下表示需要稍后重试的错误码
+const KILL_GRACE_S: u32 = 1;                // 结束远程进程组时SIGTERM后等待的时间，之后SIGKILL
+const SFTP_CHUNK_SIZE: usize = 64 * 1024;   // SFTP每次读写的大小
+const SFTP_OP_TIMEOUT_MS: u64 = 30_000;     // SFTP单次操作（创建、打开、查询、改名）的超时时间
+const HASH_TIMEOUT_MS: u64 = 120_000;       // 板卡端计算SHA-256的超时时间
+
+// 网口不可用时允许通过串口执行的命令（前缀）和最长执行时间，其余命令直接返回失败
+// 串口同一时间只能执行一条命令，常驻测试服务和固件更新不通过串口执行
+const SERIAL_ALLOWED: [(&str, u64); 21] = [
+    ("ls /root/test.tar", 5_000),
+    ("rm -rf /root/NanoKVM_Pro_Testing", 10_000),
+    ("tar -xf /root/test.tar ", 60_000),
+    ("chmod -R +x /root/NanoKVM_Pro_Testing", 10_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/01_test_hardware.sh", 30_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/02_rm_tested.sh", 10_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/03_test_emmc.sh", 330_000),     // badblocks最多300秒
+    ("/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh get_hdmi_test_status", 10_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh set_hdmi_test_ok", 10_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh io", 30_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh version ", 30_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh edid", 30_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/06_usb_test.sh", 30_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/08_wifi_test.sh connect ", 60_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/09_panel_test.sh touch ", 90_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/09_panel_test.sh rotary ", 90_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/10_atx_test.sh ", 30_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/11_io_test.sh ", 30_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/12_tf_test.sh", 30_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/13_uart_test.sh", 30_000),
+    ("/root/NanoKVM_Pro_Testing/test_sh/14_test_end.sh", 30_000),
+];
 
 // 日志控制：false=关闭日志，true=开启日志
 const LOG_ENABLE: bool = true;
@@ -20,57 +79,720 @@ fn log(msg: &str) {
     }
 }
 
-pub async fn ssh_execute_command(command: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+// 网口不可用时改为通过串口控制台执行命令
+static SERIAL_FALLBACK: AtomicBool = AtomicBool::new(false);
+// 远程进程组pid文件序号
+static COMMAND_SEQ: AtomicU64 = AtomicU64::new(1);
+
+// 设置是否通过串口执行命令
+pub fn set_serial_fallback(enable: bool) {
+    if SERIAL_FALLBACK.swap(enable, Ordering::SeqCst) != enable {
+        log(&format!("命令通道切换为: {}", if enable { "串口" } else { "SSH" }));
+    }
+}
+
+// 当前是否通过串口执行命令
+pub fn is_serial_fallback() -> bool {
+    SERIAL_FALLBACK.load(Ordering::SeqCst)
+}
+
+// 连接池状态，供前端显示
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SshHealth {
+    pub connected: bool,            // 最近一次连接或执行是否成功
+    pub open: usize,                // 当前打开的连接数
+    pub idle: usize,                // 没有命令在执行的连接数
+    pub busy: usize,                // 有命令在执行的连接数
+    pub channels: usize,            // 正在执行的命令数，即借出的通道数
+    pub connects: u64,              // 累计建立的连接数
+    pub reconnects: u64,            // 因连接失效而重连的次数
+    pub failures: u64,              // 累计连接失败次数
+    pub last_error: Option<String>, // 最近一次连接错误
+    pub last_ok_ms: Option<u64>,    // 距离最近一次成功执行的毫秒数
+}
+
+// 输出来源
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SshStream {
+    Stdout,
+    Stderr,
+}
+
+impl SshStream {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SshStream::Stdout => "stdout",
+            SshStream::Stderr => "stderr",
+        }
+    }
+}
+
+// 流式输出的一行（不含换行符）
+#[derive(Debug, Clone, Serialize)]
+pub struct SshLine {
+    pub stream: SshStream,
+    pub line: String,
+}
+
+// SSH命令失败原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum SshError {
+    Connect(String),            // 无法连接，或执行过程中连接中断
+    Auth(String),               // 用户名或密码错误
+    Timeout(u64),               // 超过指定时间（毫秒）没有结束，远程进程组已结束
+    NonZeroExit(i32, String),   // 退出码非0，内容为退出码和输出
+    Cancelled,                  // 被调用方取消，远程进程组已结束
+    Transfer(String),           // 文件传输失败，如本地文件无法读写或校验不一致
+}
+
+impl fmt::Display for SshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SshError::Connect(msg) => write!(f, "SSH 连接失败: {}", msg),
+            SshError::Auth(msg) => write!(f, "SSH 认证失败: {}", msg),
+            SshError::Timeout(timeout_ms) => write!(f, "命令执行超时: {}ms", timeout_ms),
+            SshError::NonZeroExit(code, output) => write!(f, "命令执行失败，退出状态: {}\n输出: {}", code, output),
+            SshError::Cancelled => write!(f, "命令已取消"),
+            SshError::Transfer(msg) => write!(f, "文件传输失败: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SshError {}
+
+// 取消句柄，克隆后交给其他任务，调用cancel结束正在执行的命令
+#[derive(Debug, Clone, Default)]
+pub struct SshCancel(Arc<AtomicBool>);
+
+impl SshCancel {
+    pub fn new() -> SshCancel {
+        SshCancel::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// 执行选项
+#[derive(Debug, Clone)]
+pub struct SshOptions {
+    pub timeout_ms: u64,                // 从借到通道开始计时，不包括排队等待空余通道的时间
+    pub cancel: Option<SshCancel>,
+}
+
+impl Default for SshOptions {
+    fn default() -> Self {
+        SshOptions { timeout_ms: DEFAULT_TIMEOUT_MS, cancel: None }
+    }
+}
+
+impl SshOptions {
+    pub fn timeout(timeout_ms: u64) -> SshOptions {
+        SshOptions { timeout_ms, cancel: None }
+    }
+
+    pub fn cancel_by(mut self, cancel: &SshCancel) -> SshOptions {
+        self.cancel = Some(cancel.clone());
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().map(|cancel| cancel.is_cancelled()).unwrap_or(false)
+    }
+}
+
+// 执行过程中断的原因
+enum Interrupt {
+    Broken(String),     // 连接中断
+    Transfer(String),   // 文件传输失败，连接仍然可用
+    Timeout,
+    Cancelled,
+}
+
+impl fmt::Display for Interrupt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Interrupt::Broken(msg) | Interrupt::Transfer(msg) => write!(f, "{}", msg),
+            Interrupt::Timeout => write!(f, "超时"),
+            Interrupt::Cancelled => write!(f, "已取消"),
+        }
+    }
+}
+
+impl From<std::io::Error> for Interrupt {
+    fn from(e: std::io::Error) -> Self {
+        Interrupt::Broken(e.to_string())
+    }
+}
+
+impl From<ssh2::Error> for Interrupt {
+    fn from(e: ssh2::Error) -> Self {
+        Interrupt::Broken(e.to_string())
+    }
+}
+
+// 池中的连接，多条命令各自打开通道共用
+struct PooledSession {
+    id: u64,
+    session: Session,
+    generation: u64,    // 所属的连接池代数，板卡更换后旧连接作废
+    channels: usize,    // 借出的通道数
+    broken: bool,       // 已失效，不再借出，借出的通道全部归还后关闭
+    last_used: Instant,
+}
+
+impl PooledSession {
+    fn usable(&self, generation: u64) -> bool {
+        !self.broken && self.generation == generation
+    }
+}
+
+// 连接池
+struct SshPool {
+    sessions: Vec<PooledSession>,
+    next_id: u64,
+    generation: u64,
+    connect_error: Option<SshError>,    // 最近一次建立连接失败的原因
+    health: SshHealth,
+    last_ok: Option<Instant>,
+}
+
+// 从池中借出的一个通道名额
+struct Lease {
+    id: u64,            // 所属连接
+    session: Session,
+    reused: bool,       // 是否为已有的连接，打开通道失败时可以换连接重试
+}
+
+lazy_static! {
+    static ref SSH_POOL: Mutex<SshPool> = Mutex::new(SshPool {
+        sessions: Vec::new(),
+        next_id: 0,
+        generation: 0,
+        connect_error: None,
+        health: SshHealth::default(),
+        last_ok: None,
+    });
+    static ref SSH_CONNECT: Mutex<()> = Mutex::new(());     // 同一时间只建立一个新连接，其他任务等它建好后共用
+    static ref SSH_PERMITS: Semaphore = Semaphore::new(MAX_SESSIONS * CHANNELS_PER_SESSION);    // 限制同时执行的命令数
+}
+
+// 连接阶段的错误
+fn connect_error(e: impl fmt::Display) -> SshError {
+    SshError::Connect(e.to_string())
+}
+
+// 建立新连接并认证，完成后切换为非阻塞模式
+fn connect() -> Result<Session, SshError> {
+    // 建立TCP连接
+    let addr = format!("{}:22", HOST).to_socket_addrs().map_err(connect_error)?
+        .next().ok_or_else(|| connect_error("地址解析失败"))?;
+    let tcp = TcpStream::connect_timeout(&addr, Duration::from_millis(CONNECT_TIMEOUT_MS)).map_err(connect_error)?;
+
+    // 创建SSH会话
+    let mut session = Session::new().map_err(connect_error)?;
+    session.set_tcp_stream(tcp);
+    session.set_timeout(OPEN_TIMEOUT_MS);
+    session.handshake().map_err(connect_error)?;
+
+    // 使用密码认证，用户名和密码与串口登录共用配置
+    let credentials = get_credentials();
+    session.userauth_password(&credentials.username, &credentials.password)
+        .map_err(|e| SshError::Auth(e.to_string()))?;
+
+    if !session.authenticated() {
+        return Err(SshError::Auth(format!("用户 {} 未通过认证", credentials.username)));
+    }
+    session.set_keepalive(true, KEEPALIVE_INTERVAL_S);
+    // 之后所有操作都以非阻塞方式重试；超时设置只对释放SFTP句柄时ssh2内部的阻塞调用生效
+    session.set_blocking(false);
+    Ok(session)
+}
+
+// 在池中找一个有空余通道的连接并借出一个通道，返回借出的通道和该连接是否空闲较久
+fn take_slot() -> Option<(Lease, bool)> {
+    let mut pool = SSH_POOL.lock().unwrap();
+    let generation = pool.generation;
+    let pooled = pool.sessions.iter_mut()
+        .find(|pooled| pooled.usable(generation) && pooled.channels < CHANNELS_PER_SESSION)?;
+    let stale = pooled.channels == 0 && pooled.last_used.elapsed() >= Duration::from_millis(IDLE_CHECK_MS);
+    pooled.channels += 1;
+    Some((Lease { id: pooled.id, session: pooled.session.clone(), reused: true }, stale))
+}
+
+// 借出一个通道：优先使用已有连接上的空余通道，都借满了再建立新连接
+fn checkout() -> Result<Lease, SshError> {
+    loop {
+        let (lease, stale) = match take_slot() {
+            Some(slot) => slot,
+            None => {
+                let failures = SSH_POOL.lock().unwrap().health.failures;
+                let _connecting = SSH_CONNECT.lock().unwrap_or_else(|e| e.into_inner());
+                // 等待期间其他任务可能已经建好新连接，或者已经连接失败（板卡多半不可达，不再重复尝试）
+                match take_slot() {
+                    Some(slot) => slot,
+                    None => {
+                        let pool = SSH_POOL.lock().unwrap();
+                        if pool.health.failures != failures {
+                            if let Some(e) = &pool.connect_error {
+                                return Err(e.clone());
+                            }
+                        }
+                        drop(pool);
+                        return open_session();
+                    }
+                }
+            }
+        };
+        // 空闲较久的连接发送一次保活，失败说明板卡已经断开或重启
+        if stale {
+            if let Err(e) = lease.session.keepalive_send() {
+                if !would_block(&e) {
+                    log(&format!("空闲连接已失效: {}", e));
+                    checkin(lease, Some(e.to_string()));
+                    continue;
+                }
+            }
+        }
+        return Ok(lease);
+    }
+}
+
+// 建立新连接加入池中，并借出其中一个通道
+fn open_session() -> Result<Lease, SshError> {
+    let generation = SSH_POOL.lock().unwrap().generation;
+    match connect() {
+        Ok(session) => {
+            let mut pool = SSH_POOL.lock().unwrap();
+            let id = pool.next_id;
+            pool.next_id += 1;
+            pool.sessions.push(PooledSession {
+                id,
+                session: session.clone(),
+                generation,
+                channels: 1,
+                broken: false,
+                last_used: Instant::now(),
+            });
+            pool.health.connects += 1;
+            log(&format!("✅ SSH 连接成功！当前连接数: {}", pool.sessions.len()));
+            Ok(Lease { id, session, reused: false })
+        }
+        Err(e) => {
+            let mut pool = SSH_POOL.lock().unwrap();
+            pool.health.connected = false;
+            pool.health.failures += 1;
+            pool.health.last_error = Some(e.to_string());
+            pool.connect_error = Some(e.clone());
+            Err(e)
+        }
+    }
+}
+
+// 归还借出的通道；error不为空表示连接已失效，不再借出，最后一个通道归还后关闭连接
+fn checkin(lease: Lease, error: Option<String>) {
+    let closing = {
+        let mut pool = SSH_POOL.lock().unwrap();
+        let generation = pool.generation;
+        let Some(index) = pool.sessions.iter().position(|pooled| pooled.id == lease.id) else { return };
+        let newly_broken = error.is_some() && !pool.sessions[index].broken;
+        let pooled = &mut pool.sessions[index];
+        pooled.channels = pooled.channels.saturating_sub(1);
+        pooled.last_used = Instant::now();
+        pooled.broken |= error.is_some();
+        let finished = pooled.channels == 0 && !pooled.usable(generation);
+
+        match error {
+            Some(error) if newly_broken => {
+                pool.health.connected = false;
+                pool.health.reconnects += 1;
+                pool.health.last_error = Some(error);
+            }
+            Some(_) => {}
+            None => {
+                pool.health.connected = true;
+                pool.last_ok = Some(Instant::now());
+            }
+        }
+        if finished { Some(pool.sessions.remove(index)) } else { None }
+    };
+    if let Some(pooled) = closing {
+        close_session(pooled.session);
+    }
+}
+
+// 关闭已经没有借出通道的连接
+fn close_session(session: Session) {
+    // 对方已断开时避免发送断开消息阻塞太久
+    session.set_blocking(true);
+    session.set_timeout(1000);
+    let _ = session.disconnect(None, "closing", None);
+}
+
+// libssh2非阻塞操作是否需要稍后重试
+fn would_block(e: &ssh2::Error) -> bool {
+    e.code() == ErrorCode::Session(LIBSSH2_ERROR_EAGAIN)
+}
+
+// 非阻塞模式下重复执行一个libssh2操作直到完成，返回操作本身的结果
+// 超过deadline仍未完成说明连接已经没有响应
+fn retry<T>(what: &str, deadline: Instant, mut op: impl FnMut() -> Result<T, ssh2::Error>) -> Result<Result<T, ssh2::Error>, Interrupt> {
+    loop {
+        match op() {
+            Err(e) if would_block(&e) => {
+                if Instant::now() >= deadline {
+                    return Err(Interrupt::Broken(format!("{}超时", what)));
+                }
+                std::thread::sleep(Duration::from_millis(RETRY_POLL_MS));
+            }
+            result => return Ok(result),
+        }
+    }
+}
+
+// 打开、关闭通道等协议操作的截止时间
+fn open_deadline() -> Instant {
+    Instant::now() + Duration::from_millis(u64::from(OPEN_TIMEOUT_MS))
+}
+
+// 打开一个通道，失败说明连接已经不可用
+fn open_channel(session: &Session) -> Result<Channel, Interrupt> {
+    Ok(retry("打开通道", open_deadline(), || session.channel_session())??)
+}
+
+// 关闭通道并等待对方确认，之后释放通道不会留下未关闭的远端通道
+fn close_channel(channel: &mut Channel) -> Result<(), Interrupt> {
+    let deadline = open_deadline();
+    retry("关闭通道", deadline, || channel.close())??;
+    retry("关闭通道", deadline, || channel.wait_close())??;
+    Ok(())
+}
+
+// 把新收到的数据按行推送，返回剩余不足一行的部分
+fn send_lines(stream: SshStream, pending: &mut Vec<u8>, lines: &UnboundedSender<SshLine>) {
+    while let Some(newline) = pending.iter().position(|&b| b == b'\n') {
+        let line: Vec<u8> = pending.drain(..=newline).collect();
+        let line = String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']).to_string();
+        let _ = lines.send(SshLine { stream, line });
+    }
+}
+
+// 以非阻塞方式同时读取stdout和stderr，返回完整的stdout
+// 指定lines时逐行推送；超过deadline或被取消时中断
+fn read_output(channel: &mut Channel, lines: Option<&UnboundedSender<SshLine>>, deadline: Instant, options: &SshOptions) -> Result<Vec<u8>, Interrupt> {
+    let mut output = Vec::new();
+    let mut pending = [Vec::new(), Vec::new()];
+    let mut buf = [0u8; 4096];
+    loop {
+        let mut received = false;
+        for (index, stream) in [SshStream::Stdout, SshStream::Stderr].into_iter().enumerate() {
+            let read = match stream {
+                SshStream::Stdout => channel.read(&mut buf),
+                SshStream::Stderr => channel.stderr().read(&mut buf),
+            };
+            match read {
+                Ok(0) => {}
+                Ok(n) => {
+                    received = true;
+                    if stream == SshStream::Stdout {
+                        output.extend_from_slice(&buf[..n]);
+                    }
+                    if let Some(lines) = lines {
+                        pending[index].extend_from_slice(&buf[..n]);
+                        send_lines(stream, &mut pending[index], lines);
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if options.is_cancelled() {
+            return Err(Interrupt::Cancelled);
+        }
+        if Instant::now() >= deadline {
+            return Err(Interrupt::Timeout);
+        }
+        // 两路都没有新数据时，对方已结束输出则退出，否则稍后再读
+        if !received {
+            if channel.eof() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(STREAM_POLL_MS));
+        }
+    }
+    // 最后不以换行结尾的内容
+    if let Some(lines) = lines {
+        for (index, stream) in [SshStream::Stdout, SshStream::Stderr].into_iter().enumerate() {
+            if !pending[index].is_empty() {
+                pending[index].push(b'\n');
+                send_lines(stream, &mut pending[index], lines);
+            }
+        }
+    }
+    Ok(output)
+}
+
+// 在独立进程组中运行命令，并把进程组号写入pid文件，退出码与原命令一致
+fn wrap_command(command: &str, pid_file: &str) -> String {
+    format!("setsid sh -c '{}' & echo $! > {pid}; wait $!; r=$?; rm -f {pid}; exit $r",
+        command.replace('\'', "'\\''"), pid = pid_file)
+}
+
+// 在同一连接上另开一个通道执行辅助命令并返回stdout，用于结束进程组和传输后的校验
+fn run_remote(session: &Session, command: &str, deadline: Instant, options: &SshOptions) -> Result<String, Interrupt> {
+    let mut channel = open_channel(session)?;
+    retry("执行命令", deadline, || channel.exec(command))??;
+    let output = read_output(&mut channel, None, deadline, options);
+    let closed = close_channel(&mut channel);
+    let output = output?;
+    closed?;
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
+
+// 超时或取消后结束远程进程组，先SIGTERM，仍未退出再SIGKILL
+fn kill_remote(session: &Session, pid_file: &str) {
+    let command = format!(
+        "p=$(cat {pid} 2>/dev/null); [ -n \"$p\" ] && {{ kill -TERM -- -$p 2>/dev/null; sleep {grace}; kill -KILL -- -$p 2>/dev/null; }}; rm -f {pid}",
+        pid = pid_file, grace = KILL_GRACE_S);
+    let deadline = open_deadline() + Duration::from_secs(u64::from(KILL_GRACE_S));
+    match run_remote(session, &command, deadline, &SshOptions::default()) {
+        Ok(_) => log(&format!("已结束远程进程组: {}", pid_file)),
+        Err(e) => log(&format!("结束远程进程组失败 {}: {}", pid_file, e)),
+    }
+}
+
+// 在通道上执行命令，返回退出状态和原始stdout；超时或取消时只关闭本命令的通道
+fn exec(session: &Session, mut channel: Channel, command: &str, lines: Option<&UnboundedSender<SshLine>>, deadline: Instant, options: &SshOptions) -> Result<(i32, Vec<u8>), Interrupt> {
+    let pid_file = format!("/tmp/ssh_cmd_{}_{}.pid", std::process::id(), COMMAND_SEQ.fetch_add(1, Ordering::SeqCst));
+    let wrapped = wrap_command(command, &pid_file);
+    retry("执行命令", open_deadline(), || channel.exec(&wrapped))??;
+
+    let output = match read_output(&mut channel, lines, deadline, options) {
+        Ok(output) => output,
+        Err(Interrupt::Broken(e)) => return Err(Interrupt::Broken(e)),
+        Err(interrupt) => {
+            let _ = close_channel(&mut channel);
+            kill_remote(session, &pid_file);
+            return Err(interrupt);
+        }
+    };
+
+    // 对方结束输出后关闭通道，退出状态在关闭前已经收到
+    close_channel(&mut channel)?;
+    let exit_status = channel.exit_status()?;
+    Ok((exit_status, output))
+}
+
+// 关闭全部连接，更换板卡时调用，正在执行命令的连接在命令结束后关闭
+pub fn reset_ssh_pool() {
+    let idle: Vec<PooledSession> = {
+        let mut pool = SSH_POOL.lock().unwrap();
+        pool.generation += 1;
+        pool.connect_error = None;
+        pool.health.connected = false;
+        pool.health.last_error = None;
+        pool.last_ok = None;
+        let (idle, busy) = std::mem::take(&mut pool.sessions).into_iter().partition(|pooled| pooled.channels == 0);
+        pool.sessions = busy;
+        idle
+    };
+    if !idle.is_empty() {
+        log(&format!("关闭 {} 个空闲连接", idle.len()));
+    }
+    for pooled in idle {
+        close_session(pooled.session);
+    }
+}
+
+// 连接池状态
+pub fn get_ssh_health() -> SshHealth {
+    let pool = SSH_POOL.lock().unwrap();
+    let mut health = pool.health.clone();
+    health.open = pool.sessions.len();
+    health.idle = pool.sessions.iter().filter(|pooled| pooled.channels == 0).count();
+    health.busy = health.open - health.idle;
+    health.channels = pool.sessions.iter().map(|pooled| pooled.channels).sum();
+    health.last_ok_ms = pool.last_ok.map(|at| at.elapsed().as_millis() as u64);
+    health
+}
+
+// 前端查询SSH连接状态
+#[tauri::command]
+pub fn ssh_health() -> SshHealth {
+    get_ssh_health()
+}
+
+// 等待取消，没有取消句柄时一直等待
+async fn wait_cancelled(options: &SshOptions) {
+    match &options.cancel {
+        Some(cancel) => while !cancel.is_cancelled() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        },
+        None => std::future::pending::<()>().await,
+    }
+}
+
+// 通过串口执行命令，只执行SERIAL_ALLOWED中的命令，超时取调用方和列表中较小的值，超时或取消时发送Ctrl-C
+async fn execute_serial(command: &str, lines: Option<UnboundedSender<SshLine>>, options: &SshOptions) -> Result<(i32, String), SshError> {
+    let Some(&(_, max_timeout_ms)) = SERIAL_ALLOWED.iter().find(|(prefix, _)| command.starts_with(prefix)) else {
+        log(&format!("以太网不可用，该命令不通过串口执行: {}", command));
+        return Err(SshError::Connect("以太网不可用，该命令不能通过串口执行".to_string()));
+    };
+    let timeout_ms = options.timeout_ms.min(max_timeout_ms);
+    log(&format!("通过串口执行命令({}ms): {}", timeout_ms, command));
+    // 串口输出无法区分stdout和stderr，全部作为stdout逐行推送
+    let on_line = |line: &str| {
+        if let Some(lines) = &lines {
+            let _ = lines.send(SshLine { stream: SshStream::Stdout, line: line.to_string() });
+        }
+    };
+    serial_shell_cancellable(command, timeout_ms, || options.is_cancelled(), on_line).await
+        .map_err(|e| match e {
+            ShellError::Timeout(_) => SshError::Timeout(timeout_ms),
+            ShellError::Cancelled => SshError::Cancelled,
+            ShellError::Exit(code, output) => SshError::NonZeroExit(code, output),
+        })
+}
+
+// 等待空余通道，同时执行的命令已满时排队，排队时间不计入命令超时
+async fn acquire_permit(options: &SshOptions) -> Result<SemaphorePermit<'static>, SshError> {
+    tokio::select! {
+        permit = SSH_PERMITS.acquire() => permit.map_err(|e| SshError::Connect(e.to_string())),
+        _ = wait_cancelled(options) => Err(SshError::Cancelled),
+    }
+}
+
+// 借用池中连接的一个通道完成一次操作：open打开通道或SFTP，run完成操作
+// open失败时该连接不再借出，借用的是已有连接时换一个连接重试；只有连接中断时才关闭连接
+fn with_session<C, T>(
+    what: &str,
+    options: &SshOptions,
+    open: impl Fn(&Session) -> Result<C, Interrupt>,
+    run: impl FnOnce(&Session, C) -> Result<T, Interrupt>,
+) -> Result<T, SshError> {
+    let (lease, opened) = loop {
+        let lease = checkout()?;
+        match open(&lease.session) {
+            Ok(opened) => break (lease, opened),
+            Err(e) => {
+                let reused = lease.reused;
+                checkin(lease, Some(e.to_string()));
+                // 复用的连接可能在空闲期间失效（如板卡重启），换一个连接重试
+                if !reused {
+                    return Err(SshError::Connect(e.to_string()));
+                }
+                log(&format!("连接失效，重新连接: {}", e));
+            }
+        }
+    };
+    log(&format!("执行: {}", what));
+    match run(&lease.session, opened) {
+        Ok(result) => {
+            checkin(lease, None);
+            Ok(result)
+        }
+        Err(Interrupt::Broken(e)) => {
+            checkin(lease, Some(e.clone()));
+            Err(SshError::Connect(format!("执行过程中连接中断: {}", e)))
+        }
+        Err(Interrupt::Transfer(e)) => {
+            // 本地文件或校验错误，连接仍然可用
+            checkin(lease, None);
+            Err(SshError::Transfer(e))
+        }
+        Err(Interrupt::Timeout) => {
+            log(&format!("超时 {}ms: {}", options.timeout_ms, what));
+            checkin(lease, None);
+            Err(SshError::Timeout(options.timeout_ms))
+        }
+        Err(Interrupt::Cancelled) => {
+            log(&format!("已取消: {}", what));
+            checkin(lease, None);
+            Err(SshError::Cancelled)
+        }
+    }
+}
+
+// 执行命令，返回退出状态和stdout；连接失败、超时或取消返回错误
+async fn execute(command: &str, lines: Option<UnboundedSender<SshLine>>, options: &SshOptions) -> Result<(i32, String), SshError> {
+    if is_serial_fallback() {
+        return execute_serial(command, lines, options).await;
+    }
+
+    let _permit = acquire_permit(options).await?;
+    let deadline = Instant::now() + Duration::from_millis(options.timeout_ms);
     let command = command.to_string();
-    
+    let options = options.clone();
+
     task::spawn_blocking(move || {
-        // 建立TCP连接
-        let tcp = TcpStream::connect(format!("{}:22", HOST))?;
-        
-        // 创建SSH会话
-        let mut session = Session::new()?;
-        session.set_tcp_stream(tcp);
-        session.handshake()?;
-        
-        // 使用密码认证
-        session.userauth_password(USER, PASSWORD)?;
-        
-        if !session.authenticated() {
-            return Err("SSH 认证失败".to_string().into());
-        }
-        
-        log(&format!("✅ SSH 连接成功！执行命令: {}", command));
-        
-        // 执行命令
-        let mut channel = session.channel_session()?;
-        channel.exec(&command)?;
-        
-        // 读取命令输出
-        let mut output = Vec::new();
-        channel.read_to_end(&mut output)?;
-        
-        // 等待命令执行完成并获取退出状态
-        let exit_status = channel.exit_status()?;
-        
-        // 关闭通道
-        channel.send_eof()?;
-        channel.wait_eof()?;
-        channel.wait_close()?;
-        
+        let (exit_status, output) = with_session(&command, &options, open_channel, |session, channel| {
+            exec(session, channel, &command, lines.as_ref(), deadline, &options)
+        })?;
+
         // 转换输出为字符串
-        let output_str = String::from_utf8(output)?;
-        
-        // 返回结果
-        if exit_status == 0 {
-            Ok(output_str)
-        } else {
-            Err(format!("命令执行失败，退出状态: {}\n输出: {}", exit_status, output_str).into())
+        Ok((exit_status, String::from_utf8_lossy(&output).to_string()))
+    }).await.map_err(|e| SshError::Connect(e.to_string()))?
+}
+
+// 退出码非0时转换为错误
+fn check_exit((exit_status, output_str): (i32, String)) -> Result<String, SshError> {
+    if exit_status == 0 {
+        Ok(output_str)
+    } else {
+        Err(SshError::NonZeroExit(exit_status, output_str))
+    }
+}
+
+// 使用默认超时执行命令
+pub async fn ssh_execute_command(command: &str) -> Result<String, SshError> {
+    ssh_execute_command_with(command, &SshOptions::default()).await
+}
+
+// 指定超时和取消句柄执行命令
+pub async fn ssh_execute_command_with(command: &str, options: &SshOptions) -> Result<String, SshError> {
+    check_exit(execute(command, None, options).await?)
+}
+
+// 流式执行命令：输出逐行以 "ssh-output" 事件推送给前端，并标记测试项目，完整输出保存到板卡记录
+// 返回值与ssh_execute_command相同
+pub async fn ssh_execute_command_stream(app_handle: &AppHandle, item: &str, command: &str, options: &SshOptions) -> Result<String, SshError> {
+    let (sender, mut receiver) = unbounded_channel();
+    let mut transcript = Transcript::start(item, command);
+    let mut emit_line = |line: SshLine| {
+        transcript.push(line.stream.as_str(), &line.line);
+        if let Err(e) = app_handle.emit("ssh-output", serde_json::json!({
+            "item": item,
+            "stream": line.stream,
+            "line": line.line,
+        })) {
+            log(&format!("推送命令输出失败: {}", e));
+        }
+    };
+
+    let running = execute(command, Some(sender), options);
+    tokio::pin!(running);
+    let result = loop {
+        tokio::select! {
+            result = &mut running => break result,
+            Some(line) = receiver.recv() => emit_line(line),
         }
-    }).await?
+    };
+    // 命令结束后剩余的输出
+    while let Ok(line) = receiver.try_recv() {
+        emit_line(line);
+    }
+    transcript.finish(result.as_ref().ok().map(|(exit_status, _)| *exit_status));
+    check_exit(result?)
 }
 
 // 执行命令判断是否成功，返回结果包含是否成功和命令输出
-pub async fn ssh_execute_command_check_success(command: &str, success_keyword: &str) -> Result<(bool, String), Box<dyn std::error::Error + Send + Sync>> {
+pub async fn ssh_execute_command_check_success(command: &str, success_keyword: &str) -> Result<(bool, String), SshError> {
     match ssh_execute_command(command).await {
         Ok(output) => {
             let success = output.contains(success_keyword);
@@ -88,6 +810,176 @@ pub async fn ssh_execute_command_check_success(command: &str, success_keyword: &
     }
 }
 
+// 打开SFTP会话，失败说明连接已经不可用
+// 用完释放时ssh2内部临时切换为阻塞模式关闭会话，只占用连接一个往返的时间
+fn open_sftp(session: &Session) -> Result<Sftp, Interrupt> {
+    Ok(retry("打开SFTP", open_deadline(), || session.sftp())??)
+}
+
+// SFTP单次操作的截止时间
+fn sftp_deadline() -> Instant {
+    Instant::now() + Duration::from_millis(SFTP_OP_TIMEOUT_MS)
+}
+
+// 文件操作错误，连接仍然可用
+fn transfer_error(e: impl fmt::Display) -> Interrupt {
+    Interrupt::Transfer(e.to_string())
+}
+
+// 传输过程中检查超时和取消
+fn check_interrupt(deadline: Instant, options: &SshOptions) -> Result<(), Interrupt> {
+    if options.is_cancelled() {
+        return Err(Interrupt::Cancelled);
+    }
+    if Instant::now() >= deadline {
+        return Err(Interrupt::Timeout);
+    }
+    Ok(())
+}
+
+// 非阻塞写入远程文件，EAGAIN时用同一段数据重试
+fn write_remote(target: &mut SftpFile, data: &[u8], deadline: Instant, options: &SshOptions) -> Result<(), Interrupt> {
+    let mut written = 0;
+    while written < data.len() {
+        match target.write(&data[written..]) {
+            Ok(0) => return Err(Interrupt::Broken("写入远程文件中断".to_string())),
+            Ok(n) => written += n,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                check_interrupt(deadline, options)?;
+                std::thread::sleep(Duration::from_millis(RETRY_POLL_MS));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+// 非阻塞读取远程文件，返回0表示读完
+fn read_remote(source: &mut SftpFile, buf: &mut [u8], deadline: Instant, options: &SshOptions) -> Result<usize, Interrupt> {
+    loop {
+        match source.read(buf) {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                check_interrupt(deadline, options)?;
+                std::thread::sleep(Duration::from_millis(RETRY_POLL_MS));
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+// 上传本地文件：先写入临时文件，校验大小和SHA-256一致后改名，返回文件大小
+fn sftp_upload(session: &Session, sftp: Sftp, local: &Path, remote: &str, deadline: Instant, options: &SshOptions) -> Result<u64, Interrupt> {
+    let mut source = fs::File::open(local).map_err(|e| transfer_error(format!("{}: {}", local.display(), e)))?;
+    let part = format!("{}.part", remote);
+    let mut target = retry("创建远程文件", sftp_deadline(), || sftp.create(Path::new(&part)))?.map_err(transfer_error)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; SFTP_CHUNK_SIZE];
+    let mut size = 0u64;
+    loop {
+        check_interrupt(deadline, options)?;
+        let n = source.read(&mut buf).map_err(transfer_error)?;
+        if n == 0 {
+            break;
+        }
+        write_remote(&mut target, &buf[..n], deadline, options)?;
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+    // 关闭远程文件，等待板卡确认数据全部写入
+    retry("关闭远程文件", sftp_deadline(), || target.close())?.map_err(transfer_error)?;
+    let local_hash = format!("{:x}", hasher.finalize());
+
+    // 校验大小和SHA-256
+    let remote_size = retry("查询远程文件", sftp_deadline(), || sftp.stat(Path::new(&part)))?.map_err(transfer_error)?.size.unwrap_or(0);
+    if remote_size != size {
+        return Err(transfer_error(format!("大小不一致，本地 {} 板卡 {}", size, remote_size)));
+    }
+    let hash_deadline = Instant::now() + Duration::from_millis(HASH_TIMEOUT_MS);
+    let remote_hash = run_remote(session, &format!("sha256sum '{}' | cut -d' ' -f1", part), hash_deadline, options)?;
+    if remote_hash.trim() != local_hash {
+        return Err(transfer_error(format!("SHA-256不一致，本地 {} 板卡 {}", local_hash, remote_hash.trim())));
+    }
+
+    let _ = retry("删除远程文件", sftp_deadline(), || sftp.unlink(Path::new(remote)))?;
+    retry("重命名远程文件", sftp_deadline(), || sftp.rename(Path::new(&part), Path::new(remote), None))?.map_err(transfer_error)?;
+    log(&format!("上传完成 {} -> {}, {} 字节, SHA-256 {}", local.display(), remote, size, local_hash));
+    Ok(size)
+}
+
+// 下载远程文件或目录（递归），把下载的本地文件路径加入files
+fn sftp_download(sftp: &Sftp, remote: &Path, local: &Path, deadline: Instant, options: &SshOptions, files: &mut Vec<PathBuf>) -> Result<(), Interrupt> {
+    let stat = retry("查询远程文件", sftp_deadline(), || sftp.stat(remote))?
+        .map_err(|e| transfer_error(format!("{}: {}", remote.display(), e)))?;
+    if stat.is_dir() {
+        fs::create_dir_all(local).map_err(transfer_error)?;
+        for (path, _) in retry("读取远程目录", sftp_deadline(), || sftp.readdir(remote))?.map_err(transfer_error)? {
+            let Some(name) = path.file_name() else { continue };
+            sftp_download(sftp, &path, &local.join(name), deadline, options, files)?;
+        }
+        return Ok(());
+    }
+    // 跳过设备、管道等特殊文件
+    if !stat.is_file() {
+        return Ok(());
+    }
+
+    if let Some(parent) = local.parent() {
+        fs::create_dir_all(parent).map_err(transfer_error)?;
+    }
+    let mut source = retry("打开远程文件", sftp_deadline(), || sftp.open(remote))?.map_err(transfer_error)?;
+    let mut target = fs::File::create(local).map_err(|e| transfer_error(format!("{}: {}", local.display(), e)))?;
+    let mut buf = vec![0u8; SFTP_CHUNK_SIZE];
+    loop {
+        check_interrupt(deadline, options)?;
+        let n = read_remote(&mut source, &mut buf, deadline, options)?;
+        if n == 0 {
+            break;
+        }
+        target.write_all(&buf[..n]).map_err(transfer_error)?;
+    }
+    let _ = retry("关闭远程文件", sftp_deadline(), || source.close())?;
+    files.push(local.to_path_buf());
+    Ok(())
+}
+
+// 通过SFTP把本地文件上传到板卡，校验大小和SHA-256，返回文件大小
+pub async fn ssh_upload_file(local: &Path, remote: &str, options: &SshOptions) -> Result<u64, SshError> {
+    if is_serial_fallback() {
+        return Err(SshError::Connect("以太网不可用，无法使用SFTP".to_string()));
+    }
+    let _permit = acquire_permit(options).await?;
+    let deadline = Instant::now() + Duration::from_millis(options.timeout_ms);
+    let local = local.to_path_buf();
+    let remote = remote.to_string();
+    let options = options.clone();
+
+    task::spawn_blocking(move || {
+        with_session(&format!("上传 {} -> {}", local.display(), remote), &options, open_sftp, |session, sftp| {
+            sftp_upload(session, sftp, &local, &remote, deadline, &options)
+        })
+    }).await.map_err(|e| SshError::Connect(e.to_string()))?
+}
+
+// 通过SFTP从板卡下载文件或目录到本地，返回下载的本地文件列表
+pub async fn ssh_download(remote: &str, local: &Path, options: &SshOptions) -> Result<Vec<PathBuf>, SshError> {
+    if is_serial_fallback() {
+        return Err(SshError::Connect("以太网不可用，无法使用SFTP".to_string()));
+    }
+    let _permit = acquire_permit(options).await?;
+    let deadline = Instant::now() + Duration::from_millis(options.timeout_ms);
+    let local = local.to_path_buf();
+    let remote = remote.to_string();
+    let options = options.clone();
+
+    task::spawn_blocking(move || {
+        with_session(&format!("下载 {} -> {}", remote, local.display()), &options, open_sftp, |_, sftp| {
+            let mut files = Vec::new();
+            sftp_download(&sftp, Path::new(&remote), &local, deadline, &options, &mut files)?;
+            Ok(files)
+        })
+    }).await.map_err(|e| SshError::Connect(e.to_string()))?
+}
+
 // #[tokio::main]
 // async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     
@@ -0,0 +1,118 @@
+// SSH命令输出记录：流式执行的命令逐行写入save目录的 ssh_<时间>_<项目>.log，并在JSON记录中登记
+// 串号检测出来之前执行的命令先暂存，关联串号后一并登记
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+use std::time::Instant;
+use chrono::Local;
+use lazy_static::lazy_static;
+
+use crate::function::save::{get_save_dir, add_command_log, CommandLog};
+
+// 输出文件名前缀
+const TRANSCRIPT_PREFIX: &str = "ssh_";
+
+// 日志控制：false=关闭日志，true=开启日志
+const LOG_ENABLE: bool = true;
+
+// 自定义日志函数
+fn log(msg: &str) {
+    if LOG_ENABLE {
+        println!("[transcript]{}", msg);
+    }
+}
+
+// 当前板卡的记录状态
+#[derive(Default)]
+struct TranscriptRecords {
+    serial: Option<String>,     // 已关联的串号
+    pending: Vec<CommandLog>,   // 关联串号前完成的命令
+}
+
+lazy_static! {
+    static ref TRANSCRIPT_RECORDS: Mutex<TranscriptRecords> = Mutex::new(TranscriptRecords::default());
+}
+
+// 一条命令的输出记录
+pub struct Transcript {
+    log: CommandLog,
+    writer: Option<BufWriter<File>>,    // save目录不可用时只推送不保存
+    started: Instant,
+}
+
+impl Transcript {
+    // 开始记录，创建输出文件并写入命令
+    pub fn start(item: &str, command: &str) -> Transcript {
+        let now = Local::now();
+        let name = format!("{}{}_{}.log", TRANSCRIPT_PREFIX, now.format("%Y%m%d_%H%M%S%.3f"), item);
+        let writer = match get_save_dir().map_err(|e| e.to_string()).and_then(|dir| File::create(dir.join(&name)).map_err(|e| e.to_string())) {
+            Ok(file) => {
+                let mut writer = BufWriter::new(file);
+                let _ = writeln!(writer, "# item: {}\n# command: {}\n# started: {}", item, command, now.format("%Y-%m-%d %H:%M:%S"));
+                Some(writer)
+            }
+            Err(e) => {
+                log(&format!("无法创建命令输出文件 {}: {}", name, e));
+                None
+            }
+        };
+        Transcript {
+            log: CommandLog {
+                item: item.to_string(),
+                command: command.to_string(),
+                started: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+                duration_ms: 0,
+                exit_status: None,
+                transcript: name,
+            },
+            writer,
+            started: Instant::now(),
+        }
+    }
+
+    // 写入一行输出，stream为 "stdout" 或 "stderr"
+    pub fn push(&mut self, stream: &str, line: &str) {
+        if let Some(writer) = self.writer.as_mut() {
+            let _ = writeln!(writer, "[{}] [{}] {}", Local::now().format("%H:%M:%S%.3f"), stream, line);
+        }
+    }
+
+    // 结束记录并登记到当前板卡的JSON记录
+    pub fn finish(mut self, exit_status: Option<i32>) {
+        self.log.duration_ms = self.started.elapsed().as_millis() as u64;
+        self.log.exit_status = exit_status;
+        let Some(mut writer) = self.writer.take() else { return };
+        let status = exit_status.map(|code| code.to_string()).unwrap_or_else(|| "none".to_string());
+        let _ = writeln!(writer, "# exit: {}, duration: {}ms", status, self.log.duration_ms);
+        if let Err(e) = writer.flush() {
+            log(&format!("写入命令输出文件失败 {}: {}", self.log.transcript, e));
+        }
+
+        let mut records = TRANSCRIPT_RECORDS.lock().unwrap_or_else(|e| e.into_inner());
+        match records.serial.clone() {
+            Some(serial) => save_command_log(&serial, &self.log),
+            None => records.pending.push(self.log),
+        }
+    }
+}
+
+fn save_command_log(serial: &str, command_log: &CommandLog) {
+    if let Err(e) = add_command_log(serial, command_log) {
+        log(&format!("登记命令输出失败 {}: {}", command_log.transcript, e));
+    }
+}
+
+// 更换板卡时清空，之后的命令记录等待关联新的串号
+pub fn reset_transcripts() {
+    let mut records = TRANSCRIPT_RECORDS.lock().unwrap_or_else(|e| e.into_inner());
+    *records = TranscriptRecords::default();
+}
+
+// 关联串号，登记之前暂存的命令记录
+pub fn link_transcripts(serial: &str) {
+    let mut records = TRANSCRIPT_RECORDS.lock().unwrap_or_else(|e| e.into_inner());
+    records.serial = Some(serial.to_string());
+    for command_log in std::mem::take(&mut records.pending) {
+        save_command_log(serial, &command_log);
+    }
+}
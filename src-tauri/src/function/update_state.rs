@@ -1,6 +1,7 @@
 // 更新前端状态库：使用几个函数直接确定前端状态亮哪个灭哪个
 use tauri::{AppHandle, Emitter};
 use std::fmt;
+use serde::Serialize;
 use serde_json;
 
 // use state::Storage;
@@ -111,6 +112,8 @@ pub enum AppStepStatus {
     StartStep2          = 11, // 启动Step2
     StartStep3          = 12, // 启动Step3
     Finished            = 13, // 完成
+    LoginFailed         = 14, // 串口登录被拒绝（密码错误或账户锁定）
+    Recovering          = 15, // 进入U-Boot通过串口恢复固件
 }
 
 // 日志控制：false=关闭日志，true=开启日志
@@ -183,6 +186,22 @@ pub fn set_upload_count(app_handle: AppHandle, count: u64) {
     }
 }
 
+// 上传队列状态，推送到前端
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UploadQueueStatus {
+    pub depth: u64,                     // 待上传数量
+    pub oldest_age_secs: Option<u64>,   // 最早的待上传记录已等待的时间
+    pub last_error: Option<String>,     // 最近一次失败的原因，上传成功后清除
+    pub quarantined: u64,               // quarantine目录中的记录数量
+}
+
+// 设置上传队列状态：待上传数量、最早记录的等待时间、最近的错误和隔离数量
+pub fn set_upload_queue_status(app_handle: AppHandle, status: &UploadQueueStatus) {
+    if let Err(e) = app_handle.clone().emit("upload-queue-update", status) {
+        log(&format!("测试任务推送上传队列状态失败: {}", e));
+    }
+}
+
 pub fn set_state_to_struct(test_str: &str, test_status: AppTestStatus) {
     if let Some(state_arc) = CURRENT_TEST_STATE.get() {
         if let Ok(mut state) = state_arc.lock() {
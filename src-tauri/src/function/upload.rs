@@ -1,14 +1,31 @@
-use anyhow::{Result, Context};
-use reqwest::Client;
+use anyhow::{anyhow, bail, Result, Context};
+use reqwest::{Certificate, Client, Proxy};
 use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
 use tauri::async_runtime::{spawn};
 use tauri::{AppHandle};
-use std::time::Duration;
-use std::thread;
-use crate::function::update_state::{set_server_state, set_upload_count};
-use crate::function::save::{get_unuploaded_num, get_one_unuploaded_file_path, rm_from_unuploaded};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::time::sleep;
+use crate::function::update_state::{set_server_state, set_upload_count, set_upload_queue_status, UploadQueueStatus};
+use crate::function::save::{get_unuploaded_files, rm_from_unuploaded, quarantine_unuploaded, get_quarantine_num, 
+    get_mes_config, get_mes_secrets, get_config_file, get_deliveries, set_delivery, MesSecrets, UnuploadedFile};
+
+pub mod sink;
+use sink::configured_sinks;
+
+// 重试间隔：第一次失败后5秒，之后每次翻倍，最长10分钟
+const RETRY_BASE_SECS: u64 = 5;
+const RETRY_MAX_SECS: u64 = 600;
+// 没有到期的记录时检查新记录的间隔
+const IDLE_CHECK_SECS: u64 = 10;
+// 上传到MES和写入CSV的测试项目
+const TEST_FIELDS: [&str; 14] = [
+    "app", "atx", "emmc", "eth", "lt6911", "lt86102", "rotary",
+    "screen", "sdcard", "touch", "uart", "usb", "wifi", "ws2812",
+];
 
 // 日志控制：false=关闭日志，true=开启日志
 const LOG_ENABLE: bool = true;
@@ -20,17 +37,147 @@ fn log(msg: &str) {
     }
 }
 
+// 一条待投递的测试记录
+pub struct ResultRecord {
+    pub file_name: String,      // 待上传目录中的文件名，如 "NeaZ10003.json"
+    pub serial: String,
+    pub test_pass: bool,
+    pub request_body: Value,    // MES请求体：uid、serial、hardware和各测试项目结果
+    pub raw: Value,             // 完整记录
+}
+
+// 上传失败原因
+#[derive(Debug, Clone)]
+pub enum UploadError {
+    Invalid(String),    // 记录本身有问题（JSON格式错误、缺少必填字段），重试也不会成功，移入quarantine
+    Failed(String),     // 读取文件、配置、网络或服务器拒绝，按退避时间重试
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadError::Invalid(reason) => write!(f, "记录无效: {}", reason),
+            UploadError::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+// 单条记录的重试状态
+struct RetryState {
+    failures: u32,
+    next_attempt: Instant,
+}
+
+// 第failures次失败后的等待时间
+fn retry_delay(failures: u32) -> Duration {
+    let secs = RETRY_BASE_SECS.saturating_mul(1u64 << failures.saturating_sub(1).min(16));
+    Duration::from_secs(secs.min(RETRY_MAX_SECS))
+}
+
+// MES请求使用的客户端、接口地址和凭据
+struct MesContext {
+    client: Client,
+    base_url: String,
+    secrets: MesSecrets,
+}
+
+impl MesContext {
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+}
+
+// 按配置创建MES客户端，每次上传前重新读取，修改配置或更换凭据后不需要重启
+fn mes_context() -> Result<MesContext> {
+    let config = get_mes_config();
+    let secrets = get_mes_secrets().map_err(|e| anyhow!("读取MES凭据失败: {}", e))?;
+    if secrets.token.is_empty() {
+        bail!("MES凭据未配置，请填写config目录中的secrets.toml");
+    }
+
+    let mut builder = Client::builder().timeout(Duration::from_secs(config.timeout_secs.max(1)));
+    if !config.ca_cert.is_empty() {
+        let ca_path = get_config_file(&config.ca_cert).map_err(|e| anyhow!("{}", e))?;
+        let pem = fs::read(&ca_path).context(format!("Failed to read CA certificate: {}", ca_path.display()))?;
+        builder = builder.add_root_certificate(Certificate::from_pem(&pem).context("Invalid CA certificate")?);
+    }
+    if !config.proxy.is_empty() {
+        builder = builder.proxy(Proxy::all(&config.proxy).context(format!("Invalid proxy: {}", config.proxy))?);
+    }
+    Ok(MesContext {
+        client: builder.build().context("Failed to build HTTP client")?,
+        base_url: config.base_url.trim_end_matches('/').to_string(),
+        secrets,
+    })
+}
+
 // 从JSON文件读取并上传数据
-pub async fn upload_from_json_file(file_path: &str) -> Result<bool> {
-    // 1. 读取JSON文件
+pub async fn upload_from_json_file(file_path: &Path) -> Result<(), UploadError> {
+    // 1. 读取JSON文件，读取失败可能是文件正在写入，按失败重试
     let json_content = fs::read_to_string(file_path)
-        .context(format!("Failed to read file: {}", file_path))?;
+        .map_err(|e| UploadError::Failed(format!("Failed to read file {}: {}", file_path.display(), e)))?;
     
-    // 2. 解析JSON
-    let json_data: Value = serde_json::from_str(&json_content)
+    // 2. 解析和检查记录，不合格的记录不再重试
+    let file_name = file_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let record = parse_record(&file_name, &json_content)
+        .map_err(|e| UploadError::Invalid(format!("{:#}", e)))?;
+    
+    // 3. 投递到各个结果输出
+    deliver_record(&record).await
+}
+
+// 投递到所有启用的结果输出，已接收的输出不再重复投递；必须的输出全部接收后才算完成
+// 可选的输出失败只记录，必须的输出完成后不再重试
+async fn deliver_record(record: &ResultRecord) -> Result<(), UploadError> {
+    let sinks = configured_sinks();
+    if !sinks.iter().any(|sink| sink.required()) {
+        return Err(UploadError::Failed("没有启用必须的结果输出，请检查配置[sinks]".to_string()));
+    }
+
+    let deliveries = get_deliveries(&record.file_name);
+    let mut first_error = None;
+    for sink in &sinks {
+        if deliveries.get(sink.name()).is_some_and(|delivery| delivery.accepted) {
+            continue;
+        }
+        let result = sink.deliver(record).await;
+        let error = result.as_ref().err().map(|e| e.to_string());
+        if let Err(e) = set_delivery(&record.file_name, sink.name(), error.as_deref()) {
+            log(&format!("保存 {} 的投递记录失败: {}", record.file_name, e));
+        }
+        match result {
+            Ok(()) => log(&format!("{} 已投递到 {}", record.file_name, sink.name())),
+            Err(e) => {
+                log(&format!("{} 投递到 {} 失败: {}", record.file_name, sink.name(), e));
+                if sink.required() && first_error.is_none() {
+                    first_error = Some(match e {
+                        UploadError::Invalid(reason) => UploadError::Invalid(format!("{}: {}", sink.name(), reason)),
+                        UploadError::Failed(reason) => UploadError::Failed(format!("{}: {}", sink.name(), reason)),
+                    });
+                }
+            }
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+// 上传到MES：测试数据，通过测试时再上传测试结果
+async fn upload_to_mes(record: &ResultRecord) -> Result<(), UploadError> {
+    let mes = mes_context().map_err(|e| UploadError::Failed(format!("{:#}", e)))?;
+    upload_json_data(&mes, &record.request_body).await.map_err(UploadError::Failed)?;
+    if record.test_pass {
+        upload_test_results(&mes, &record.serial).await.map_err(UploadError::Failed)?;
+    }
+    Ok(())
+}
+
+// 解析并检查记录，生成MES请求体
+fn parse_record(file_name: &str, json_content: &str) -> Result<ResultRecord> {
+    // 1. 解析JSON
+    let json_data: Value = serde_json::from_str(json_content)
         .context("Failed to parse JSON")?;
     
-    // 3. 提取必填字段
+    // 2. 提取必填字段
     let device_info = &json_data["device_info"];
     let uid = device_info["soc_uid"]
         .as_str()
@@ -51,10 +198,10 @@ pub async fn upload_from_json_file(file_path: &str) -> Result<bool> {
         .as_bool()
         .context("Missing test_pass in device_info")?;
     
-    // 4. 提取测试结果
+    // 3. 提取测试结果
     let test_content = &json_data["test_content"];
     
-    // 5. 使用 serde_json::json! 宏构建JSON，这是关键修复
+    // 4. 使用 serde_json::json! 宏构建JSON，这是关键修复
     let mut request_body = json!({
         "uid": uid,
         "serial": serial,
@@ -67,24 +214,18 @@ pub async fn upload_from_json_file(file_path: &str) -> Result<bool> {
     // 打印查看（可选）
     // log(&format!("JSON请求体: {}", serde_json::to_string_pretty(&request_body)?));
     
-    // 7. 发送请求
-    let mut final_result = true;
-    let data_result = upload_json_data(&request_body).await;
-    if test_pass {
-        final_result = upload_test_results(&serial).await;
-    }
-    
-    Ok(final_result && data_result)
+    Ok(ResultRecord {
+        file_name: file_name.to_string(),
+        serial,
+        test_pass,
+        request_body,
+        raw: json_data,
+    })
 }
 
 fn add_test_fields(request_body: &mut Value, test_content: &Value) {
     if let Value::Object(map) = request_body {
-        let test_fields = [
-            "app", "atx", "emmc", "eth", "lt6911", "lt86102", "rotary",
-            "screen", "sdcard", "touch", "uart", "usb", "wifi", "ws2812",
-        ];
-        
-        for field in test_fields.iter() {
+        for field in TEST_FIELDS.iter() {
             if let Some(value) = test_content.get(*field) {
                 if let Some(str_value) = value.as_str() {
                     map.insert(
@@ -98,21 +239,17 @@ fn add_test_fields(request_body: &mut Value, test_content: &Value) {
 }
 
 // 上传JSON数据到服务器
-async fn upload_json_data(request_body: &Value) -> bool {
-    let client = Client::new();
-    let url = "https://maixvision.sipeed.com/api/v1/nanokvm/test-items";
-    
-    let response = match client
-        .post(url)
-        .header("token", "MaixVision2024")
+async fn upload_json_data(mes: &MesContext, request_body: &Value) -> Result<(), String> {
+    let response = match mes.client
+        .post(mes.url("test-items"))
+        .header("token", &mes.secrets.token)
         .json(request_body)  // 直接传递 Value 类型，reqwest 会正确处理
         .send()
         .await
     {
         Ok(resp) => resp,
         Err(e) => {
-            log(&format!("Failed to send request: {}", e));
-            return false;
+            return Err(format!("Failed to send request: {}", e));
         }
     };
     
@@ -120,59 +257,51 @@ async fn upload_json_data(request_body: &Value) -> bool {
     let json_response = match response.json::<Value>().await {
         Ok(json) => json,
         Err(e) => {
-            log(&format!("Failed to parse response: {}", e));
-            return false;
+            return Err(format!("Failed to parse response: {}", e));
         }
     };
     
     let code = match json_response["code"].as_i64() {
         Some(c) => c as i32,
         None => {
-            log(&format!("Missing code in response"));
-            return false;
+            return Err("Missing code in response".to_string());
         }
     };
     
     let msg = match json_response["msg"].as_str() {
         Some(m) => m.to_string(),
         None => {
-            log(&format!("Missing msg in response"));
-            return false;
+            return Err("Missing msg in response".to_string());
         }
     };
     
     if code == 0 {
         log(&format!("上传成功: code={}, msg={}", code, msg));
-        true
+        Ok(())
     } else {
-        log(&format!("上传失败: code={}, msg={}", code, msg));
-        false
+        Err(format!("上传失败: code={}, msg={}", code, msg))
     }
 }
 
 // 上传测试结果到服务器
-async fn upload_test_results(serial: &str) -> bool {
-    let client = Client::new();
-    let url = "https://maixvision.sipeed.com/api/v1/nanokvm/test-result";
-
+async fn upload_test_results(mes: &MesContext, serial: &str) -> Result<(), String> {
     let test_status = "pass";
     let request_body = json!({
         "serial": serial,
         "status": test_status,
     });
     
-    let response = match client
-        .post(url)
-        .header("token", "MaixVision2024")
-        .header("passwd", "Sipeed.NanoKVM@25")
+    let response = match mes.client
+        .post(mes.url("test-result"))
+        .header("token", &mes.secrets.token)
+        .header("passwd", &mes.secrets.passwd)
         .json(&request_body)  // 直接传递 Value 类型，reqwest 会正确处理
         .send()
         .await
     {
         Ok(resp) => resp,
         Err(e) => {
-            log(&format!("Failed to send request: {}", e));
-            return false;
+            return Err(format!("Failed to send request: {}", e));
         }
     };
     
@@ -180,33 +309,29 @@ async fn upload_test_results(serial: &str) -> bool {
     let json_response = match response.json::<Value>().await {
         Ok(json) => json,
         Err(e) => {
-            log(&format!("Failed to parse response: {}", e));
-            return false;
+            return Err(format!("Failed to parse response: {}", e));
         }
     };
     
     let code = match json_response["code"].as_i64() {
         Some(c) => c as i32,
         None => {
-            log(&format!("Missing code in response"));
-            return false;
+            return Err("Missing code in response".to_string());
         }
     };
     
     let msg = match json_response["msg"].as_str() {
         Some(m) => m.to_string(),
         None => {
-            log(&format!("Missing msg in response"));
-            return false;
+            return Err("Missing msg in response".to_string());
         }
     };
     
     if code == 0 || code == -4 {
         log(&format!("上传成功: code={}, msg={}", code, msg));
-        true
+        Ok(())
     } else {
-        log(&format!("上传失败: code={}, msg={}", code, msg));
-        false
+        Err(format!("上传失败: code={}, msg={}", code, msg))
     }
 }
 
@@ -220,41 +345,71 @@ async fn upload_test_results(serial: &str) -> bool {
     
 //     Ok(())
 // }
+// 推送队列状态到前端
+fn report_queue(app_handle: &AppHandle, pending: &[UnuploadedFile], last_error: &Option<String>) {
+    let status = UploadQueueStatus {
+        depth: pending.len() as u64,
+        oldest_age_secs: pending.first().map(|file| SystemTime::now().duration_since(file.modified).map_or(0, |age| age.as_secs())),
+        last_error: last_error.clone(),
+        quarantined: get_quarantine_num() as u64,
+    };
+    set_upload_count(app_handle.clone(), status.depth);
+    set_upload_queue_status(app_handle.clone(), &status);
+}
+
+// 上传队列：按进入队列的时间从旧到新上传，每条记录失败后单独退避，无效记录移入quarantine，不阻塞后面的记录
 pub fn spawn_upload_task(app_handle: AppHandle) {
     log(&format!("开始上传线程"));
     spawn(async move {
+        let mut retries: HashMap<String, RetryState> = HashMap::new();
+        let mut last_error: Option<String> = None;
         loop {
-            log(&format!("开始检查未上传文件"));
-            let mut unuploaded_num = get_unuploaded_num() as u64;
-            set_upload_count(app_handle.clone(), unuploaded_num);
-            while unuploaded_num > 0 {
-                unuploaded_num = get_unuploaded_num() as u64;
-                set_upload_count(app_handle.clone(), unuploaded_num);
-                if unuploaded_num > 0 {
-                    let file_path = get_one_unuploaded_file_path().unwrap();
-                    match upload_from_json_file(&file_path).await {
-                        Ok(true) => {
-                            log(&format!("上传文件 {} 成功", file_path));
-                            let file_name = PathBuf::from(&file_path).file_name().unwrap().to_str().unwrap().to_string();
-                            let _ = rm_from_unuploaded(&file_name);
+            let pending = get_unuploaded_files();
+            retries.retain(|name, _| pending.iter().any(|file| &file.name == name));
+            report_queue(&app_handle, &pending, &last_error);
+
+            // 取最早的已到重试时间的记录，没有时等到最近的重试时间
+            let now = Instant::now();
+            let Some(file) = pending.iter().find(|file| retries.get(&file.name).is_none_or(|retry| retry.next_attempt <= now)) else {
+                let wait = retries.values()
+                    .map(|retry| retry.next_attempt.saturating_duration_since(now))
+                    .min()
+                    .unwrap_or(Duration::from_secs(IDLE_CHECK_SECS))
+                    .min(Duration::from_secs(IDLE_CHECK_SECS));
+                sleep(wait).await;
+                continue;
+            };
 
-                            set_server_state(app_handle.clone(), true);
-                        },
-                        Ok(false) => {
-                            log(&format!("上传文件 {} 失败", file_path));
-                            set_server_state(app_handle.clone(), false);
-                        },
-                        Err(e) => {
-                            log(&format!("上传文件 {} 出错: {:?}", file_path, e));
-                            set_server_state(app_handle.clone(), false);
-                        }
+            match upload_from_json_file(&file.path).await {
+                Ok(()) => {
+                    log(&format!("上传文件 {} 成功", file.name));
+                    if let Err(e) = rm_from_unuploaded(&file.name) {
+                        log(&format!("移除已上传文件 {} 失败: {}", file.name, e));
                     }
+                    retries.remove(&file.name);
+                    last_error = None;
+                    set_server_state(app_handle.clone(), true);
+                }
+                Err(UploadError::Invalid(reason)) => {
+                    log(&format!("文件 {} 无效，移入quarantine: {}", file.name, reason));
+                    match quarantine_unuploaded(&file.name, &reason) {
+                        Ok(path) => log(&format!("已移入 {}", path.display())),
+                        Err(e) => log(&format!("移入quarantine失败: {}", e)),
+                    }
+                    retries.remove(&file.name);
+                    last_error = Some(format!("{}: {}", file.name, UploadError::Invalid(reason)));
+                }
+                Err(UploadError::Failed(reason)) => {
+                    let retry = retries.entry(file.name.clone()).or_insert(RetryState { failures: 0, next_attempt: now });
+                    retry.failures += 1;
+                    let delay = retry_delay(retry.failures);
+                    retry.next_attempt = Instant::now() + delay;
+                    log(&format!("上传文件 {} 失败（第{}次），{}秒后重试: {}", file.name, retry.failures, delay.as_secs(), reason));
+                    last_error = Some(format!("{}: {}", file.name, reason));
+                    set_server_state(app_handle.clone(), false);
                 }
-                thread::sleep(Duration::from_secs(1));
             }
-
-
-            thread::sleep(Duration::from_secs(10));
+            sleep(Duration::from_secs(1)).await;
         }
     });
-}
\ No newline at end of file
+}
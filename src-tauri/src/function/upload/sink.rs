@@ -0,0 +1,245 @@
+// 测试记录的结果输出：MES、本地SQLite存档、共享文件夹和Webhook，按配置 [sinks] 启用
+// 每个输出的投递情况记录在save目录的JSON中，已接收的输出不再重复投递
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use chrono::Local;
+
+use super::{upload_to_mes, ResultRecord, UploadError, TEST_FIELDS};
+use crate::function::save::{get_sinks_config, get_data_file, get_webhook_secrets,
+    SqliteSinkConfig, FolderSinkConfig, WebhookSinkConfig};
+
+// 结果输出
+pub trait ResultSink: Send + Sync {
+    // 名称，用于投递记录和日志
+    fn name(&self) -> &'static str;
+    // 是否必须接收，必须的输出全部接收后记录才算上传完成
+    fn required(&self) -> bool;
+    // 投递一条记录，同一条记录可能因为其他输出失败而再次投递，实现需要可以重复写入
+    fn deliver<'a>(&'a self, record: &'a ResultRecord) -> BoxFuture<'a, Result<(), UploadError>>;
+}
+
+// 按配置创建已启用的结果输出，每次上传前重新读取，修改配置后不需要重启
+pub fn configured_sinks() -> Vec<Box<dyn ResultSink>> {
+    let config = get_sinks_config();
+    let mut sinks: Vec<Box<dyn ResultSink>> = Vec::new();
+    if config.mes.enabled {
+        sinks.push(Box::new(MesSink { required: config.mes.required }));
+    }
+    if config.sqlite.enabled {
+        sinks.push(Box::new(SqliteSink { config: config.sqlite }));
+    }
+    if config.folder.enabled {
+        sinks.push(Box::new(FolderSink { config: config.folder }));
+    }
+    if config.webhook.enabled {
+        sinks.push(Box::new(WebhookSink { config: config.webhook }));
+    }
+    sinks
+}
+
+// 在阻塞线程中执行文件和数据库操作，网络文件夹无响应时不阻塞上传任务
+async fn run_blocking<F>(task: F) -> Result<(), UploadError>
+where
+    F: FnOnce() -> Result<(), String> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(task).await {
+        Ok(result) => result.map_err(UploadError::Failed),
+        Err(e) => Err(UploadError::Failed(format!("任务异常结束: {}", e))),
+    }
+}
+
+// 现有的HTTP MES，接口地址和凭据见 [mes] 和 secrets.toml
+struct MesSink {
+    required: bool,
+}
+
+impl ResultSink for MesSink {
+    fn name(&self) -> &'static str {
+        "mes"
+    }
+
+    fn required(&self) -> bool {
+        self.required
+    }
+
+    fn deliver<'a>(&'a self, record: &'a ResultRecord) -> BoxFuture<'a, Result<(), UploadError>> {
+        upload_to_mes(record).boxed()
+    }
+}
+
+// 本地SQLite存档，同一文件重复投递时覆盖
+struct SqliteSink {
+    config: SqliteSinkConfig,
+}
+
+impl SqliteSink {
+    fn write(path: &Path, file_name: &str, serial: &str, test_pass: bool, record: &str) -> rusqlite::Result<()> {
+        let conn = rusqlite::Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS results (
+                file_name   TEXT PRIMARY KEY,
+                serial      TEXT NOT NULL,
+                test_pass   INTEGER NOT NULL,
+                archived_at TEXT NOT NULL,
+                record      TEXT NOT NULL
+            )",
+        )?;
+        conn.execute(
+            "INSERT OR REPLACE INTO results (file_name, serial, test_pass, archived_at, record) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![file_name, serial, test_pass, Local::now().format("%Y-%m-%d %H:%M:%S").to_string(), record],
+        )?;
+        Ok(())
+    }
+}
+
+impl ResultSink for SqliteSink {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn required(&self) -> bool {
+        self.config.required
+    }
+
+    fn deliver<'a>(&'a self, record: &'a ResultRecord) -> BoxFuture<'a, Result<(), UploadError>> {
+        let path = get_data_file(&self.config.path).map_err(|e| e.to_string());
+        let file_name = record.file_name.clone();
+        let serial = record.serial.clone();
+        let test_pass = record.test_pass;
+        let raw = record.raw.to_string();
+        run_blocking(move || {
+            let path = path?;
+            SqliteSink::write(&path, &file_name, &serial, test_pass, &raw)
+                .map_err(|e| format!("写入 {} 失败: {}", path.display(), e))
+        }).boxed()
+    }
+}
+
+// 共享文件夹，每条记录写一个JSON和/或一行带表头的CSV，先写临时文件再改名，读取方不会读到一半的文件
+struct FolderSink {
+    config: FolderSinkConfig,
+}
+
+// CSV字段转义
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// 单条记录的CSV：串号、uid、硬件、是否通过和各测试项目
+fn record_csv(record: &ResultRecord) -> String {
+    let device_info = &record.raw["device_info"];
+    let test_content = &record.raw["test_content"];
+    let mut header = vec!["serial", "uid", "hardware", "test_pass"];
+    header.extend(TEST_FIELDS);
+    let mut row = vec![
+        record.serial.clone(),
+        device_info["soc_uid"].as_str().unwrap_or_default().to_string(),
+        device_info["hardware"].as_str().unwrap_or_default().to_string(),
+        record.test_pass.to_string(),
+    ];
+    row.extend(TEST_FIELDS.iter().map(|field| test_content[*field].as_str().unwrap_or_default().to_string()));
+    format!(
+        "{}\r\n{}\r\n",
+        header.join(","),
+        row.iter().map(|value| csv_field(value)).collect::<Vec<_>>().join(","),
+    )
+}
+
+// 先写临时文件再改名
+fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+    fs::write(&temp_path, content).map_err(|e| format!("写入 {} 失败: {}", temp_path.display(), e))?;
+    fs::rename(&temp_path, path).map_err(|e| format!("重命名 {} 失败: {}", path.display(), e))
+}
+
+impl ResultSink for FolderSink {
+    fn name(&self) -> &'static str {
+        "folder"
+    }
+
+    fn required(&self) -> bool {
+        self.config.required
+    }
+
+    fn deliver<'a>(&'a self, record: &'a ResultRecord) -> BoxFuture<'a, Result<(), UploadError>> {
+        let dir = PathBuf::from(&self.config.path);
+        let format = self.config.format.clone();
+        let stem = record.file_name.trim_end_matches(".json").to_string();
+        let json = serde_json::to_string_pretty(&record.raw).unwrap_or_default();
+        let csv = record_csv(record);
+        run_blocking(move || {
+            if dir.as_os_str().is_empty() {
+                return Err("没有配置共享文件夹路径".to_string());
+            }
+            fs::create_dir_all(&dir).map_err(|e| format!("无法访问 {}: {}", dir.display(), e))?;
+            if format != "csv" {
+                write_atomic(&dir.join(format!("{}.json", stem)), json.as_bytes())?;
+            }
+            if format == "csv" || format == "both" {
+                write_atomic(&dir.join(format!("{}.csv", stem)), csv.as_bytes())?;
+            }
+            Ok(())
+        }).boxed()
+    }
+}
+
+// 通用Webhook，POST完整记录，2xx为接收
+struct WebhookSink {
+    config: WebhookSinkConfig,
+}
+
+impl WebhookSink {
+    async fn post(&self, record: &ResultRecord) -> Result<(), UploadError> {
+        if self.config.url.is_empty() {
+            return Err(UploadError::Failed("没有配置Webhook地址".to_string()));
+        }
+        let token = get_webhook_secrets().map(|secrets| secrets.token).map_err(|e| UploadError::Failed(format!("读取Webhook凭据失败: {}", e)))?;
+        let client = Client::builder()
+            .timeout(Duration::from_secs(self.config.timeout_secs.max(1)))
+            .build()
+            .map_err(|e| UploadError::Failed(format!("Failed to build HTTP client: {}", e)))?;
+
+        let body: Value = json!({
+            "file_name": record.file_name,
+            "serial": record.serial,
+            "test_pass": record.test_pass,
+            "record": record.raw,
+        });
+        let mut request = client.post(&self.config.url).json(&body);
+        if !token.is_empty() {
+            request = request.bearer_auth(&token);
+        }
+        let response = request.send().await
+            .map_err(|e| UploadError::Failed(format!("Failed to send request: {}", e)))?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(UploadError::Failed(format!("Webhook返回 {}", response.status())))
+        }
+    }
+}
+
+impl ResultSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn required(&self) -> bool {
+        self.config.required
+    }
+
+    fn deliver<'a>(&'a self, record: &'a ResultRecord) -> BoxFuture<'a, Result<(), UploadError>> {
+        self.post(record).boxed()
+    }
+}
@@ -4,6 +4,13 @@ mod second_app;
 
 // 从dialog_test模块导入按钮点击处理命令
 use crate::function::dialog_test::handle_button_click;
+// 从serial模块导入串口终端命令
+use crate::function::serial::terminal::{open_terminal, close_terminal, terminal_input};
+use crate::function::serial::transport::{list_serial_ports, select_serial_port};
+// 从ssh模块导入连接状态查询命令
+use crate::function::ssh::ssh_health;
+// 从server模块导入文件服务器状态查询命令
+use crate::function::server::file_server_status;
 use std::sync::Arc;
 use tauri::State;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -84,7 +91,7 @@ pub fn run() {
     
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![handle_button_click, select_program])
+        .invoke_handler(tauri::generate_handler![handle_button_click, select_program, open_terminal, close_terminal, terminal_input, list_serial_ports, select_serial_port, ssh_health, file_server_status])
         .setup(move |_app| {
             Ok(())
         })
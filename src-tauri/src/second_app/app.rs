@@ -3,16 +3,22 @@ use tauri::async_runtime::{spawn, JoinHandle};
 use tauri::{AppHandle};
 use crate::function::serial::{
     is_usb_tool_connected, get_current_data_density, serial_receive_clean, 
-    serial_send, detect_serial_string, execute_command_and_wait};
+    serial_send, detect_serial_string, detect_serial_regex, execute_command_and_wait};
+use crate::function::serial::{clear_console_buffer, terminal::wait_terminal_closed};
+use crate::function::serial::capture::{start_console_capture, stop_console_capture, link_console_capture};
+use crate::function::defect::{save_defect_record, handle_login_failure};
+use crate::function::serial::script::{login_script, LoginFailure};
 use crate::function::dialog_test::{show_dialog_and_wait};
 use crate::second_app::state::{AppStepStatus, AppTestStatus, 
     set_step_status, clean_step1_status, set_current_hardware, 
     set_target_serial, all_step_status_is_success, add_error_msg, get_error_msg};
 use crate::function::printer::{generate_image_with_params, print_image, generate_defects_image_with_params, PRINTER_ENABLE, TARGET_PRINTER};
-use crate::function::save::{get_config_str};
+use crate::function::save::{get_config_str, get_credentials};
 
 
 const NOT_CONNECTED_KVM_COUNT_THRESHOLD: u64 = 10;  // 未连接KVM超过10次，同步弹窗提示,约10s
+const VERSION_INFO_REGEX: &str = r"\((?P<type>[^()\r\n]+)\)\s*(?P<serial>N[0-9A-Za-z]{8})";  // /proc/lt6911_info/version 中的版本和串号
+const SERIAL_FILE_REGEX: &str = r"(?m)^(?P<serial>N[0-9A-Za-z]{8})\s*$";    // /etc/test-kvm/serial 中的串号
 
 // 日志控制：false=关闭日志，true=开启日志
 const LOG_ENABLE: bool = true;
@@ -26,8 +32,10 @@ fn log(msg: &str) {
 
 pub fn spawn_app_step1_task(app_handle: AppHandle) -> JoinHandle<()> {
     spawn(async move {
+        start_console_capture();    // 每块板卡单独录制串口日志
         let mut app_step1_status = AppStepStatus::Unconnected;
         let mut current_step = app_step1_status.clone();
+        let mut login_failure = LoginFailure::NoResponse;
         let mut not_connected_kvm_count = 0;
         let mut target_name = String::new();
         let mut target_serial = String::new();
@@ -36,6 +44,10 @@ pub fn spawn_app_step1_task(app_handle: AppHandle) -> JoinHandle<()> {
         
         let _ = serial_receive_clean().await;
         loop {
+            // 工程师打开串口终端时暂停，关闭后丢弃期间的输出
+            if wait_terminal_closed().await {
+                clear_console_buffer().await;
+            }
             // 每轮一定要检测的内容：
             if !is_usb_tool_connected().await {
                 app_step1_status = AppStepStatus::Unconnected;
@@ -191,26 +203,33 @@ pub fn spawn_app_step1_task(app_handle: AppHandle) -> JoinHandle<()> {
                         }
                     }
                 }
+                AppStepStatus::LoginFailed => {  // 串口登录被拒绝（密码错误或账户锁定）
+                    set_step_status(app_handle.clone(), "wait_power_on", AppTestStatus::Failed);
+                    let printed = handle_login_failure(app_handle.clone(), login_failure, |msg| {
+                        add_error_msg(msg);
+                        get_error_msg()
+                    }).await;
+                    if printed {
+                        break;
+                    }
+                    app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM，重新检测后再次登录
+                }
                 AppStepStatus::BootedLogin => {  // 已连接KVM，已开机（现在出现login）
                     log("已连接KVM，已开机（现在出现login）, 输入root密码");
                     set_step_status(app_handle.clone(), "wait_connection", AppTestStatus::Success);
                     set_step_status(app_handle.clone(), "wait_power_on", AppTestStatus::Testing);
-                    if ! execute_command_and_wait("root\n", "Password", 1000).await {
-                        app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM
-                        continue;
-                    }
-                    log("输入root密码成功, 输入sipeed密码");
-                    if ! execute_command_and_wait("sipeed\n", "Welcome", 1000).await {
-                        app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM
-                        continue;
-                    }
-                    // 等待一部分初始信息
-                    std::thread::sleep(Duration::from_millis(100));
-                    log("登录成功, 发送回车等待:~#");
-                    if ! execute_command_and_wait("\n", ":~#", 1000).await { 
-                        app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM
+                    let credentials = get_credentials();
+                    if let Err(failure) = login_script(&credentials.username, &credentials.password).run().await {
+                        log(&format!("登录失败: {}", failure));
+                        login_failure = LoginFailure::from_script(&failure);
+                        if login_failure == LoginFailure::NoResponse {
+                            app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM
+                        } else {
+                            app_step1_status = AppStepStatus::LoginFailed;  // 登录被拒绝
+                        }
                         continue;
                     }
+                    log("登录成功");
                     app_step1_status = AppStepStatus::CheckingState;  // 已连接KVM，已登录（现在出现:~#）
                 }
                 AppStepStatus::CheckingState => {  // 检查状态中，当前已登录（现在出现:~#）
@@ -241,41 +260,33 @@ pub fn spawn_app_step1_task(app_handle: AppHandle) -> JoinHandle<()> {
                     // 是否有串号，并获取串号
                     let mut serial_exit = false;
                     log("check serial & type");
-                    // serial_send("cat /proc/lt6911_info/version\n").await;
-                    let _ = execute_command_and_wait("cat /proc/lt6911_info/version\n", "version", 1000).await;
-                    std::thread::sleep(Duration::from_secs(1));
-                    let received = serial_receive_clean().await;
-                    if !received.contains("Unknown") {
+                    // 版本信息如 NanoKVM_Pro (Desk-G) Nebc1000A，没有写入过时为Unknown
+                    serial_send("cat /proc/lt6911_info/version\n").await;
+                    let version = detect_serial_regex(&[VERSION_INFO_REGEX, "Unknown"], 2000).await;
+                    if let Some(found) = version.filter(|found| found.index == 0) {
                         serial_exit = true;
-                        if let Some(start) = received.find("(") {
-                            if let Some(end) = received.find(")") {
-                                target_type = received[start+1..end].to_string();
-                                log(&format!("target_type: {}", target_type));
-                            }
-                        }
-                        if let Some(start) = received.rfind("N") {
-                            target_serial = received[start+0..start+9].to_string();
-                            log(&format!("target_serial: {}", target_serial));
-                        }
+                        target_type = found.named.get("type").cloned().unwrap_or_default();
+                        log(&format!("target_type: {}", target_type));
+                        target_serial = found.named.get("serial").cloned().unwrap_or_default();
+                        log(&format!("target_serial: {}", target_serial));
                         log("check serial & type end");
                     }
                     // 没有内容：
                     if !serial_exit {
                         let mut tmp_serial = String::new();
                         // 从cat /etc/test-kvm/serial 中获取串号，Nebc1000A
-                        // serial_send("cat /etc/test-kvm/serial\n").await;
-                        let _ = execute_command_and_wait("cat /etc/test-kvm/serial\n", "test-kvm", 1000).await;
-                        std::thread::sleep(Duration::from_millis(100));     // 等待版本信息返回
-                        let received = serial_receive_clean().await;
+                        serial_send("cat /etc/test-kvm/serial\n").await;
+                        let serial_file = detect_serial_regex(&[SERIAL_FILE_REGEX, "No such file or directory"], 1000).await;
                         // 没有内容，或者内容中包含directory，都认为是失败
-                        if !received.contains("No such file or directory") {
-                            if let Some(start) = received.rfind("N") {
-                                tmp_serial = received[start+0..start+9].to_string();
+                        if serial_file.as_ref().is_none_or(|found| found.index == 0) {
+                            if let Some(serial) = serial_file.and_then(|found| found.named.get("serial").cloned()) {
+                                tmp_serial = serial;
                                 target_serial = tmp_serial.clone();
                                 log(&format!("tmp_serial: {}", tmp_serial));
                             }
                         } else {
                             // 直接打印错误贴纸
+                            save_defect_record("没有产测串号");
                             add_error_msg("产测异常，请重新烧录镜像后重新产测");
                             let error_msg = get_error_msg();
                             if !error_msg.is_empty() {
@@ -328,6 +339,9 @@ pub fn spawn_app_step1_task(app_handle: AppHandle) -> JoinHandle<()> {
                     set_current_hardware(app_handle.clone(), &target_type);
                     // 推送target_serial到前端
                     set_target_serial(app_handle.clone(), &target_serial);
+                    if !target_serial.is_empty() {
+                        link_console_capture(&target_serial);
+                    }
 
                     set_step_status(app_handle.clone(), "get_status", AppTestStatus::Success);
                     app_step1_status = AppStepStatus::CheckingHDMI;  // 检查HDMI
@@ -484,6 +498,7 @@ pub fn spawn_app_step1_task(app_handle: AppHandle) -> JoinHandle<()> {
                     set_step_status(app_handle.clone(), "print_label", AppTestStatus::Success);
                     // 等待弹窗消失500ms
                     std::thread::sleep(Duration::from_millis(500));
+                    stop_console_capture();
                     break;
                 }
             }
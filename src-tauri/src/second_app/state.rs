@@ -22,6 +22,7 @@ pub enum AppStepStatus {
     CheckingTouch       = 10, // 检查触摸中
     CheckingKnob        = 11, // 检查旋钮中
     Finished            = 12, // 完成
+    LoginFailed         = 13, // 串口登录被拒绝（密码错误或账户锁定）
 }
 
 // 测试项目的状态枚举：'untested' | 'testing' | 'repairing' | 'success' | 'failed' | 'hidden';
@@ -1,16 +1,26 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tauri::async_runtime::{spawn, JoinHandle};
 use tauri::{AppHandle};
 use crate::function::serial::{
     is_usb_tool_connected, get_current_data_density, 
     serial_send, detect_serial_string, execute_command_and_wait};
+use crate::function::serial::{clear_console_buffer, terminal::wait_terminal_closed};
+use crate::function::serial::power::{pulse_reset, power_cycle, ResetTiming};
+use crate::function::serial::capture::{start_console_capture, stop_console_capture, link_console_capture};
+use crate::function::serial::boot::{reset_boot_analysis, link_boot_reports, boot_defect_message};
+use crate::function::transcript::{reset_transcripts, link_transcripts};
+use crate::function::script_result::ScriptOutput;
+use crate::function::defect::{save_defect_record, handle_login_failure};
+use crate::function::serial::uboot::{enter_uboot, recover_firmware, boot};
+use crate::function::serial::transfer::push_app_file;
+use crate::function::serial::script::{login_script, static_ip_script, LoginFailure};
 use crate::function::dialog_test::{show_dialog_and_wait};
 use crate::function::update_state::{AppStepStatus, AppTestStatus, 
     set_step_status, clean_step1_status, set_target_ip, set_current_hardware, 
     set_target_serial, all_step_status_is_success, add_error_msg, get_error_msg};
-use crate::function::server::spawn_file_server_task;
-use crate::function::ssh::{ssh_execute_command, ssh_execute_command_check_success};
-use crate::function::save::{get_config_str, create_serial_number, set_test_status};
+use crate::function::server::{spawn_file_server_task, server_url, set_dut_ip, take_server_measurement, DUT_DOWNLOAD_ITEM};
+use crate::function::ssh::{ssh_execute_command, ssh_execute_command_with, ssh_execute_command_check_success, ssh_upload_file, set_serial_fallback, is_serial_fallback, reset_ssh_pool, SshOptions};
+use crate::function::save::{get_config_str, get_credentials, create_serial_number, set_test_status, get_app_file_path, add_speed_measurement, SpeedMeasurement};
 use crate::function::printer::{is_printer_connected, generate_image_with_params, print_image, generate_defects_image_with_params, PRINTER_ENABLE, TARGET_PRINTER};
 use crate::test_app::step2::{spawn_step2_file_update, spawn_step2_hdmi_testing, 
     spawn_step2_usb_testing, spawn_step2_eth_testing, spawn_step2_wifi_testing, 
@@ -21,7 +31,12 @@ use crate::function::static_eth::STATIC_IP_ENABLE;
 
 const NOT_CONNECTED_KVM_COUNT_THRESHOLD: u64 = 10;  // 未连接KVM超过10次，同步弹窗提示,约10s
 const GET_IP_MAX_RETRY_COUNT: u64 = 10;
+const HARD_RESET_MAX_COUNT: u64 = 2;                // 每块板卡最多自动硬复位2次，之后交给操作员处理
+const POWER_CYCLE_OFF_MS: u64 = 1000;               // 断电重启的断电时间
 const DOWNLOAD_MAX_RETRY_COUNT: u64 = 5;
+const UPLOAD_TIMEOUT_MS: u64 = 120_000;             // SFTP推送或HTTP下载产测包的超时时间
+const EMMC_TEST_TIMEOUT_MS: u64 = 360_000;          // eMMC坏块检测的超时时间，脚本内badblocks最多300秒
+const PACKAGE_PUSH_ITEM: &str = "package_push";     // SFTP推送产测包的测量项，与板卡HTTP下载的服务端测量分开记录
 
 // 日志控制：false=关闭日志，true=开启日志
 const LOG_ENABLE: bool = true;
@@ -43,8 +58,14 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
         let current_target_ip = target_ip;
         let ssid = ssid;
         let password = password;
+        start_console_capture();    // 每块板卡单独录制串口日志
+        reset_boot_analysis();      // 每块板卡单独分析开机日志
+        set_serial_fallback(false); // 每块板卡默认通过SSH执行命令
+        reset_ssh_pool();           // 上一块板卡的SSH连接作废
+        reset_transcripts();        // 每块板卡单独登记命令输出
         let mut app_step1_status = AppStepStatus::Unconnected;
         let mut current_step = app_step1_status.clone();
+        let mut login_failure = LoginFailure::NoResponse;
         let mut not_connected_kvm_count = 0;
         let mut target_serial = String::new();
         let mut target_name = String::new();
@@ -55,8 +76,15 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
         let mut auto_type = true;
         let mut get_ip_retry_count = 0;
         let mut download_retry_count = 0;
-        let file_server_handle = spawn_file_server_task();     // 启动文件服务器任务
+        let mut hard_reset_count = 0;
+        let mut package_push: Option<SpeedMeasurement> = None;     // SFTP推送产测包的大小和用时
+        let file_server_handle = spawn_file_server_task(app_handle.clone());     // 启动文件服务器任务
+        set_dut_ip(&current_target_ip);     // 该IP下载产测包的速度记入当前板卡
         loop {
+            // 工程师打开串口终端时暂停，关闭后丢弃期间的输出
+            if wait_terminal_closed().await {
+                clear_console_buffer().await;
+            }
             // 每轮一定要检测的内容：
             if !is_usb_tool_connected().await {
                 app_step1_status = AppStepStatus::Unconnected;
@@ -99,6 +127,15 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
                     set_step_status(app_handle.clone(), "wait_connection", AppTestStatus::Testing);
 
                     not_connected_kvm_count += 1;
+                    // 先尝试断电重启，仍然没有数据再提示操作员
+                    if not_connected_kvm_count >= NOT_CONNECTED_KVM_COUNT_THRESHOLD && hard_reset_count < HARD_RESET_MAX_COUNT {
+                        hard_reset_count += 1;
+                        log(&format!("未检测到KVM, 第{}次断电重启", hard_reset_count));
+                        if power_cycle(POWER_CYCLE_OFF_MS).await {
+                            not_connected_kvm_count = 0;
+                            continue;
+                        }
+                    }
                     if not_connected_kvm_count >= NOT_CONNECTED_KVM_COUNT_THRESHOLD {
                         // 未连接KVM超过10次，同步弹窗提示
                         set_step_status(app_handle.clone(), "wait_connection", AppTestStatus::Failed);
@@ -183,7 +220,7 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
                     log("已连接KVM，不慎进入BOOT（现在出现AXERA-UBOOT=>，输入boot\n）, 发送boot\n");
                     set_step_status(app_handle.clone(), "wait_connection", AppTestStatus::Success);
                     set_step_status(app_handle.clone(), "wait_boot", AppTestStatus::Testing);
-                    serial_send("boot\n").await;
+                    boot().await;
                     std::thread::sleep(Duration::from_millis(100));
                     app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM
                 }
@@ -193,12 +230,67 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
                     set_step_status(app_handle.clone(), "wait_boot", AppTestStatus::Testing);
                     let patterns = ["login"];
                     let result = detect_serial_string(&patterns, 30000, 10).await;
+                    // 开机过程中出现内核崩溃/eMMC错误/看门狗复位等故障，或多次复位仍无法开机，作为开机不良处理
+                    let mut boot_defect = boot_defect_message();
+                    if boot_defect.is_none() && result == "UNMATCHED" && hard_reset_count >= HARD_RESET_MAX_COUNT {
+                        boot_defect = Some("多次复位后仍未出现登录提示".to_string());
+                    }
+                    if let Some(defect) = boot_defect {
+                        log(&format!("开机日志检测到故障: {}", defect));
+                        set_step_status(app_handle.clone(), "wait_boot", AppTestStatus::Failed);
+                        let response = show_dialog_and_wait(app_handle.clone(), format!("⚠️ 开机异常：{}", defect), vec![
+                            serde_json::json!({ "text": "复位后再次检测" }),
+                            serde_json::json!({ "text": "串口恢复U-Boot/DTB" }),
+                            serde_json::json!({ "text": "直接打印不良" })
+                        ]);
+                        if response == "直接打印不良" {
+                            log("用户选择了直接打印不良");
+                            save_defect_record(&format!("开机异常：{}", defect));
+                            // 生成错误图片
+                            add_error_msg(&format!("开机异常：{}，请检查eMMC焊接及固件 | ", defect));
+
+                            let error_msg = get_error_msg();
+                            if !error_msg.is_empty() {
+                                log(&format!("测试过程中出现错误: {}", error_msg));
+                                // 生成错误图片
+                                let img = generate_defects_image_with_params(&error_msg);
+                                if PRINTER_ENABLE {
+                                    if let Err(e) = print_image(&img, Some(TARGET_PRINTER)) {
+                                        log(&format!("打印图像失败: {}", e));
+                                        // #
+                                    }
+                                }
+                            }
+                            // 等待弹窗消失500ms
+                            std::thread::sleep(Duration::from_millis(500));
+                            app_step1_status = AppStepStatus::Finished;  // 跳转到结束
+                        } else if response == "串口恢复U-Boot/DTB" {
+                            log("用户选择了串口恢复固件");
+                            // 等待弹窗消失500ms
+                            std::thread::sleep(Duration::from_millis(500));
+                            app_step1_status = AppStepStatus::Recovering;  // 进入U-Boot恢复固件
+                        } else {
+                            log("用户选择了复位后再次检测");
+                            // 等待弹窗消失500ms
+                            std::thread::sleep(Duration::from_millis(500));
+                            let _ = pulse_reset(ResetTiming::default()).await;
+                            app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM
+                        }
+                        continue;
+                    }
                     match result.as_str() {
                         "login" => {
                             app_step1_status = AppStepStatus::BootedLogin;  // 已连接KVM，已开机（现在出现login）
                         }
                         "UNMATCHED" => {
-                            // 开着，但是超时了，也有可能关不上了，建议是拔掉再试一次，或者打印贴纸
+                            // 开着，但是超时了，可能卡死在启动过程中，复位后重新检测
+                            if hard_reset_count < HARD_RESET_MAX_COUNT {
+                                hard_reset_count += 1;
+                                log(&format!("开机超时, 第{}次复位KVM", hard_reset_count));
+                                if pulse_reset(ResetTiming::default()).await {
+                                    app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM
+                                }
+                            }
                             // ##
                         }
                         "NO-DATA" => {
@@ -213,26 +305,80 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
                         }
                     }
                 }
+                AppStepStatus::Recovering => {  // 进入U-Boot通过串口恢复固件
+                    log("进入U-Boot恢复固件");
+                    set_step_status(app_handle.clone(), "wait_boot", AppTestStatus::Repairing);
+                    let result = match enter_uboot().await {
+                        Ok(()) => recover_firmware().await,
+                        Err(e) => Err(e),
+                    };
+                    match result {
+                        Ok(()) => {
+                            log("固件恢复完成，重新启动");
+                            boot().await;
+                            hard_reset_count = 0;
+                            app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM，重新检测开机
+                        }
+                        Err(e) => {
+                            log(&format!("固件恢复失败: {}", e));
+                            set_step_status(app_handle.clone(), "wait_boot", AppTestStatus::Failed);
+                            let response = show_dialog_and_wait(app_handle.clone(), format!("⚠️ 串口恢复固件失败：{}", e), vec![
+                                serde_json::json!({ "text": "再次恢复" }),
+                                serde_json::json!({ "text": "直接打印不良" })
+                            ]);
+                            // 等待弹窗消失500ms
+                            std::thread::sleep(Duration::from_millis(500));
+                            if response == "直接打印不良" {
+                                log("用户选择了直接打印不良");
+                                save_defect_record(&format!("串口恢复固件失败：{}", e));
+                                // 生成错误图片
+                                add_error_msg("开机异常且串口恢复固件失败，请检查eMMC焊接 | ");
+
+                                let error_msg = get_error_msg();
+                                if !error_msg.is_empty() {
+                                    log(&format!("测试过程中出现错误: {}", error_msg));
+                                    // 生成错误图片
+                                    let img = generate_defects_image_with_params(&error_msg);
+                                    if PRINTER_ENABLE {
+                                        if let Err(e) = print_image(&img, Some(TARGET_PRINTER)) {
+                                            log(&format!("打印图像失败: {}", e));
+                                            // #
+                                        }
+                                    }
+                                }
+                                app_step1_status = AppStepStatus::Finished;  // 跳转到结束
+                            }
+                        }
+                    }
+                }
+                AppStepStatus::LoginFailed => {  // 串口登录被拒绝（密码错误或账户锁定）
+                    set_step_status(app_handle.clone(), "wait_boot", AppTestStatus::Failed);
+                    let printed = handle_login_failure(app_handle.clone(), login_failure, |msg| {
+                        add_error_msg(msg);
+                        get_error_msg()
+                    }).await;
+                    if printed {
+                        app_step1_status = AppStepStatus::Finished;  // 跳转到结束
+                        continue;
+                    }
+                    app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM，重新检测后再次登录
+                }
                 AppStepStatus::BootedLogin => {  // 已连接KVM，已开机（现在出现login）
                     log("已连接KVM，已开机（现在出现login）, 输入root密码");
                     set_step_status(app_handle.clone(), "wait_connection", AppTestStatus::Success);
                     set_step_status(app_handle.clone(), "wait_boot", AppTestStatus::Testing);
-                    if ! execute_command_and_wait("root\n", "Password", 1000).await {
-                        app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM
-                        continue;
-                    }
-                    log("输入root密码成功, 输入sipeed密码");
-                    if ! execute_command_and_wait("sipeed\n", "Welcome", 1000).await {
-                        app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM
-                        continue;
-                    }
-                    // 等待一部分初始信息
-                    std::thread::sleep(Duration::from_millis(100));
-                    log("登录成功, 发送回车等待:~#");
-                    if ! execute_command_and_wait("\n", ":~#", 1000).await { 
-                        app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM
+                    let credentials = get_credentials();
+                    if let Err(failure) = login_script(&credentials.username, &credentials.password).run().await {
+                        log(&format!("登录失败: {}", failure));
+                        login_failure = LoginFailure::from_script(&failure);
+                        if login_failure == LoginFailure::NoResponse {
+                            app_step1_status = AppStepStatus::ConnectedNoKVM;  // 未连接KVM
+                        } else {
+                            app_step1_status = AppStepStatus::LoginFailed;  // 登录被拒绝
+                        }
                         continue;
                     }
+                    log("登录成功");
                     app_step1_status = AppStepStatus::LoggedIn;  // 已连接KVM，已登录（现在出现:~#）
                 }
                 AppStepStatus::LoggedIn => {  // 已连接KVM，已登录（现在出现:~#）
@@ -244,28 +390,11 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
                     log("正在开启ssh服务...");
                     let _ = execute_command_and_wait("sudo systemctl start sshd.service\n", "#", 2000).await;
                     if STATIC_IP_ENABLE {
-                        if ! execute_command_and_wait("pkill dhclient\n", ":~#", 1000).await { 
-                            log("关闭DHCP超时");
-                            // ##
+                        // 关闭DHCP，设置静态IP并配置对方路由
+                        log(&format!("设置静态IP: {}, 对方: {}", current_target_ip, current_static_ip));
+                        if let Err(failure) = static_ip_script(&current_target_ip, &current_static_ip, false).run().await {
+                            log(&format!("设置静态IP失败: {}", failure));
                         }
-                        // 清空ip
-                        log("清空ip");
-                        if ! execute_command_and_wait("ip addr flush dev eth0\n", ":~#", 1000).await {
-                            log("清空ip超时");
-                            // ##
-                        };
-                        // 设置静态IP
-                        log(&format!("设置静态IP: {}", current_static_ip));
-                        if ! execute_command_and_wait(&format!("ip addr add {} dev eth0\n", current_target_ip), ":~#", 1000).await { 
-                            log("设置静态IP超时");
-                            // ##
-                        };
-                        // 配置对方路由
-                        log(&format!("配置对方路由: {}", current_static_ip));
-                        if ! execute_command_and_wait(&format!("ip route add {} dev eth0\n", current_static_ip), ":~#", 1000).await { 
-                            log("配置对方路由超时");
-                            // ##
-                        };
                         while ! execute_command_and_wait(&format!("ping -c 1 {}\n", current_static_ip), "1 received", 1000).await {
                             get_ip_retry_count += 1;
                             if get_ip_retry_count >= GET_IP_MAX_RETRY_COUNT {
@@ -274,9 +403,18 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
                                 // 弹窗选择打印不良/再次检测
                                 let response = show_dialog_and_wait(app_handle.clone(), "获取IP失败，是否再检测一遍".to_string(), vec![
                                     serde_json::json!({ "text": "再次检测" }),
+                                    serde_json::json!({ "text": "通过串口继续测试（跳过以太网）" }),
                                     serde_json::json!({ "text": "否，直接打印不良" })
                                 ]);
-                                if response == "否，直接打印不良" {
+                                if response == "通过串口继续测试（跳过以太网）" {
+                                    // 以太网记为不良，其余项目通过串口下发产测包并执行
+                                    log("用户选择了通过串口继续测试");
+                                    add_error_msg("以太网连接异常，请检查网线连接或PHY部分焊接 | ");
+                                    set_serial_fallback(true);
+                                    // 等待弹窗消失500ms
+                                    std::thread::sleep(Duration::from_millis(500));
+                                    break;
+                                } else if response == "否，直接打印不良" {
                                     log("用户选择了直接打印不良");
                                     // 生成错误图片
                                     add_error_msg("以太网连接异常，请检查网线连接或PHY部分焊接 | ");
@@ -305,36 +443,22 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
                                 }
                             }
                             set_step_status(app_handle.clone(), "get_ip", AppTestStatus::Repairing);
-                            log("需要发送CTRL C确保退出ping");
-                            if ! execute_command_and_wait("\x03", ":~#", 1000).await {
-                                log("CTRL+C超时");
-                                // ##
-                            };
-                            // 清空ip
-                            log("清空ip");
-                            if ! execute_command_and_wait("ip addr flush dev eth0\n", ":~#", 1000).await {
-                                log("清空ip超时");
-                                // ##
-                            };
-                            // 设置静态IP
-                            log("设置静态IP");
-                            if ! execute_command_and_wait(&format!("ip addr add {} dev eth0\n", current_target_ip), ":~#", 1000).await { 
-                                log("设置静态IP超时");
-                                // ##
-                            };
-                            // 配置对方路由
-                            log(&format!("配置对方路由: {}", current_static_ip));
-                            if ! execute_command_and_wait(&format!("ip route add {} dev eth0\n", current_static_ip), ":~#", 1000).await { 
-                                log("配置对方路由超时");
-                                // ##
-                            };
+                            log("需要发送CTRL C确保退出ping, 重新设置静态IP");
+                            if let Err(failure) = static_ip_script(&current_target_ip, &current_static_ip, true).run().await {
+                                log(&format!("设置静态IP失败: {}", failure));
+                            }
                         }
                     }
-                    set_step_status(app_handle.clone(), "get_ip", AppTestStatus::Success);
-                    set_target_ip(app_handle.clone(), &current_target_ip);
                     if app_step1_status == AppStepStatus::Finished {
                         continue;
                     }
+                    if is_serial_fallback() {
+                        set_step_status(app_handle.clone(), "get_ip", AppTestStatus::Failed);
+                        app_step1_status = AppStepStatus::DownloadFile;  // 通过串口下发产测包
+                        continue;
+                    }
+                    set_step_status(app_handle.clone(), "get_ip", AppTestStatus::Success);
+                    set_target_ip(app_handle.clone(), &current_target_ip);
                     app_step1_status = AppStepStatus::DownloadFile;  // 下载文件中
                 }
                 AppStepStatus::DownloadFile => {  // 下载文件中
@@ -360,6 +484,7 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
 
                     let _ = ssh_execute_command("rm -rf /root/NanoKVM_Pro_Testing").await;
 
+                    package_push = None;
                     loop {
                         download_retry_count += 1;
                         if download_retry_count > DOWNLOAD_MAX_RETRY_COUNT {
@@ -369,7 +494,32 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
                             break;
                         }
                         
-                        let _ = ssh_execute_command("curl \"http://172.168.100.1:8080/download\" --output /root/test.tar -s -o /dev/null -w \"speed: %{speed_download} B/s\\n\"").await;
+                        if is_serial_fallback() {
+                            // 网口不可用，通过串口推送产测包，校验通过后才会出现test.tar
+                            if let Err(e) = push_app_file("/root/test.tar").await {
+                                log(&format!("串口推送产测包失败: {}", e));
+                            }
+                        } else {
+                            let started = Instant::now();
+                            match ssh_upload_file(&get_app_file_path(), "/root/test.tar", &SshOptions::timeout(UPLOAD_TIMEOUT_MS)).await {
+                                Ok(size) => {
+                                    let elapsed = started.elapsed();
+                                    package_push = Some(SpeedMeasurement {
+                                        item: PACKAGE_PUSH_ITEM.to_string(),
+                                        server_mbps: Some(size as f64 * 8.0 / elapsed.as_secs_f64().max(0.000_001) / 1_000_000.0),
+                                        server_bytes: size,
+                                        server_ms: elapsed.as_millis() as u64,
+                                        method: "sftp".to_string(),
+                                        ..Default::default()
+                                    });
+                                }
+                                Err(e) => {
+                                    // SFTP推送失败时退回由板卡从本机下载
+                                    log(&format!("SFTP推送产测包失败: {}，改用HTTP下载", e));
+                                    let _ = ssh_execute_command_with(&format!("curl \"{}\" --output /root/test.tar -s -o /dev/null -w \"speed: %{{speed_download}} B/s\\n\"", server_url(&current_static_ip, "/download")), &SshOptions::timeout(UPLOAD_TIMEOUT_MS)).await;
+                                }
+                            }
+                        }
 
                         let (ls_success, _) = ssh_execute_command_check_success("ls /root/test.tar", "test.tar").await.unwrap_or((false, String::new()));
                         if ls_success {
@@ -400,75 +550,61 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
                     match ssh_execute_command("/root/NanoKVM_Pro_Testing/test_sh/01_test_hardware.sh").await {
                         Ok(output) => {
                             // log(&format!("输出: \n{}", output));
-                            // 判断是否存在：“弹窗内容”，获取"弹窗内容："到"\n"之间的内容
-                            if let Some(start) = output.find("弹窗内容：") {
-                                let content_start = start + "弹窗内容：".len();
-                                let remaining = &output[content_start..];
-                                if let Some(end) = remaining.find('\n') {
-                                    let popup_content = &remaining[..end].trim();
-                                    log(&format!("弹窗内容: {}", popup_content));
-                                    // 弹窗
-                                    let response = show_dialog_and_wait(app_handle.clone(), popup_content.to_string(), vec![
-                                        serde_json::json!({ "text": "YES" }),
-                                        serde_json::json!({ "text": "NO" })
-                                    ]);
-                                    if response == "NO" {
-                                        log("用户选择了NO");
-                                        // 获取哪些硬件已经通过产测
-                                        // #
-                                    } else {
-                                        log("用户选择了YES，清除已测试硬件记录");
-                                        let _ = ssh_execute_command("/root/NanoKVM_Pro_Testing/test_sh/02_rm_tested.sh").await.unwrap();
-                                    }
-                                    // 等待弹窗消失500ms
-                                    std::thread::sleep(Duration::from_millis(500));
+                            let output = ScriptOutput::parse(&output);
+                            // 判断是否需要弹窗：结构化结果的prompt，旧格式为"弹窗内容："到"\n"之间的内容
+                            if let Some(popup_content) = output.value("detect_hardware", "prompt", "弹窗内容：") {
+                                log(&format!("弹窗内容: {}", popup_content));
+                                // 弹窗
+                                let response = show_dialog_and_wait(app_handle.clone(), popup_content, vec![
+                                    serde_json::json!({ "text": "YES" }),
+                                    serde_json::json!({ "text": "NO" })
+                                ]);
+                                if response == "NO" {
+                                    log("用户选择了NO");
+                                    // 获取哪些硬件已经通过产测
+                                    // #
+                                } else {
+                                    log("用户选择了YES，清除已测试硬件记录");
+                                    let _ = ssh_execute_command("/root/NanoKVM_Pro_Testing/test_sh/02_rm_tested.sh").await.unwrap();
                                 }
+                                // 等待弹窗消失500ms
+                                std::thread::sleep(Duration::from_millis(500));
                             }
                             // 获取当前板卡的类型
-                            if let Some(start) = output.find("当前板卡的类型为：") {
-                                let content_start = start + "当前板卡的类型为：".len();
-                                let remaining = &output[content_start..];
-                                if let Some(end) = remaining.find('\n') {
-                                    let mut hardware_type = remaining[..end].trim().to_string();
-                                    if !hardware_type.contains("-") {
-                                        if hardware_type == "Unknown" {
-                                            log("当前板卡的类型为: Unknown, 弹窗判断");
-                                            let result = show_dialog_and_wait(app_handle.clone(), "⚠️ 可能试屏幕接触不良导致无法判断版本，请手动选择：".to_string(), vec![
-                                                serde_json::json!({ "text": "ATX" }),
-                                                serde_json::json!({ "text": "Desk" })
-                                            ]);
-                                            hardware_type = result;
-                                            auto_type = false;
-                                            // 等待弹窗消失500ms
-                                            std::thread::sleep(Duration::from_millis(500));
-                                        }
-                                        // 获取当前板卡的类型
-                                        let hardware_version_str = get_config_str("testing", "board_version");
-                                        // hardware_type = hardware_type-hardware_version_str
-                                        hardware_type = format!("{}-{}", hardware_type, hardware_version_str.unwrap_or_default());
-                                    }
-                                    log(&format!("RUST检测到当前板卡的类型为: {}", hardware_type));
-                                    set_current_hardware(app_handle.clone(), &hardware_type);
-                                    target_name = format!("NanoKVM-{}", hardware_type);
-                                    // NanoKVM_Pro (Desk-B) NeaR00293
-                                    target_type = format!("NanoKVM_Pro ({}) ", hardware_type);
-                                    if target_type.contains("ATX") {
-                                        target_hardware_type = HardwareType::Atx;
-                                    } else {
-                                        target_hardware_type = HardwareType::Desk;
+                            if let Some(mut hardware_type) = output.value("detect_hardware", "board_type", "当前板卡的类型为：") {
+                                if !hardware_type.contains("-") {
+                                    if hardware_type == "Unknown" {
+                                        log("当前板卡的类型为: Unknown, 弹窗判断");
+                                        let result = show_dialog_and_wait(app_handle.clone(), "⚠️ 可能试屏幕接触不良导致无法判断版本，请手动选择：".to_string(), vec![
+                                            serde_json::json!({ "text": "ATX" }),
+                                            serde_json::json!({ "text": "Desk" })
+                                        ]);
+                                        hardware_type = result;
+                                        auto_type = false;
+                                        // 等待弹窗消失500ms
+                                        std::thread::sleep(Duration::from_millis(500));
                                     }
+                                    // 获取当前板卡的类型
+                                    let hardware_version_str = get_config_str("testing", "board_version");
+                                    // hardware_type = hardware_type-hardware_version_str
+                                    hardware_type = format!("{}-{}", hardware_type, hardware_version_str.unwrap_or_default());
+                                }
+                                log(&format!("RUST检测到当前板卡的类型为: {}", hardware_type));
+                                set_current_hardware(app_handle.clone(), &hardware_type);
+                                target_name = format!("NanoKVM-{}", hardware_type);
+                                // NanoKVM_Pro (Desk-B) NeaR00293
+                                target_type = format!("NanoKVM_Pro ({}) ", hardware_type);
+                                if target_type.contains("ATX") {
+                                    target_hardware_type = HardwareType::Atx;
+                                } else {
+                                    target_hardware_type = HardwareType::Desk;
                                 }
                             }
                             // 获取或生成当前板卡的串号
-                            if let Some(start) = output.find("当前板卡的串号为：") {
-                                let content_start = start + "当前板卡的串号为：".len();
-                                let remaining = &output[content_start..];
-                                if let Some(end) = remaining.find('\n') {
-                                    let serial_number = &remaining[..end].trim();
-                                    log(&format!("RUST检测到当前板卡的串号为: {}", serial_number));
-                                    set_target_serial(app_handle.clone(), serial_number);
-                                    target_serial = serial_number.to_string();
-                                }
+                            if let Some(serial_number) = output.value("detect_hardware", "serial", "当前板卡的串号为：") {
+                                log(&format!("RUST检测到当前板卡的串号为: {}", serial_number));
+                                set_target_serial(app_handle.clone(), &serial_number);
+                                target_serial = serial_number;
                             } else {
                                 // 生成新串号
                                 let serial_number = create_serial_number(&target_name).unwrap_or_default();
@@ -477,22 +613,35 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
                                 target_serial = serial_number;
                             }
                             // 获取当前板卡是否有wifi模块
-                            if output.contains("当前板卡有wifi模块") {
+                            if output.flag("detect_hardware", "wifi", "当前板卡有wifi模块") {
                                 log("RUST检测到当前板卡有wifi模块");
                                 wifi_exist = true;
                             }
                             // 获取soc id
-                            if let Some(start) = output.find("SOC ID: ") {
-                                let content_start = start + "SOC ID: ".len();
-                                let remaining = &output[content_start..];
-                                if let Some(end) = remaining.find('\n') {
-                                    soc_id = remaining[..end].trim().to_string();
-                                    log(&format!("RUST检测到当前板卡的soc id为: {}", soc_id));
-                                }
+                            if let Some(id) = output.value("detect_hardware", "soc_id", "SOC ID: ") {
+                                soc_id = id;
+                                log(&format!("RUST检测到当前板卡的soc id为: {}", soc_id));
                             }
                             let _ = set_test_status(&target_serial, "soc_uid", &soc_id);
                             let _ = set_test_status(&target_serial, "hardware", &target_type);
                             let _ = set_test_status(&target_serial, "wifi_exist", &wifi_exist.to_string());
+                            link_console_capture(&target_serial);
+                            link_boot_reports(&target_serial);
+                            link_transcripts(&target_serial);
+                            // 产测包的传输速度：SFTP推送记为本机测得的大小和用时，板卡HTTP下载记为服务端测量值，两者各自保存
+                            let http_download = take_server_measurement(DUT_DOWNLOAD_ITEM).map(|download| SpeedMeasurement {
+                                item: DUT_DOWNLOAD_ITEM.to_string(),
+                                server_mbps: Some(download.mbps),
+                                server_bytes: download.bytes,
+                                server_ms: download.millis,
+                                method: "http".to_string(),
+                                ..Default::default()
+                            });
+                            for measurement in package_push.take().into_iter().chain(http_download) {
+                                if let Err(e) = add_speed_measurement(&target_serial, &measurement) {
+                                    log(&format!("保存{}速度失败: {}", measurement.item, e));
+                                }
+                            }
                         }
                         Err(e) => {
                             log(&format!("SSH命令执行失败: {}", e));
@@ -508,9 +657,9 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
                     log("检查eMMC中");
                     set_step_status(app_handle.clone(), "emmc_test", AppTestStatus::Testing);
 
-                    match ssh_execute_command("/root/NanoKVM_Pro_Testing/test_sh/03_test_emmc.sh").await {
+                    match ssh_execute_command_with("/root/NanoKVM_Pro_Testing/test_sh/03_test_emmc.sh", &SshOptions::timeout(EMMC_TEST_TIMEOUT_MS)).await {
                         Ok(output) => {
-                            if output.contains("eMMC test passed") {
+                            if ScriptOutput::parse(&output).passed("emmc_test", "eMMC test passed") {
                                 log("eMMC测试通过");
                                 set_step_status(app_handle.clone(), "emmc_test", AppTestStatus::Success);
                                 app_step1_status = AppStepStatus::Printing;
@@ -652,6 +801,8 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
                         ]);
                     }
                     std::thread::sleep(Duration::from_millis(500));
+                    stop_console_capture();
+                    set_serial_fallback(false);
                     break;
                 }
             }
@@ -659,3 +810,4 @@ pub fn spawn_app_step1_task(app_handle: AppHandle, ssid: String, password: Strin
         }
     })
 }
+
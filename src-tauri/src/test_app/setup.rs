@@ -2,7 +2,7 @@ use std::thread;
 use tokio::time::sleep;
 use tauri::async_runtime::{spawn};
 use std::time::Duration;
-use crate::function::save::{init_appdata, get_config_str, is_app_folder_empty};
+use crate::function::save::{init_appdata, get_config_str, get_server_config, get_app_dir, is_app_folder_empty};
 use crate::function::serial::{is_usb_tool_connected};
 use crate::function::printer::is_printer_connected;
 use crate::function::camera::{get_camera_status, CameraStatus};
@@ -35,7 +35,6 @@ pub fn spawn_setup_task(app_handle: AppHandle) {
         log("初始化线程已启动");
         let mut ap_ssid = String::new();
         let mut ap_password = String::new();
-        let static_ip = "172.168.100.1";
         let target_ip = "172.168.100.2";
         // let static_ip = "192.168.1.7";
         // let target_ip = "192.168.1.15";
@@ -57,6 +56,9 @@ pub fn spawn_setup_task(app_handle: AppHandle) {
             }
         }
 
+        // 本机静态IP即板卡访问文件服务器的地址，来自配置 [server]
+        let static_ip = get_server_config().host;
+
         // 延迟2秒后推送初始测试数据，确保前端已经准备好
         // std::thread::sleep(std::time::Duration::from_secs(2));
         sleep(Duration::from_secs(2)).await;
@@ -73,7 +75,8 @@ pub fn spawn_setup_task(app_handle: AppHandle) {
         }
         // 检测是否存在APP测试文件
         if is_app_folder_empty() {
-            config_warning_msg.push_str(&format!("⚠️ 测试数据文件夹为空，请在下面的位置存放产测软件：\n\"C:/Users/{}/AppData/Local/NanoKVM-Testing/app\"\n", std::env::var("USERNAME").unwrap()));
+            let app_dir = get_app_dir().map(|path| path.display().to_string()).unwrap_or_default();
+            config_warning_msg.push_str(&format!("⚠️ 测试数据文件夹为空，请在下面的位置存放产测软件：\n\"{}\"\n", app_dir));
         }
         
         // 如果有问题就弹窗提示
@@ -97,7 +100,7 @@ pub fn spawn_setup_task(app_handle: AppHandle) {
         // 初始化静态IP
         if STATIC_IP_ENABLE {
             log("初始化静态IP");
-            if let Err(e) = set_static_ip_for_testing(static_ip) {
+            if let Err(e) = set_static_ip_for_testing(&static_ip) {
                 log(&format!("静态IP配置失败: {}", e));
             }
         }
@@ -165,7 +168,7 @@ pub fn spawn_setup_task(app_handle: AppHandle) {
         }
         // serial_data_management_task(app_handle.clone());
         loop {
-            let app_step_handle = spawn_app_step1_task(app_handle.clone(), ap_ssid.clone(), ap_password.clone(), static_ip.to_string(), target_ip.to_string());
+            let app_step_handle = spawn_app_step1_task(app_handle.clone(), ap_ssid.clone(), ap_password.clone(), static_ip.clone(), target_ip.to_string());
             app_step_handle.await.unwrap();
         }
     });
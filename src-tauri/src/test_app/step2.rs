@@ -3,12 +3,14 @@ use tauri::async_runtime::{spawn, JoinHandle};
 use tauri::AppHandle;
 use tokio::time::sleep;
 use crate::function::update_state::{AppTestStatus, set_step_status, all_step_status_is_success, add_error_msg, get_error_msg};
-use crate::function::ssh::{ssh_execute_command_check_success, ssh_execute_command};
+use crate::function::ssh::{ssh_execute_command_check_success, ssh_execute_command_with, ssh_execute_command_stream, ssh_download, is_serial_fallback, SshCancel, SshOptions};
 use crate::function::camera::{get_camera_status, CameraStatus};
-use crate::function::save::{get_config_str, set_test_status, cp_to_unuploaded};
+use crate::function::save::{get_config_str, get_server_config, set_test_status, cp_to_unuploaded, get_save_dir, get_record_dir, add_artifacts, add_speed_measurement, SpeedMeasurement};
 use crate::function::dialog_test::{show_dialog_and_wait};
 use crate::function::printer::{generate_defects_image_with_params, print_image, PRINTER_ENABLE, TARGET_PRINTER};
 use crate::function::hdmi::if_two_monitor;
+use crate::function::script_result::ScriptOutput;
+use crate::function::server::{take_server_measurement, take_net_test_result, server_url};
 
 const HDMI_IO_TEST_MAX_RETRY_COUNT: u64 = 5;
 const HDMI_VIN_TEST_MAX_RETRY_COUNT: u64 = 5;
@@ -20,6 +22,20 @@ const ETH_DOWNLOAD_TEST_MAX_RETRY_COUNT: u64 = 5;
 const ETH_UPLOAD_TEST_MAX_RETRY_COUNT: u64 = 5;
 const WIFI_CONNECT_MAX_RETRY_COUNT: u64 = 10;
 const IO_TEST_MAX_RETRY_COUNT: u64 = 5;
+const FILE_UPDATE_MAX_RETRY_COUNT: u64 = 5;
+const FILE_UPDATE_TIMEOUT_MS: u64 = 300_000;    // 单次固件/应用更新脚本的超时时间
+const PULL_TIMEOUT_MS: u64 = 60_000;            // 取回单个目录的超时时间
+const COMMAND_TIMEOUT_MS: u64 = 30_000;         // 普通测试脚本和命令的超时时间
+const WIFI_CONNECT_TIMEOUT_MS: u64 = 60_000;    // 连接WiFi并获取IP的超时时间
+const SPEED_TEST_TIMEOUT_MS: u64 = 120_000;     // 单次网速或丢包测试的超时时间
+const PANEL_TEST_TIMEOUT_MS: u64 = 90_000;      // 屏幕、触摸和旋钮测试的超时时间，脚本最多等待60秒
+const SERVICE_TIMEOUT_MS: u64 = 600_000;        // 常驻测试服务的超时时间，测试结束时会主动取消
+
+// 测试结束时从板卡取回的目录：(板卡路径, 记录目录下的名称)
+const PULL_ARTIFACTS: [(&str, &str); 2] = [
+    ("/root/factory_test/done", "done"),    // 各测试项完成标记
+    ("/root/log", "log"),                   // 脚本日志和dmesg
+];
 
 // 枚举atx/desk：
 #[derive(PartialEq, Clone)]
@@ -39,13 +55,16 @@ fn log(msg: &str) {
 }
 
 // 自动多次测试
-async fn auto_test_with_retry(app_handle: &AppHandle, test_name: &str, test_cmd: &str, success_msg: &str, max_retry: u64) -> (bool, String) {
+async fn auto_test_with_retry(app_handle: &AppHandle, test_name: &str, test_cmd: &str, success_msg: &str, max_retry: u64, timeout_ms: u64) -> (bool, String) {
     let mut retry_count = 0;
     set_step_status(app_handle.clone(), test_name, AppTestStatus::Testing);
     let mut last_output = String::new();
     while retry_count < max_retry {
         log(&format!("{} 测试中...", test_name));
-        let (success, output) = ssh_execute_command_check_success(test_cmd, success_msg).await.unwrap_or((false, String::new()));
+        let (success, output) = match ssh_execute_command_with(test_cmd, &SshOptions::timeout(timeout_ms)).await {
+            Ok(output) => (ScriptOutput::parse(&output).passed(test_name, success_msg), output),
+            Err(_) => (false, String::new()),
+        };
         last_output = output.clone();
         if success {
             log(&format!("{} 成功", test_name));
@@ -62,42 +81,139 @@ async fn auto_test_with_retry(app_handle: &AppHandle, test_name: &str, test_cmd:
     (false, last_output)
 }
 
-pub fn spawn_step2_file_update(app_handle: AppHandle) -> JoinHandle<()> {
-    log("进入step2_file_update");
-    spawn(async move {
-        log("更新KVM文件");
-        // dtb
-        set_step_status(app_handle.clone(), "dtb", AppTestStatus::Testing);
-        let mut dtb_update_success = false;
-
-        while !dtb_update_success {
-            log("dtb文件更新中...");
-            dtb_update_success = ssh_execute_command_check_success("/root/NanoKVM_Pro_Testing/test_sh/04_update_file.sh dtb", "dtb done").await.map(|(success, _)| success).unwrap_or(false);
-            set_step_status(app_handle.clone(), "dtb", AppTestStatus::Repairing);
-        }
-        set_step_status(app_handle.clone(), "dtb", AppTestStatus::Success);
+// 生成网速测试的上传和下载命令：tcp使用内置网络测试服务，http使用curl访问文件服务器
+// script为07/08测试脚本，prefix为 "eth" 或 "wifi"
+fn speed_test_commands(script: &str, prefix: &str, ip: &str, upload_threshold: &str, download_threshold: &str, download_size_mb: &str) -> (String, String) {
+    if get_config_str("testing", "net_test").unwrap_or("tcp".to_string()) == "http" {
+        (
+            format!("{} upload {} \"{}\"", script, upload_threshold, server_url(ip, &format!("/upload?item={}_upload_test", prefix))),
+            format!("{} download {} \"{}\"", script, download_threshold, server_url(ip, &format!("/download_small?size_mb={}&item={}_download_test", download_size_mb, prefix))),
+        )
+    } else {
+        let seconds = get_config_str("testing", "net_test_seconds").unwrap_or("5".to_string());
+        let port = get_server_config().net_test_port;
+        (
+            format!("{} tcp upload {} {} {}", script, ip, port, seconds),
+            format!("{} tcp download {} {} {}", script, ip, port, seconds),
+        )
+    }
+}
 
-        // uboot
-        set_step_status(app_handle.clone(), "uboot", AppTestStatus::Testing);
-        let mut uboot_update_success = false;
+// UDP丢包和抖动测试，只在使用内置网络测试服务时进行，结果写入板卡记录
+async fn udp_test(serial: &str, script: &str, prefix: &str, ip: &str) -> bool {
+    if get_config_str("testing", "net_test").unwrap_or("tcp".to_string()) == "http" {
+        return true;
+    }
+    let test_name = format!("{}_udp_test", prefix);
+    let packets = get_config_str("testing", "udp_packets").unwrap_or("200".to_string());
+    let command = format!("{} udp {} {} {}", script, ip, get_server_config().net_test_port, packets);
+    log(&format!("{}测试命令：{}", test_name, command));
+
+    let _ = take_net_test_result(&test_name);
+    let passed = match ssh_execute_command_with(&command, &SshOptions::timeout(SPEED_TEST_TIMEOUT_MS)).await {
+        Ok(output) => ScriptOutput::parse(&output).passed(&test_name, "UDP test passed"),
+        Err(e) => {
+            log(&format!("{}失败: {}", test_name, e));
+            false
+        }
+    };
+    if let Some(net) = take_net_test_result(&test_name) {
+        let measurement = SpeedMeasurement {
+            item: test_name.clone(),
+            method: "udp".to_string(),
+            server_ms: net.millis,
+            loss_percent: net.loss_percent,
+            jitter_ms: net.jitter_ms,
+            ..Default::default()
+        };
+        log(&format!("{} 丢包 {:?}%, 抖动 {:?} ms", test_name, measurement.loss_percent, measurement.jitter_ms));
+        if let Err(e) = add_speed_measurement(serial, &measurement) {
+            log(&format!("保存网速测试结果失败: {}", e));
+        }
+    }
+    passed
+}
 
-        while !uboot_update_success {
-            log("uboot文件更新中...");
-            uboot_update_success = ssh_execute_command_check_success("/root/NanoKVM_Pro_Testing/test_sh/04_update_file.sh uboot", "uboot done").await.map(|(success, _)| success).unwrap_or(false);
-            set_step_status(app_handle.clone(), "uboot", AppTestStatus::Repairing);
+// 网速测试：清除上一次的服务端测量值后执行，结束后把客户端和服务端的测量值写入板卡记录
+async fn speed_test_with_retry(app_handle: &AppHandle, serial: &str, test_name: &str, test_cmd: &str, success_msg: &str, threshold: &str, max_retry: u64) -> (bool, String) {
+    let _ = take_server_measurement(test_name);
+    let _ = take_net_test_result(test_name);
+    let (result, output) = auto_test_with_retry(app_handle, test_name, test_cmd, success_msg, max_retry, SPEED_TEST_TIMEOUT_MS).await;
+
+    let measurement = match take_net_test_result(test_name) {
+        // 内置网络测试服务：客户端速度由脚本在查询结果时上报
+        Some(net) => SpeedMeasurement {
+            item: test_name.to_string(),
+            threshold_mbps: threshold.parse().unwrap_or(0.0),
+            client_mbps: net.client_mbps,
+            server_mbps: Some(net.mbps()),
+            server_bytes: net.rx_bytes + net.tx_bytes,
+            server_ms: net.millis,
+            method: "tcp".to_string(),
+            ..Default::default()
+        },
+        None => {
+            // 客户端速度为curl测得的B/s，旧格式取"实际上传速度: "或"实际下载速度: "之后的数字
+            let legacy_prefix = if test_name.ends_with("upload_test") { "实际上传速度: " } else { "实际下载速度: " };
+            let client_mbps = ScriptOutput::parse(&output).value(test_name, "speed_bps", legacy_prefix)
+                .and_then(|speed| speed.split_whitespace().next().and_then(|speed| speed.parse::<f64>().ok()))
+                .map(|speed| speed * 8.0 / 1_000_000.0);
+            let server = take_server_measurement(test_name);
+            SpeedMeasurement {
+                item: test_name.to_string(),
+                threshold_mbps: threshold.parse().unwrap_or(0.0),
+                client_mbps,
+                server_mbps: server.map(|server| server.mbps),
+                server_bytes: server.map_or(0, |server| server.bytes),
+                server_ms: server.map_or(0, |server| server.millis),
+                method: "http".to_string(),
+                ..Default::default()
+            }
         }
-        set_step_status(app_handle.clone(), "uboot", AppTestStatus::Success);
+    };
+    log(&format!("{} 客户端 {:?} Mbps, 服务端 {:?} Mbps", test_name, measurement.client_mbps, measurement.server_mbps));
+    if let Err(e) = add_speed_measurement(serial, &measurement) {
+        log(&format!("保存网速测试结果失败: {}", e));
+    }
+    (result, output)
+}
 
-        // kernel
-        set_step_status(app_handle.clone(), "kernel", AppTestStatus::Testing);
-        let mut kernel_update_success = false;
+// 执行04_update_file.sh更新一项文件，超时或失败时重试，超过次数后记为失败
+async fn update_file_with_retry(app_handle: &AppHandle, test_name: &str, target: &str) -> bool {
+    // 通过串口测试时不更新固件，保留板卡原有的文件
+    if is_serial_fallback() {
+        log(&format!("以太网不可用，跳过{}文件更新", target));
+        set_step_status(app_handle.clone(), test_name, AppTestStatus::Failed);
+        return false;
+    }
+    set_step_status(app_handle.clone(), test_name, AppTestStatus::Testing);
+    let command = format!("/root/NanoKVM_Pro_Testing/test_sh/04_update_file.sh {}", target);
+    let done_msg = format!("{} done", target);
+    for retry_count in 0..FILE_UPDATE_MAX_RETRY_COUNT {
+        log(&format!("{}文件更新中...", target));
+        match ssh_execute_command_stream(app_handle, test_name, &command, &SshOptions::timeout(FILE_UPDATE_TIMEOUT_MS)).await {
+            Ok(output) if ScriptOutput::parse(&output).passed(test_name, &done_msg) => {
+                set_step_status(app_handle.clone(), test_name, AppTestStatus::Success);
+                return true;
+            }
+            Ok(_) => log(&format!("{}文件更新未完成，第 {} 次", target, retry_count + 1)),
+            Err(e) => log(&format!("{}文件更新失败，第 {} 次: {}", target, retry_count + 1, e)),
+        }
+        set_step_status(app_handle.clone(), test_name, AppTestStatus::Repairing);
+    }
+    set_step_status(app_handle.clone(), test_name, AppTestStatus::Failed);
+    add_error_msg(&format!("{}文件更新失败 | ", target));
+    false
+}
 
-        while !kernel_update_success {
-            log("kernel文件更新中...");
-            kernel_update_success = ssh_execute_command_check_success("/root/NanoKVM_Pro_Testing/test_sh/04_update_file.sh kernel", "kernel done").await.map(|(success, _)| success).unwrap_or(false);
-            set_step_status(app_handle.clone(), "kernel", AppTestStatus::Repairing);
+pub fn spawn_step2_file_update(app_handle: AppHandle) -> JoinHandle<()> {
+    log("进入step2_file_update");
+    spawn(async move {
+        log("更新KVM文件");
+        // dtb、uboot、kernel依次更新
+        for target in ["dtb", "uboot", "kernel"] {
+            update_file_with_retry(&app_handle, target, target).await;
         }
-        set_step_status(app_handle.clone(), "kernel", AppTestStatus::Success);
         
         // 等待1秒，确保文件更新完成
         sleep(Duration::from_secs(1)).await;
@@ -108,15 +224,7 @@ pub fn spawn_step2_app_install(app_handle: AppHandle) -> JoinHandle<()> {
     log("进入step2_app_install");
     spawn(async move {
         // app
-        set_step_status(app_handle.clone(), "app_install", AppTestStatus::Testing);
-        let mut app_update_success = false;
-
-        while !app_update_success {
-            log("app文件更新中...");
-            app_update_success = ssh_execute_command_check_success("/root/NanoKVM_Pro_Testing/test_sh/04_update_file.sh app", "app done").await.map(|(success, _)| success).unwrap_or(false);
-            set_step_status(app_handle.clone(), "app_install", AppTestStatus::Repairing);
-        }
-        set_step_status(app_handle.clone(), "app_install", AppTestStatus::Success);
+        update_file_with_retry(&app_handle, "app_install", "app").await;
         
         // 等待1秒，确保文件更新完成
         sleep(Duration::from_secs(1)).await;
@@ -149,10 +257,13 @@ pub fn spawn_step2_hdmi_testing(app_handle: AppHandle, target_type: &str, target
             return;
         }
 
-        // 先启动vin_test
-        spawn(async {
+        // 先启动vin_test，服务输出推送到采集测试项，HDMI测试结束后停止
+        let vin_app_handle = app_handle.clone();
+        let vin_cancel = SshCancel::new();
+        let vin_options = SshOptions::timeout(SERVICE_TIMEOUT_MS).cancel_by(&vin_cancel);
+        spawn(async move {
             log("启动vin_test测试服务");
-            let _ = ssh_execute_command("/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh start").await;
+            let _ = ssh_execute_command_stream(&vin_app_handle, "hdmi_capture_test", "/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh start", &vin_options).await;
             log("vin_test测试服务退出");
         });
 
@@ -167,16 +278,17 @@ pub fn spawn_step2_hdmi_testing(app_handle: AppHandle, target_type: &str, target
         set_step_status(app_handle.clone(), "hdmi_wait_connection", AppTestStatus::Success);
 
         // hdmi_io_test
-        let (hdmi_io_test_result, hdmi_io_test_output) = auto_test_with_retry(&app_handle, "hdmi_io_test", "/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh io", "HDMI IO test passed", HDMI_IO_TEST_MAX_RETRY_COUNT).await;
+        let (hdmi_io_test_result, hdmi_io_test_output) = auto_test_with_retry(&app_handle, "hdmi_io_test", "/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh io", "HDMI IO test passed", HDMI_IO_TEST_MAX_RETRY_COUNT, COMMAND_TIMEOUT_MS).await;
         if !hdmi_io_test_result {
             log(&format!("hdmi_io_test失败，输出: {}", hdmi_io_test_output));
-            if hdmi_io_test_output.contains("LT86102 RST 引脚异常") { lt86102_rst_io = false; }
-            if hdmi_io_test_output.contains("LT6911 RST 引脚异常") { lt6911_rst_io = false; }
-            if hdmi_io_test_output.contains("LT86102 RX 引脚异常") { lt86102_rx_io = false; }
-            if hdmi_io_test_output.contains("LT86102 TX 引脚异常") { lt86102_tx_io = false; }
-            if hdmi_io_test_output.contains("LT6911 INT 引脚异常") { lt6911_int_io = false; }
-            if hdmi_io_test_output.contains("LT6911 I2C 引脚异常") { lt6911_i2c_io = false; }
-            if hdmi_io_test_output.contains("LT86102 I2C 引脚异常") { lt86102_i2c_io = false; }
+            let output = ScriptOutput::parse(&hdmi_io_test_output);
+            if output.has_defect("hdmi_io_test", "LT86102_RST", "LT86102 RST 引脚异常") { lt86102_rst_io = false; }
+            if output.has_defect("hdmi_io_test", "LT6911_RST", "LT6911 RST 引脚异常") { lt6911_rst_io = false; }
+            if output.has_defect("hdmi_io_test", "LT86102_RX", "LT86102 RX 引脚异常") { lt86102_rx_io = false; }
+            if output.has_defect("hdmi_io_test", "LT86102_TX", "LT86102 TX 引脚异常") { lt86102_tx_io = false; }
+            if output.has_defect("hdmi_io_test", "LT6911_INT", "LT6911 INT 引脚异常") { lt6911_int_io = false; }
+            if output.has_defect("hdmi_io_test", "LT6911_I2C", "LT6911 I2C 引脚异常") { lt6911_i2c_io = false; }
+            if output.has_defect("hdmi_io_test", "LT86102_I2C", "LT86102 I2C 引脚异常") { lt86102_i2c_io = false; }
 
             let mut hdmi_io_error_msg = "HDMI-IO:".to_string();
             if !lt86102_rst_io { hdmi_io_error_msg.push_str("LT86102 RST "); }
@@ -236,7 +348,7 @@ pub fn spawn_step2_hdmi_testing(app_handle: AppHandle, target_type: &str, target
         }
 
         // 测试采集
-        let (hdmi_capture_test_result, hdmi_capture_test_output) = auto_test_with_retry(&app_handle, "hdmi_capture_test", "/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh vin", "HDMI VIN test passed", HDMI_VIN_TEST_MAX_RETRY_COUNT).await;
+        let (hdmi_capture_test_result, hdmi_capture_test_output) = auto_test_with_retry(&app_handle, "hdmi_capture_test", "/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh vin", "HDMI VIN test passed", HDMI_VIN_TEST_MAX_RETRY_COUNT, COMMAND_TIMEOUT_MS).await;
         if !hdmi_capture_test_result {
             log(&format!("hdmi_capture_test失败，输出: {}", hdmi_capture_test_output));
             add_error_msg("HDMI采集异常，建议检查IO错误或MIPI-CSI | ");
@@ -249,10 +361,13 @@ pub fn spawn_step2_hdmi_testing(app_handle: AppHandle, target_type: &str, target
             }
         }
 
+        // 采集测试结束后vin_test不再需要，结束服务并释放连接
+        vin_cancel.cancel();
+
         // 写入version
-        let _ = ssh_execute_command(&format!("echo \"{}\" > /etc/test-kvm/serial", target_serial)).await;
+        let _ = ssh_execute_command_with(&format!("echo \"{}\" > /etc/test-kvm/serial", target_serial), &SshOptions::timeout(COMMAND_TIMEOUT_MS)).await;
         let full_version_str = format!("{}{}", target_type, target_serial);
-        let (hdmi_version_test_result, hdmi_version_test_output) = auto_test_with_retry(&app_handle, "hdmi_version", &format!("/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh version \"{}\"", full_version_str), "HDMI version write passed", HDMI_VERSION_TEST_MAX_RETRY_COUNT).await;
+        let (hdmi_version_test_result, hdmi_version_test_output) = auto_test_with_retry(&app_handle, "hdmi_version", &format!("/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh version \"{}\"", full_version_str), "HDMI version write passed", HDMI_VERSION_TEST_MAX_RETRY_COUNT, COMMAND_TIMEOUT_MS).await;
         if !hdmi_version_test_result {
             log(&format!("hdmi_version_test失败，输出: {}", hdmi_version_test_output));
             add_error_msg("Ver写入异常，建议检查6911 I2C | ");
@@ -261,7 +376,7 @@ pub fn spawn_step2_hdmi_testing(app_handle: AppHandle, target_type: &str, target
         sleep(Duration::from_secs(1)).await;
 
         // 写入EDID
-        let (hdmi_write_edid_test_result, hdmi_write_edid_test_output) = auto_test_with_retry(&app_handle, "hdmi_write_edid", "/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh edid", "HDMI EDID write passed", HDMI_EDID_TEST_MAX_RETRY_COUNT).await;
+        let (hdmi_write_edid_test_result, hdmi_write_edid_test_output) = auto_test_with_retry(&app_handle, "hdmi_write_edid", "/root/NanoKVM_Pro_Testing/test_sh/05_hdmi_test.sh edid", "HDMI EDID write passed", HDMI_EDID_TEST_MAX_RETRY_COUNT, COMMAND_TIMEOUT_MS).await;
         if !hdmi_write_edid_test_result {
             log(&format!("hdmi_write_edid_test失败，输出: {}", hdmi_write_edid_test_output));
             add_error_msg("EDID写入异常，建议检查6911 I2C | ");
@@ -280,7 +395,7 @@ pub fn spawn_step2_usb_testing(app_handle: AppHandle, target_serial: &str) -> Jo
     let serial = target_serial.to_string();
     spawn(async move {
         log("USB测试中...");
-        let (usb_test_result, usb_test_output) = auto_test_with_retry(&app_handle, "usb_wait_connection", "/root/NanoKVM_Pro_Testing/test_sh/06_usb_test.sh", "USB test passed", USB_TEST_MAX_RETRY_COUNT).await;
+        let (usb_test_result, usb_test_output) = auto_test_with_retry(&app_handle, "usb_wait_connection", "/root/NanoKVM_Pro_Testing/test_sh/06_usb_test.sh", "USB test passed", USB_TEST_MAX_RETRY_COUNT, COMMAND_TIMEOUT_MS).await;
         if !usb_test_result {
             log(&format!("usb_test失败，输出: {}", usb_test_output));
             add_error_msg("USB测试异常，检查接口短路/24P排线连接/共模电感 | ");
@@ -298,33 +413,48 @@ pub fn spawn_step2_eth_testing(app_handle: AppHandle, target_serial: &str, ip: &
     let ip = ip.to_string();
     spawn(async move {
         log("网络测试中...");
+        // 通过串口测试时以太网已记为不良，不再测速
+        if is_serial_fallback() {
+            log("以太网不可用，跳过网速测试");
+            set_step_status(app_handle.clone(), "eth_wait_connection", AppTestStatus::Failed);
+            set_step_status(app_handle.clone(), "eth_upload_test", AppTestStatus::Failed);
+            set_step_status(app_handle.clone(), "eth_download_test", AppTestStatus::Failed);
+            let _ = set_test_status(&serial, "eth", "Damage");
+            return;
+        }
         set_step_status(app_handle.clone(), "eth_wait_connection", AppTestStatus::Success);
 
         // 获取阈值
         let upload_speed_threshold = get_config_str("testing", "eth_up_speed").unwrap_or("300".to_string());
         let download_speed_threshold = get_config_str("testing", "eth_down_speed").unwrap_or("500".to_string());
+        let download_size_mb = get_config_str("testing", "eth_test_mb").unwrap_or("50".to_string());
         
         // 测试命令
-        let upload_test_cmd = format!("/root/NanoKVM_Pro_Testing/test_sh/07_eth_test.sh upload {} \"http://{}:8080/upload\"", upload_speed_threshold, ip);
-        let download_test_cmd = format!("/root/NanoKVM_Pro_Testing/test_sh/07_eth_test.sh download {} \"http://{}:8080/download_small\"", download_speed_threshold, ip);
+        let (upload_test_cmd, download_test_cmd) = speed_test_commands("/root/NanoKVM_Pro_Testing/test_sh/07_eth_test.sh", "eth", &ip, &upload_speed_threshold, &download_speed_threshold, &download_size_mb);
 
         log(&format!("eth上传测试命令：{}", upload_test_cmd));
         log(&format!("eth下载测试命令：{}", download_test_cmd));
         
         // 测试上传
-        let (eth_upload_test_result, eth_upload_test_output) = auto_test_with_retry(&app_handle, "eth_upload_test", &upload_test_cmd, "ETH upload test passed", ETH_UPLOAD_TEST_MAX_RETRY_COUNT).await;
+        let (eth_upload_test_result, eth_upload_test_output) = speed_test_with_retry(&app_handle, &serial, "eth_upload_test", &upload_test_cmd, "ETH upload test passed", &upload_speed_threshold, ETH_UPLOAD_TEST_MAX_RETRY_COUNT).await;
         if !eth_upload_test_result {
             log(&format!("eth_upload_test失败，输出: {}", eth_upload_test_output));
         }
         // 测试下载
-        let (eth_download_test_result, eth_download_test_output) = auto_test_with_retry(&app_handle, "eth_download_test", &download_test_cmd, "ETH download test passed", ETH_DOWNLOAD_TEST_MAX_RETRY_COUNT).await;
+        let (eth_download_test_result, eth_download_test_output) = speed_test_with_retry(&app_handle, &serial, "eth_download_test", &download_test_cmd, "ETH download test passed", &download_speed_threshold, ETH_DOWNLOAD_TEST_MAX_RETRY_COUNT).await;
         if !eth_download_test_result {
             log(&format!("eth_download_test失败，输出: {}", eth_download_test_output));
         }
 
+        // 丢包和抖动
+        let eth_udp_test_result = udp_test(&serial, "/root/NanoKVM_Pro_Testing/test_sh/07_eth_test.sh", "eth", &ip).await;
+
         if !eth_upload_test_result || !eth_download_test_result {
             add_error_msg("以太网网速异常 | ");
             let _ = set_test_status(&serial, "eth", "Damage");
+        } else if !eth_udp_test_result {
+            add_error_msg("以太网丢包或抖动异常 | ");
+            let _ = set_test_status(&serial, "eth", "Damage");
         } else {
             let _ = set_test_status(&serial, "eth", "Normal");
         }
@@ -347,43 +477,44 @@ pub fn spawn_step2_wifi_testing(app_handle: AppHandle, target_serial: &str, ssid
         } else {
             log("等待wifi连接...");
             let connect_test_cmd = format!("/root/NanoKVM_Pro_Testing/test_sh/08_wifi_test.sh connect {} {}", ssid, password);
-            let (wifi_connect_result, wifi_connect_output) = auto_test_with_retry(&app_handle, "wifi_wait_connection", &connect_test_cmd, "WiFi connect passed", WIFI_CONNECT_MAX_RETRY_COUNT).await;
+            let (wifi_connect_result, wifi_connect_output) = auto_test_with_retry(&app_handle, "wifi_wait_connection", &connect_test_cmd, "WiFi connect passed", WIFI_CONNECT_MAX_RETRY_COUNT, WIFI_CONNECT_TIMEOUT_MS).await;
             if wifi_connect_result {
                 // 连接成功
                 let mut target_ip = String::new();
-                if let Some(start) = wifi_connect_output.find("DHCP服务器IP: ") {
-                    let content_start = start + "DHCP服务器IP: ".len();
-                    let remaining = &wifi_connect_output[content_start..];
-                    if let Some(end) = remaining.find('\n') {
-                        let ip = &remaining[..end].trim();
-                        log(&format!("RUST检测到当前板卡的IP为: {}", ip));
-                        target_ip = ip.to_string();
-                    }
+                if let Some(ip) = ScriptOutput::parse(&wifi_connect_output).value("wifi_wait_connection", "server_ip", "DHCP服务器IP: ") {
+                    log(&format!("RUST检测到当前板卡的IP为: {}", ip));
+                    target_ip = ip;
                 }
                 // 获取阈值
                 let upload_speed_threshold = get_config_str("testing", "wifi_up_speed").unwrap_or("10".to_string());
                 let download_speed_threshold = get_config_str("testing", "wifi_down_speed").unwrap_or("10".to_string());
+                let download_size_mb = get_config_str("testing", "wifi_test_mb").unwrap_or("10".to_string());
                 
                 // 测试命令
-                let upload_test_cmd = format!("/root/NanoKVM_Pro_Testing/test_sh/08_wifi_test.sh upload {} \"http://{}:8080/upload\"", upload_speed_threshold, target_ip);
-                let download_test_cmd = format!("/root/NanoKVM_Pro_Testing/test_sh/08_wifi_test.sh download {} \"http://{}:8080/download_small\"", download_speed_threshold, target_ip);
+                let (upload_test_cmd, download_test_cmd) = speed_test_commands("/root/NanoKVM_Pro_Testing/test_sh/08_wifi_test.sh", "wifi", &target_ip, &upload_speed_threshold, &download_speed_threshold, &download_size_mb);
 
                 log(&format!("wifi上传测试命令：{}", upload_test_cmd));
                 log(&format!("wifi下载测试命令：{}", download_test_cmd));
                 
                 // 测试上传
-                let (wifi_upload_test_result, wifi_upload_test_output) = auto_test_with_retry(&app_handle, "wifi_upload_test", &upload_test_cmd, "WiFi upload test passed", ETH_UPLOAD_TEST_MAX_RETRY_COUNT).await;
+                let (wifi_upload_test_result, wifi_upload_test_output) = speed_test_with_retry(&app_handle, &serial, "wifi_upload_test", &upload_test_cmd, "WiFi upload test passed", &upload_speed_threshold, ETH_UPLOAD_TEST_MAX_RETRY_COUNT).await;
                 if !wifi_upload_test_result {
                     log(&format!("wifi_upload_test失败，输出: {}", wifi_upload_test_output));
                 }
                 // 测试下载
-                let (wifi_download_test_result, wifi_download_test_output) = auto_test_with_retry(&app_handle, "wifi_download_test", &download_test_cmd, "WiFi download test passed", ETH_DOWNLOAD_TEST_MAX_RETRY_COUNT).await;
+                let (wifi_download_test_result, wifi_download_test_output) = speed_test_with_retry(&app_handle, &serial, "wifi_download_test", &download_test_cmd, "WiFi download test passed", &download_speed_threshold, ETH_DOWNLOAD_TEST_MAX_RETRY_COUNT).await;
                 if !wifi_download_test_result {
                     log(&format!("wifi_download_test失败，输出: {}", wifi_download_test_output));
                 }
+                // 丢包和抖动
+                let wifi_udp_test_result = udp_test(&serial, "/root/NanoKVM_Pro_Testing/test_sh/08_wifi_test.sh", "wifi", &target_ip).await;
+
                 if !wifi_upload_test_result || !wifi_download_test_result {
                     add_error_msg("WiFi网速异常，检查天线连接 | ");
                     let _ = set_test_status(&serial, "wifi", "Damage");
+                } else if !wifi_udp_test_result {
+                    add_error_msg("WiFi丢包或抖动异常，检查天线连接 | ");
+                    let _ = set_test_status(&serial, "wifi", "Damage");
                 } else {
                     let _ = set_test_status(&serial, "wifi", "Normal");
                 }
@@ -403,11 +534,11 @@ pub fn spawn_step2_penal_testing(app_handle: AppHandle, hardware_type: HardwareT
         log("启动屏幕测试服务");
         if hardware_type == HardwareType::Atx {
             set_step_status(app_handle.clone(), "screen", AppTestStatus::Testing);
-            let _ = ssh_execute_command("/root/NanoKVM_Pro_Testing/test_sh/09_panel_test.sh oled 60").await;
+            let _ = ssh_execute_command_with("/root/NanoKVM_Pro_Testing/test_sh/09_panel_test.sh oled 60", &SshOptions::timeout(PANEL_TEST_TIMEOUT_MS)).await;
             log("屏幕测试服务退出");
         } else {
             set_step_status(app_handle.clone(), "screen", AppTestStatus::Testing);
-            let _ = ssh_execute_command("/root/NanoKVM_Pro_Testing/test_sh/09_panel_test.sh lcd 60").await;
+            let _ = ssh_execute_command_with("/root/NanoKVM_Pro_Testing/test_sh/09_panel_test.sh lcd 60", &SshOptions::timeout(PANEL_TEST_TIMEOUT_MS)).await;
             log("屏幕测试服务退出");
         }
 
@@ -438,13 +569,13 @@ pub fn spawn_step2_ux_testing(app_handle: AppHandle, target_serial: &str, hardwa
         // 等待弹窗消失500ms
         // std::thread::sleep(Duration::from_millis(500));
         if hardware_type == HardwareType::Atx {
-            let _ = ssh_execute_command("kill $(cat /tmp/oled.pid)").await;
+            let _ = ssh_execute_command_with("kill $(cat /tmp/oled.pid)", &SshOptions::timeout(COMMAND_TIMEOUT_MS)).await;
             set_step_status(app_handle.clone(), "touch", AppTestStatus::Hidden);
             set_step_status(app_handle.clone(), "knob", AppTestStatus::Hidden);
             let _ = set_test_status(&serial, "touch", "No hardware");
             let _ = set_test_status(&serial, "rotary", "No hardware");
         } else {
-            let _ = ssh_execute_command("kill $(cat /tmp/lcd.pid)").await;
+            let _ = ssh_execute_command_with("kill $(cat /tmp/lcd.pid)", &SshOptions::timeout(COMMAND_TIMEOUT_MS)).await;
             // 测试触摸
             if need_set_fail {
                 // 需要直接给一个失败状态
@@ -453,7 +584,7 @@ pub fn spawn_step2_ux_testing(app_handle: AppHandle, target_serial: &str, hardwa
                 let _ = set_test_status(&serial, "touch", "Damage");
             } else {
                 // 正常的触摸测试
-                let (touch_test_result, touch_test_output) = auto_test_with_retry(&app_handle, "touch", "/root/NanoKVM_Pro_Testing/test_sh/09_panel_test.sh touch 60", "Touch test passed", 1).await;
+                let (touch_test_result, touch_test_output) = auto_test_with_retry(&app_handle, "touch", "/root/NanoKVM_Pro_Testing/test_sh/09_panel_test.sh touch 60", "Touch test passed", 1, PANEL_TEST_TIMEOUT_MS).await;
                 if !touch_test_result {
                     log(&format!("touch测试失败，输出: {}", touch_test_output));
                     add_error_msg("触摸 | ");
@@ -464,7 +595,7 @@ pub fn spawn_step2_ux_testing(app_handle: AppHandle, target_serial: &str, hardwa
             }
 
             // 测试旋钮
-            let (knob_test_result, knob_test_output) = auto_test_with_retry(&app_handle, "knob", "/root/NanoKVM_Pro_Testing/test_sh/09_panel_test.sh rotary 60", "Rotary test passed", 1).await;
+            let (knob_test_result, knob_test_output) = auto_test_with_retry(&app_handle, "knob", "/root/NanoKVM_Pro_Testing/test_sh/09_panel_test.sh rotary 60", "Rotary test passed", 1, PANEL_TEST_TIMEOUT_MS).await;
             if !knob_test_result {
                 log(&format!("knob测试失败，输出: {}", knob_test_output));
                 add_error_msg("旋钮 | ");
@@ -483,7 +614,7 @@ pub fn spawn_step2_atx_testing(app_handle: AppHandle, target_serial: &str, hardw
     let serial = target_serial.to_string();
     spawn(async move {
         if hardware_type == HardwareType::Atx {
-            let (atx_test_result, atx_test_output) = auto_test_with_retry(&app_handle, "atx", "/root/NanoKVM_Pro_Testing/test_sh/10_atx_test.sh atx", "ATX test passed", IO_TEST_MAX_RETRY_COUNT).await;
+            let (atx_test_result, atx_test_output) = auto_test_with_retry(&app_handle, "atx", "/root/NanoKVM_Pro_Testing/test_sh/10_atx_test.sh atx", "ATX test passed", IO_TEST_MAX_RETRY_COUNT, COMMAND_TIMEOUT_MS).await;
             if !atx_test_result {
                 log(&format!("atx_test失败，输出: {}", atx_test_output));
                 add_error_msg("ATX IO异常 | ");
@@ -492,7 +623,7 @@ pub fn spawn_step2_atx_testing(app_handle: AppHandle, target_serial: &str, hardw
                 let _ = set_test_status(&serial, "atx", "Normal");
             }
         } else {
-            let (atx_test_result, atx_test_output) = auto_test_with_retry(&app_handle, "atx", "/root/NanoKVM_Pro_Testing/test_sh/10_atx_test.sh desk", "ATX test passed", IO_TEST_MAX_RETRY_COUNT).await;
+            let (atx_test_result, atx_test_output) = auto_test_with_retry(&app_handle, "atx", "/root/NanoKVM_Pro_Testing/test_sh/10_atx_test.sh desk", "ATX test passed", IO_TEST_MAX_RETRY_COUNT, COMMAND_TIMEOUT_MS).await;
             if !atx_test_result {
                 log(&format!("atx_test失败，输出: {}", atx_test_output));
                 add_error_msg("ATX IO异常 | ");
@@ -510,7 +641,7 @@ pub fn spawn_step2_atx_testing(app_handle: AppHandle, target_serial: &str, hardw
 pub fn spawn_step2_io_testing(app_handle: AppHandle, target_serial: &str) -> JoinHandle<()> {
     let serial = target_serial.to_string();
     spawn(async move {        
-        let (io_test_result, io_test_output) = auto_test_with_retry(&app_handle, "io", "/root/NanoKVM_Pro_Testing/test_sh/11_io_test.sh 10", "IO test passed", IO_TEST_MAX_RETRY_COUNT).await;
+        let (io_test_result, io_test_output) = auto_test_with_retry(&app_handle, "io", "/root/NanoKVM_Pro_Testing/test_sh/11_io_test.sh 10", "IO test passed", IO_TEST_MAX_RETRY_COUNT, COMMAND_TIMEOUT_MS).await;
         if !io_test_result {
             log(&format!("io_test失败，输出: {}", io_test_output));
             add_error_msg("WS2812 IO异常 | ");
@@ -531,7 +662,7 @@ pub fn spawn_step2_tf_testing(app_handle: AppHandle, target_serial: &str, hardwa
             set_step_status(app_handle.clone(), "tf_card", AppTestStatus::Hidden);
             let _ = set_test_status(&serial, "sdcard", "No hardware");
         } else {
-            let (tf_test_result, tf_test_output) = auto_test_with_retry(&app_handle, "tf_card", "/root/NanoKVM_Pro_Testing/test_sh/12_tf_test.sh", "TF test passed", IO_TEST_MAX_RETRY_COUNT).await;
+            let (tf_test_result, tf_test_output) = auto_test_with_retry(&app_handle, "tf_card", "/root/NanoKVM_Pro_Testing/test_sh/12_tf_test.sh", "TF test passed", IO_TEST_MAX_RETRY_COUNT, COMMAND_TIMEOUT_MS).await;
             if !tf_test_result {
                 log(&format!("tf_test失败，输出: {}", tf_test_output));
                 add_error_msg("TF卡，检查SDIO相关器件/测试卡是否损坏 | ");
@@ -550,7 +681,7 @@ pub fn spawn_step2_uart_testing(app_handle: AppHandle, target_serial: &str, hard
     let serial = target_serial.to_string();
     spawn(async move {
         if hardware_type == HardwareType::Desk {
-            let (uart_test_result, uart_test_output) = auto_test_with_retry(&app_handle, "uart", "/root/NanoKVM_Pro_Testing/test_sh/13_uart_test.sh", "UART test passed", IO_TEST_MAX_RETRY_COUNT).await;
+            let (uart_test_result, uart_test_output) = auto_test_with_retry(&app_handle, "uart", "/root/NanoKVM_Pro_Testing/test_sh/13_uart_test.sh", "UART test passed", IO_TEST_MAX_RETRY_COUNT, COMMAND_TIMEOUT_MS).await;
             if !uart_test_result {
                 log(&format!("uart_test失败，输出: {}", uart_test_output));
                 add_error_msg("UART异常，检查24P排线 | ");
@@ -567,13 +698,47 @@ pub fn spawn_step2_uart_testing(app_handle: AppHandle, target_serial: &str, hard
     })
 }
 
+// 从板卡取回测试标记、dmesg和脚本日志，保存到板卡记录目录并登记到JSON
+async fn pull_artifacts(serial: &str) {
+    if is_serial_fallback() {
+        log("以太网不可用，不取回板卡日志");
+        return;
+    }
+    let (save_dir, record_dir) = match (get_save_dir(), get_record_dir(serial)) {
+        (Ok(save_dir), Ok(record_dir)) => (save_dir, record_dir),
+        _ => {
+            log("无法创建记录目录，不取回板卡日志");
+            return;
+        }
+    };
+    let _ = ssh_execute_command_with("mkdir -p /root/log && dmesg > /root/log/dmesg.log", &SshOptions::timeout(COMMAND_TIMEOUT_MS)).await;
+
+    let mut files = Vec::new();
+    for (remote, name) in PULL_ARTIFACTS {
+        match ssh_download(remote, &record_dir.join(name), &SshOptions::timeout(PULL_TIMEOUT_MS)).await {
+            Ok(pulled) => files.extend(pulled),
+            Err(e) => log(&format!("取回 {} 失败: {}", remote, e)),
+        }
+    }
+    let files: Vec<String> = files.iter()
+        .filter_map(|file| file.strip_prefix(&save_dir).ok())
+        .map(|file| file.to_string_lossy().replace('\\', "/"))
+        .collect();
+    log(&format!("取回 {} 个板卡文件", files.len()));
+    if let Err(e) = add_artifacts(serial, &files) {
+        log(&format!("登记板卡文件失败: {}", e));
+    }
+}
+
 pub fn spawn_step3_test_end(app_handle: AppHandle, target_serial: &str) -> JoinHandle<()> {
     let serial = target_serial.to_string();
     spawn(async move {
+        // 结束脚本会清空/root，先取回日志
+        pull_artifacts(&serial).await;
         if all_step_status_is_success() {
             set_step_status(app_handle.clone(), "auto_start", AppTestStatus::Testing);
-            let _ = ssh_execute_command("/root/NanoKVM_Pro_Testing/test_sh/14_test_end.sh").await;
-            let _ = ssh_execute_command("rm -r /root/*").await;
+            let _ = ssh_execute_command_with("/root/NanoKVM_Pro_Testing/test_sh/14_test_end.sh", &SshOptions::timeout(COMMAND_TIMEOUT_MS)).await;
+            let _ = ssh_execute_command_with("rm -r /root/*", &SshOptions::timeout(COMMAND_TIMEOUT_MS)).await;
             set_step_status(app_handle.clone(), "auto_start", AppTestStatus::Success);
             let _ = set_test_status(&serial, "app", "Normal");
             let _ = set_test_status(&serial, "test_pass", "true");
@@ -0,0 +1,103 @@
+import { useEffect, useRef, useState } from 'react';
+
+// 后端 "ssh-output" 事件推送的一行命令输出
+export interface OutputLine {
+  stream: 'stdout' | 'stderr';
+  line: string;
+}
+
+interface CommandOutputProps {
+  title: string | null;
+  lines: OutputLine[];
+  onClose: () => void;
+  isDark: boolean;
+}
+
+export function CommandOutput({ title, lines, onClose, isDark }: CommandOutputProps) {
+  const [isClosing, setIsClosing] = useState(false);
+  const [isAnimating, setIsAnimating] = useState(false);
+  const outputRef = useRef<HTMLDivElement>(null);
+  const isOpen = title !== null;
+
+  // 处理打开动画
+  useEffect(() => {
+    if (isOpen) {
+      // 延迟一帧以触发CSS过渡
+      requestAnimationFrame(() => {
+        setIsAnimating(true);
+      });
+    }
+  }, [isOpen]);
+
+  // 有新输出时滚动到底部
+  useEffect(() => {
+    if (outputRef.current) {
+      outputRef.current.scrollTop = outputRef.current.scrollHeight;
+    }
+  }, [lines, isOpen]);
+
+  // 处理关闭动画
+  const handleClose = () => {
+    setIsClosing(true);
+    setIsAnimating(false);
+    setTimeout(() => {
+      setIsClosing(false);
+      onClose();
+    }, 300); // 与动画时长一致
+  };
+
+  if (!isOpen && !isClosing) return null;
+
+  return (
+    <>
+      {/* 背景遮罩 */}
+      <div
+        className={`fixed inset-0 transition-opacity duration-300 z-40 bg-black/60 ${
+          isClosing ? 'opacity-0' : 'opacity-100'
+        }`}
+        onClick={handleClose}
+      />
+
+      {/* 输出窗口 - 从底部弹起，与串口终端相同的位置 */}
+      <div
+        className={`fixed z-50 flex flex-col transition-transform duration-300 shadow-2xl overflow-hidden rounded-t-lg ${
+          isAnimating ? 'translate-y-0' : 'translate-y-full'
+        }`}
+        style={{
+          left: '10%',
+          right: '10%',
+          top: '10%',
+          bottom: 0,
+          backgroundColor: isDark ? '#262626' : '#E5E5E5',
+        }}
+        onClick={(e) => e.stopPropagation()}
+      >
+        <div className={`px-4 py-2 ${isDark ? 'text-white' : 'text-neutral-900'}`} style={{ fontWeight: 'bold' }}>
+          {title} 命令输出
+        </div>
+        <div
+          ref={outputRef}
+          className="flex-1 overflow-auto px-4 pb-4 whitespace-pre-wrap break-all"
+          style={{ fontFamily: 'monospace', fontSize: '12px', lineHeight: 1.2 }}
+        >
+          {lines.length === 0 ? (
+            <div className={isDark ? 'text-neutral-400' : 'text-neutral-500'}>暂无输出</div>
+          ) : (
+            lines.map((line, index) => (
+              <div
+                key={index}
+                className={
+                  line.stream === 'stderr'
+                    ? (isDark ? 'text-red-400' : 'text-red-600')
+                    : (isDark ? 'text-neutral-200' : 'text-neutral-900')
+                }
+              >
+                {line.line}
+              </div>
+            ))
+          )}
+        </div>
+      </div>
+    </>
+  );
+}
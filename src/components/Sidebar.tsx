@@ -2,6 +2,21 @@ import { useState, useEffect } from 'react';
 import { ChevronDown } from 'lucide-react';
 import { listen } from '@tauri-apps/api/event';
 
+interface UploadQueueStatus {
+  depth: number;
+  oldest_age_secs: number | null;
+  last_error: string | null;
+  quarantined: number;
+}
+
+// 等待时间显示为 秒/分钟/小时
+function formatAge(secs: number | null) {
+  if (secs === null) return '-';
+  if (secs < 60) return `${secs}秒`;
+  if (secs < 3600) return `${Math.floor(secs / 60)}分钟`;
+  return `${Math.floor(secs / 3600)}小时`;
+}
+
 interface SidebarProps {
   theme: '明亮' | '暗黑';
   onThemeChange: (theme: '明亮' | '暗黑') => void;
@@ -17,6 +32,7 @@ export function Sidebar({
   const [machineCode, setMachineCode] = useState('-');
   const [serverStatus, setServerStatus] = useState<'online' | 'offline'>('offline');
   const [uploadCount, setUploadCount] = useState(0);
+  const [uploadQueue, setUploadQueue] = useState<UploadQueueStatus | null>(null);
   const [currentDevice, setCurrentDevice] = useState('-');
   const [serialNumber, setSerialNumber] = useState('-');
   const [targetIP, setTargetIP] = useState('-');
@@ -63,6 +79,14 @@ export function Sidebar({
       console.error('设置待上传数量监听器失败:', error);
     });
 
+    // 监听上传队列状态更新
+    listen('upload-queue-update', (event) => {
+      const status = event.payload as UploadQueueStatus;
+      setUploadQueue(status);
+    }).catch(error => {
+      console.error('设置上传队列监听器失败:', error);
+    });
+
     // 监听当前硬件更新
     listen('current-device-update', (event) => {
       const device = event.payload as string;
@@ -127,6 +151,23 @@ export function Sidebar({
             <span className={isDark ? 'text-neutral-300' : 'text-neutral-900'}>待上传数量：</span>
             <span className={isDark ? 'text-neutral-300' : 'text-neutral-900'}>{uploadCount}</span>
           </div>
+          {uploadQueue && uploadQueue.depth > 0 && (
+            <div className="flex items-center justify-between">
+              <span className={isDark ? 'text-neutral-300' : 'text-neutral-900'}>最早待上传：</span>
+              <span className={isDark ? 'text-neutral-300' : 'text-neutral-900'}>{formatAge(uploadQueue.oldest_age_secs)}</span>
+            </div>
+          )}
+          {uploadQueue && uploadQueue.quarantined > 0 && (
+            <div className="flex items-center justify-between">
+              <span className={isDark ? 'text-neutral-300' : 'text-neutral-900'}>无效记录：</span>
+              <span className={isDark ? 'text-red-400' : 'text-red-700'}>{uploadQueue.quarantined}</span>
+            </div>
+          )}
+          {uploadQueue?.last_error && (
+            <div className={`text-xs truncate ${isDark ? 'text-red-400' : 'text-red-700'}`} title={uploadQueue.last_error}>
+              {uploadQueue.last_error}
+            </div>
+          )}
           <div className="flex items-center justify-between">
             <span className={isDark ? 'text-neutral-300' : 'text-neutral-900'}>当前硬件：</span>
             <span className={isDark ? 'text-neutral-300' : 'text-neutral-900'}>{currentDevice}</span>
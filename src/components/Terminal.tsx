@@ -2,6 +2,8 @@ import { useEffect, useRef, useState } from 'react';
 import { Terminal as XTerminal } from '@xterm/xterm';
 import { FitAddon } from '@xterm/addon-fit';
 import '@xterm/xterm/css/xterm.css';
+import { listen } from '@tauri-apps/api/event';
+import { invoke } from '@tauri-apps/api/core';
 
 interface TerminalProps {
   isOpen: boolean;
@@ -15,7 +17,6 @@ export function Terminal({ isOpen, onClose, isDark }: TerminalProps) {
   const terminalRef = useRef<HTMLDivElement>(null);
   const terminalInstanceRef = useRef<XTerminal | null>(null);
   const fitAddonRef = useRef<FitAddon | null>(null);
-  const wsRef = useRef<WebSocket | null>(null);
 
   // 处理打开动画
   useEffect(() => {
@@ -78,12 +79,11 @@ export function Terminal({ isOpen, onClose, isDark }: TerminalProps) {
 
     window.addEventListener('resize', handleResize);
 
-    // 处理终端输入
+    // 处理终端输入，按键原样发送到串口
     terminal.onData((data) => {
-      // 发送数据到后端
-      if (wsRef.current && wsRef.current.readyState === WebSocket.OPEN) {
-        wsRef.current.send(data);
-      }
+      invoke('terminal_input', { data }).catch((error) => {
+        console.error('发送终端输入失败:', error);
+      });
     });
 
     return () => {
@@ -92,79 +92,38 @@ export function Terminal({ isOpen, onClose, isDark }: TerminalProps) {
     };
   }, [isOpen, isDark]);
 
-  // 建立WebSocket连接
+  // 打开期间订阅后端串口输出，后端同时暂停自动测试
   useEffect(() => {
     if (!isOpen) return;
 
-    // 建立WebSocket连接
-    const ws = new WebSocket('ws://localhost:8080');
-    
-    ws.onopen = () => {
-      console.log('WebSocket连接已建立');
-    };
+    let disposed = false;
+    let unlisten: (() => void) | null = null;
 
-    ws.onmessage = (event) => {
-      // 接收来自后端的数据并显示在终端
+    // 先注册监听再打开终端，避免丢失后端回放的历史输出
+    listen<string>('terminal-output', (event) => {
       if (terminalInstanceRef.current) {
-        terminalInstanceRef.current.write(event.data);
+        terminalInstanceRef.current.write(event.payload);
       }
-    };
-
-    ws.onclose = () => {
-      console.log('WebSocket连接已关闭');
-      // 显示断开连接消息
-      if (terminalInstanceRef.current) {
-        terminalInstanceRef.current.write('[disconnected]\n');
+    }).then((fn) => {
+      if (disposed) {
+        fn();
+        return;
       }
-    };
-
-    ws.onerror = (error) => {
-      console.error('WebSocket错误:', error);
-    };
-
-    // 保存WebSocket实例
-    wsRef.current = ws;
+      unlisten = fn;
+      return invoke('open_terminal');
+    }).catch((error) => {
+      console.error('打开串口终端失败:', error);
+    });
 
     return () => {
-      ws.close();
-    };
-  }, [isOpen]);
-
-  // 监听Tauri事件（用于开发模式）
-  useEffect(() => {
-    if (!isOpen) return;
-
-    // 这里使用Tauri的事件监听（开发模式下的模拟数据）
-    // @ts-ignore - Tauri API
-    if (window.__TAURI__) {
-      // @ts-ignore
-      const { listen } = window.__TAURI__.event;
-      
-      const unlisten = listen('terminal-output', (event: any) => {
-        if (terminalInstanceRef.current) {
-          terminalInstanceRef.current.write(event.payload);
-        }
-      });
-
-      return () => {
-        unlisten.then((fn: any) => fn());
-      };
-    } else {
-      // 开发模式下的模拟数据
-      const mockData = [
-        '[connected]\n',
-        '[ 13.160778] fbift_of_value: fps = 90\n',
-        '[ 13.160878] fb_jd9853 spi2.1: fbift_request_one_gpio: \'reset-gpios\' = GPI041\n',
-        '[ 13.160895] fb_jd9853 spi2.1: fbift_request_one_gpio: \'de-gpios\' = GPI043\n',
-        '[ 13.160927] fb_jd9853 spi2.1: debug mode: off\n',
-      ];
-      
-      if (terminalInstanceRef.current) {
-        mockData.forEach(line => {
-          terminalInstanceRef.current?.write(line);
-        });
+      disposed = true;
+      if (unlisten) {
+        unlisten();
       }
-    }
+      invoke('close_terminal').catch((error) => {
+        console.error('关闭串口终端失败:', error);
+      });
+    };
   }, [isOpen]);
 
   if (!isOpen && !isClosing) return null;
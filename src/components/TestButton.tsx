@@ -106,7 +106,7 @@ export function TestButton({ status = 'untested', children, onClick, isDark }: T
     <button
       onClick={onClick}
       className={`${getBaseClasses()} overflow-visible`}
-      disabled={status === 'testing' || status === 'repairing'}
+      disabled={!onClick && (status === 'testing' || status === 'repairing')}
     >
       {/* 动画边框 */}
       {getBorderAnimation()}
@@ -1,4 +1,5 @@
 import { TestButton } from './TestButton';
+import { CommandOutput, OutputLine } from './CommandOutput';
 import { useEffect, useState } from 'react';
 
 import { TestButtonStatus, TestButtonId } from '../services/backendService';
@@ -43,6 +44,9 @@ interface TestButtonStates {
   print_error_msg: TestButtonStatus;
 }
 
+// 每个测试项目最多保留的输出行数
+const MAX_OUTPUT_LINES = 2000;
+
 export function TestPanel({ isDark }: TestPanelProps) {
   // 初始化所有按钮状态为未测试
   const [buttonStates, setButtonStates] = useState<TestButtonStates>({
@@ -80,6 +84,10 @@ export function TestPanel({ isDark }: TestPanelProps) {
     print_error_msg: 'hidden',
   });
 
+  // 各测试项目的命令输出，以及当前查看的项目
+  const [outputs, setOutputs] = useState<Record<string, OutputLine[]>>({});
+  const [outputItem, setOutputItem] = useState<{ id: string; title: string } | null>(null);
+
   // 监听测试按钮状态更新事件
   useEffect(() => {
     let unlisten: (() => void) | null = null;
@@ -93,6 +101,15 @@ export function TestPanel({ isDark }: TestPanelProps) {
             ...prev,
             [buttonId]: status
           }));
+          // 新板卡开始测试时清除上一块板卡的输出
+          if (status === 'untested') {
+            setOutputs(prev => {
+              if (!(buttonId in prev)) return prev;
+              const rest = { ...prev };
+              delete rest[buttonId];
+              return rest;
+            });
+          }
         });
       } catch (error) {
         console.error('监听测试按钮状态更新失败:', error);
@@ -107,18 +124,46 @@ export function TestPanel({ isDark }: TestPanelProps) {
       }
     };
   }, []);
+
+  // 监听测试命令的逐行输出
+  useEffect(() => {
+    let unlisten: (() => void) | null = null;
+
+    const setupListener = async () => {
+      try {
+        const { listen } = await import('@tauri-apps/api/event');
+        unlisten = await listen('ssh-output', (event) => {
+          const { item, stream, line } = event.payload as { item: string } & OutputLine;
+          setOutputs(prev => ({
+            ...prev,
+            [item]: [...(prev[item] ?? []), { stream, line }].slice(-MAX_OUTPUT_LINES)
+          }));
+        });
+      } catch (error) {
+        console.error('监听命令输出失败:', error);
+      }
+    };
+
+    setupListener();
+
+    return () => {
+      if (unlisten) {
+        unlisten();
+      }
+    };
+  }, []);
   return (
     <main className="flex-1 p-8 overflow-auto">
       {/* 初始化 */}
       <section className="mb-8">
         <h2 className={`mb-4 ${isDark ? 'text-white' : 'text-neutral-900'}`} style={{ fontSize: '24px', fontWeight: 'bold' }}>初始化</h2>
         <div className="flex flex-wrap gap-3">
-          <TestButton status={buttonStates.wait_connection} isDark={isDark}>等待连接</TestButton>
-          <TestButton status={buttonStates.wait_boot} isDark={isDark}>等待开机</TestButton>
-          <TestButton status={buttonStates.get_ip} isDark={isDark}>获取IP</TestButton>
-          <TestButton status={buttonStates.download_test} isDark={isDark}>下载产测</TestButton>
-          <TestButton status={buttonStates.detect_hardware} isDark={isDark}>检测硬件</TestButton>
-          <TestButton status={buttonStates.emmc_test} isDark={isDark}>eMMC</TestButton>
+          <TestButton status={buttonStates.wait_connection} isDark={isDark} onClick={() => setOutputItem({ id: 'wait_connection', title: '等待连接' })}>等待连接</TestButton>
+          <TestButton status={buttonStates.wait_boot} isDark={isDark} onClick={() => setOutputItem({ id: 'wait_boot', title: '等待开机' })}>等待开机</TestButton>
+          <TestButton status={buttonStates.get_ip} isDark={isDark} onClick={() => setOutputItem({ id: 'get_ip', title: '获取IP' })}>获取IP</TestButton>
+          <TestButton status={buttonStates.download_test} isDark={isDark} onClick={() => setOutputItem({ id: 'download_test', title: '下载产测' })}>下载产测</TestButton>
+          <TestButton status={buttonStates.detect_hardware} isDark={isDark} onClick={() => setOutputItem({ id: 'detect_hardware', title: '检测硬件' })}>检测硬件</TestButton>
+          <TestButton status={buttonStates.emmc_test} isDark={isDark} onClick={() => setOutputItem({ id: 'emmc_test', title: 'eMMC' })}>eMMC</TestButton>
         </div>
       </section>
 
@@ -133,10 +178,10 @@ export function TestPanel({ isDark }: TestPanelProps) {
           <div className="w-24 flex items-center">
             <span className={isDark ? 'text-white' : 'text-neutral-900'}>系统更新</span>
           </div>
-          <TestButton status={buttonStates.dtb} isDark={isDark}>dtb</TestButton>
-          <TestButton status={buttonStates.uboot} isDark={isDark}>uboot</TestButton>
-          <TestButton status={buttonStates.kernel} isDark={isDark}>kernel</TestButton>
-          <TestButton status={buttonStates.app_install} isDark={isDark}>APP安装</TestButton>
+          <TestButton status={buttonStates.dtb} isDark={isDark} onClick={() => setOutputItem({ id: 'dtb', title: 'dtb' })}>dtb</TestButton>
+          <TestButton status={buttonStates.uboot} isDark={isDark} onClick={() => setOutputItem({ id: 'uboot', title: 'uboot' })}>uboot</TestButton>
+          <TestButton status={buttonStates.kernel} isDark={isDark} onClick={() => setOutputItem({ id: 'kernel', title: 'kernel' })}>kernel</TestButton>
+          <TestButton status={buttonStates.app_install} isDark={isDark} onClick={() => setOutputItem({ id: 'app_install', title: 'APP安装' })}>APP安装</TestButton>
         </div>
 
         {/* HDMI接口 */}
@@ -144,12 +189,12 @@ export function TestPanel({ isDark }: TestPanelProps) {
           <div className="w-24 flex items-center">
             <span className={isDark ? 'text-white' : 'text-neutral-900'}>HDMI接口</span>
           </div>
-          <TestButton status={buttonStates.hdmi_wait_connection} isDark={isDark}>等待连接</TestButton>
-          <TestButton status={buttonStates.hdmi_io_test} isDark={isDark}>测试IO</TestButton>
-          <TestButton status={buttonStates.hdmi_loop_test} isDark={isDark}>测试环出</TestButton>
-          <TestButton status={buttonStates.hdmi_capture_test} isDark={isDark}>测试采集</TestButton>
-          <TestButton status={buttonStates.hdmi_version} isDark={isDark}>Version</TestButton>
-          <TestButton status={buttonStates.hdmi_write_edid} isDark={isDark}>写EDID</TestButton>
+          <TestButton status={buttonStates.hdmi_wait_connection} isDark={isDark} onClick={() => setOutputItem({ id: 'hdmi_wait_connection', title: '等待连接' })}>等待连接</TestButton>
+          <TestButton status={buttonStates.hdmi_io_test} isDark={isDark} onClick={() => setOutputItem({ id: 'hdmi_io_test', title: '测试IO' })}>测试IO</TestButton>
+          <TestButton status={buttonStates.hdmi_loop_test} isDark={isDark} onClick={() => setOutputItem({ id: 'hdmi_loop_test', title: '测试环出' })}>测试环出</TestButton>
+          <TestButton status={buttonStates.hdmi_capture_test} isDark={isDark} onClick={() => setOutputItem({ id: 'hdmi_capture_test', title: '测试采集' })}>测试采集</TestButton>
+          <TestButton status={buttonStates.hdmi_version} isDark={isDark} onClick={() => setOutputItem({ id: 'hdmi_version', title: 'Version' })}>Version</TestButton>
+          <TestButton status={buttonStates.hdmi_write_edid} isDark={isDark} onClick={() => setOutputItem({ id: 'hdmi_write_edid', title: '写EDID' })}>写EDID</TestButton>
         </div>
 
         {/* USB接口 */}
@@ -157,7 +202,7 @@ export function TestPanel({ isDark }: TestPanelProps) {
           <div className="w-24 flex items-center">
             <span className={isDark ? 'text-white' : 'text-neutral-900'}>USB接口</span>
           </div>
-          <TestButton status={buttonStates.usb_wait_connection} isDark={isDark}>等待连接</TestButton>
+          <TestButton status={buttonStates.usb_wait_connection} isDark={isDark} onClick={() => setOutputItem({ id: 'usb_wait_connection', title: '等待连接' })}>等待连接</TestButton>
         </div>
 
         {/* ETH接口 */}
@@ -165,9 +210,9 @@ export function TestPanel({ isDark }: TestPanelProps) {
           <div className="w-24 flex items-center">
             <span className={isDark ? 'text-white' : 'text-neutral-900'}>ETH接口</span>
           </div>
-          <TestButton status={buttonStates.eth_wait_connection} isDark={isDark}>等待连接</TestButton>
-          <TestButton status={buttonStates.eth_upload_test} isDark={isDark}>上传测速</TestButton>
-          <TestButton status={buttonStates.eth_download_test} isDark={isDark}>下载测速</TestButton>
+          <TestButton status={buttonStates.eth_wait_connection} isDark={isDark} onClick={() => setOutputItem({ id: 'eth_wait_connection', title: '等待连接' })}>等待连接</TestButton>
+          <TestButton status={buttonStates.eth_upload_test} isDark={isDark} onClick={() => setOutputItem({ id: 'eth_upload_test', title: '上传测速' })}>上传测速</TestButton>
+          <TestButton status={buttonStates.eth_download_test} isDark={isDark} onClick={() => setOutputItem({ id: 'eth_download_test', title: '下载测速' })}>下载测速</TestButton>
         </div>
 
         {/* WiFi */}
@@ -175,9 +220,9 @@ export function TestPanel({ isDark }: TestPanelProps) {
           <div className="w-24 flex items-center">
             <span className={isDark ? 'text-white' : 'text-neutral-900'}>WiFi</span>
           </div>
-          <TestButton status={buttonStates.wifi_wait_connection} isDark={isDark}>等待连接</TestButton>
-          <TestButton status={buttonStates.wifi_upload_test} isDark={isDark}>上传测速</TestButton>
-          <TestButton status={buttonStates.wifi_download_test} isDark={isDark}>下载测速</TestButton>
+          <TestButton status={buttonStates.wifi_wait_connection} isDark={isDark} onClick={() => setOutputItem({ id: 'wifi_wait_connection', title: '等待连接' })}>等待连接</TestButton>
+          <TestButton status={buttonStates.wifi_upload_test} isDark={isDark} onClick={() => setOutputItem({ id: 'wifi_upload_test', title: '上传测速' })}>上传测速</TestButton>
+          <TestButton status={buttonStates.wifi_download_test} isDark={isDark} onClick={() => setOutputItem({ id: 'wifi_download_test', title: '下载测速' })}>下载测速</TestButton>
         </div>
 
         {/* 交互 */}
@@ -185,9 +230,9 @@ export function TestPanel({ isDark }: TestPanelProps) {
           <div className="w-24 flex items-center">
             <span className={isDark ? 'text-white' : 'text-neutral-900'}>交互</span>
           </div>
-          <TestButton status={buttonStates.screen} isDark={isDark}>屏幕</TestButton>
-          <TestButton status={buttonStates.touch} isDark={isDark}>触摸</TestButton>
-          <TestButton status={buttonStates.knob} isDark={isDark}>旋钮</TestButton>
+          <TestButton status={buttonStates.screen} isDark={isDark} onClick={() => setOutputItem({ id: 'screen', title: '屏幕' })}>屏幕</TestButton>
+          <TestButton status={buttonStates.touch} isDark={isDark} onClick={() => setOutputItem({ id: 'touch', title: '触摸' })}>触摸</TestButton>
+          <TestButton status={buttonStates.knob} isDark={isDark} onClick={() => setOutputItem({ id: 'knob', title: '旋钮' })}>旋钮</TestButton>
         </div>
 
         {/* 其他 */}
@@ -195,10 +240,10 @@ export function TestPanel({ isDark }: TestPanelProps) {
           <div className="w-24 flex items-center">
             <span className={isDark ? 'text-white' : 'text-neutral-900'}>其他</span>
           </div>
-          <TestButton status={buttonStates.atx} isDark={isDark}>ATX</TestButton>
-          <TestButton status={buttonStates.io} isDark={isDark}>IO</TestButton>
-          <TestButton status={buttonStates.tf_card} isDark={isDark}>TF卡</TestButton>
-          <TestButton status={buttonStates.uart} isDark={isDark}>UART</TestButton>
+          <TestButton status={buttonStates.atx} isDark={isDark} onClick={() => setOutputItem({ id: 'atx', title: 'ATX' })}>ATX</TestButton>
+          <TestButton status={buttonStates.io} isDark={isDark} onClick={() => setOutputItem({ id: 'io', title: 'IO' })}>IO</TestButton>
+          <TestButton status={buttonStates.tf_card} isDark={isDark} onClick={() => setOutputItem({ id: 'tf_card', title: 'TF卡' })}>TF卡</TestButton>
+          <TestButton status={buttonStates.uart} isDark={isDark} onClick={() => setOutputItem({ id: 'uart', title: 'UART' })}>UART</TestButton>
         </div>
       </section>
 
@@ -212,6 +257,14 @@ export function TestPanel({ isDark }: TestPanelProps) {
           <TestButton status={buttonStates.print_error_msg} isDark={isDark}>打印错误</TestButton>
         </div>
       </section>
+
+      {/* 测试项目的命令输出 */}
+      <CommandOutput
+        title={outputItem?.title ?? null}
+        lines={outputItem ? outputs[outputItem.id] ?? [] : []}
+        onClose={() => setOutputItem(null)}
+        isDark={isDark}
+      />
     </main>
   );
 }
\ No newline at end of file